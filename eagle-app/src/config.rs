@@ -1,24 +1,81 @@
+mod cardinality;
+mod cgroups;
+mod cpu;
 mod disks;
+mod elasticsearch;
+mod exec;
 mod file;
+mod filesystems;
+mod fluent;
 mod google;
+mod graphite;
+mod http;
+mod influx;
+mod journal;
+mod kafka;
+mod kmsg;
+mod loki;
+mod network;
+mod processes;
+mod rate;
+mod relabel;
+mod script;
+mod sockets;
+mod syslog;
 mod tags;
+mod tls;
+mod wasm;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use eagle::{
-    sinks::Console,
-    sources::{Disks, File, Load, Memory},
-    transformers::tags::Tags,
+    formats::graphite::Templates,
+    sinks::{Console, Elasticsearch, Graphite, Influx, Kafka, Loki},
+    sources::{
+        Cgroups, Cpu, Disks, Exec, File, Filesystems, FluentForward, GraphiteReceiver,
+        HttpReceiver, InfluxReceiver, Journal, KafkaConsumer, Kmsg, Load, Memory, Network,
+        Pressure, Processes, Sockets, Syslog, Transport, VmStat,
+    },
+    transformers::{
+        cardinality::Cardinality, rate::Rate, relabel::Relabel, script::Script, tags::Tags,
+    },
 };
 use eagle_core::config::{Configuration, SinkConfig, SourceConfig, TransformerConfig};
 use eagle_google::sinks::StackDriverMetrics;
+use eagle_wasm::{WasmSink, WasmSource, WasmTransformer};
 use eyre::{bail, WrapErr};
 use serde::Deserialize;
 use toml::Value;
 
 use crate::config::google::StackDriverMetricsConfig;
 
-use self::{disks::DisksConfig, file::FileConfig, tags::TagsConfig};
+use self::{
+    cardinality::CardinalityConfig,
+    cgroups::CgroupsConfig,
+    cpu::CpuConfig,
+    disks::DisksConfig,
+    elasticsearch::ElasticsearchConfig,
+    exec::ExecConfig,
+    file::FileConfig,
+    filesystems::FilesystemsConfig,
+    fluent::FluentForwardConfig,
+    graphite::{GraphiteReceiverConfig, GraphiteSinkConfig},
+    http::HttpReceiverConfig,
+    influx::{InfluxReceiverConfig, InfluxSinkConfig},
+    journal::JournalConfig,
+    kafka::{KafkaConsumerConfig, KafkaSinkConfig},
+    kmsg::KmsgConfig,
+    loki::LokiConfig,
+    network::NetworkConfig,
+    processes::ProcessesConfig,
+    rate::RateConfig,
+    relabel::RelabelConfig,
+    script::ScriptConfig,
+    sockets::SocketsConfig,
+    syslog::SyslogConfig,
+    tags::TagsConfig,
+    wasm::{WasmSinkConfig, WasmSourceConfig, WasmTransformerConfig},
+};
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
@@ -39,17 +96,89 @@ impl Config {
                 }
 
                 "memory" => {
-                    configure_memory_source(&mut config, definition);
+                    configure_memory_source(&mut config, definition)?;
                 }
 
                 "load" => {
-                    configure_load_source(&mut config, definition);
+                    configure_load_source(&mut config, definition)?;
+                }
+
+                "pressure" => {
+                    configure_pressure_source(&mut config, definition)?;
+                }
+
+                "vmstat" => {
+                    configure_vmstat_source(&mut config, definition)?;
+                }
+
+                "cpu" => {
+                    configure_cpu_source(&mut config, definition)?;
+                }
+
+                "network" => {
+                    configure_network_source(&mut config, definition)?;
+                }
+
+                "sockets" => {
+                    configure_sockets_source(&mut config, definition)?;
+                }
+
+                "filesystems" => {
+                    configure_filesystems_source(&mut config, definition)?;
+                }
+
+                "processes" => {
+                    configure_processes_source(&mut config, definition)?;
+                }
+
+                "cgroups" => {
+                    configure_cgroups_source(&mut config, definition)?;
+                }
+
+                "exec" => {
+                    configure_exec_source(&mut config, definition)?;
                 }
 
                 "file" => {
                     configure_file_source(&mut config, definition)?;
                 }
 
+                "http" => {
+                    configure_http_source(&mut config, definition)?;
+                }
+
+                "fluent" => {
+                    configure_fluent_source(&mut config, definition)?;
+                }
+
+                "graphite" => {
+                    configure_graphite_source(&mut config, definition)?;
+                }
+
+                "influx" => {
+                    configure_influx_source(&mut config, definition)?;
+                }
+
+                "journal" => {
+                    configure_journal_source(&mut config, definition)?;
+                }
+
+                "kafka" => {
+                    configure_kafka_source(&mut config, definition)?;
+                }
+
+                "kmsg" => {
+                    configure_kmsg_source(&mut config, definition)?;
+                }
+
+                "syslog" => {
+                    configure_syslog_source(&mut config, definition)?;
+                }
+
+                "wasm" => {
+                    configure_wasm_source(&mut config, definition)?;
+                }
+
                 unknown => bail!("Unknown source '{}'", unknown),
             }
         }
@@ -60,10 +189,34 @@ impl Config {
                     configure_console_sink(&mut config, definition);
                 }
 
+                "elasticsearch" => {
+                    configure_elasticsearch_sink(&mut config, definition)?;
+                }
+
+                "graphite" => {
+                    configure_graphite_sink(&mut config, definition)?;
+                }
+
+                "influx" => {
+                    configure_influx_sink(&mut config, definition)?;
+                }
+
+                "kafka" => {
+                    configure_kafka_sink(&mut config, definition)?;
+                }
+
+                "loki" => {
+                    configure_loki_sink(&mut config, definition)?;
+                }
+
                 "stackdriver_metrics" => {
                     configure_stackdriver_metrics_sink(&mut config, definition)?;
                 }
 
+                "wasm" => {
+                    configure_wasm_sink(&mut config, definition)?;
+                }
+
                 unknown => bail!("Unknown source '{}'", unknown),
             }
         }
@@ -73,6 +226,27 @@ impl Config {
                 "tags" => {
                     configure_tags_transformer(&mut config, definition)?;
                 }
+
+                "rate" => {
+                    configure_rate_transformer(&mut config, definition)?;
+                }
+
+                "relabel" => {
+                    configure_relabel_transformer(&mut config, definition)?;
+                }
+
+                "cardinality" => {
+                    configure_cardinality_transformer(&mut config, definition)?;
+                }
+
+                "script" => {
+                    configure_script_transformer(&mut config, definition)?;
+                }
+
+                "wasm" => {
+                    configure_wasm_transformer(&mut config, definition)?;
+                }
+
                 unknown => bail!("Unknown transformer '{}'", unknown),
             }
         }
@@ -86,19 +260,190 @@ fn configure_disk_source(
     definition: SourceDefinition,
 ) -> eyre::Result<()> {
     let name = definition.name.clone();
+    let source_config = definition.source_config()?;
     let options = definition.parse_params::<DisksConfig>()?;
 
-    config.register_source(name, SourceConfig::default(), Disks::new(options.disks));
+    config.register_source(name, source_config, Disks::new(options.disks));
+
+    Ok(())
+}
+
+fn configure_memory_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    config.register_source(
+        definition.name.as_str(),
+        definition.source_config()?,
+        Memory,
+    );
+
+    Ok(())
+}
+
+fn configure_load_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    config.register_source(definition.name.as_str(), definition.source_config()?, Load);
 
     Ok(())
 }
 
-fn configure_memory_source(config: &mut Configuration, definition: SourceDefinition) {
-    config.register_source(definition.name.as_str(), SourceConfig::default(), Memory);
+fn configure_pressure_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    config.register_source(
+        definition.name.as_str(),
+        definition.source_config()?,
+        Pressure,
+    );
+
+    Ok(())
 }
 
-fn configure_load_source(config: &mut Configuration, definition: SourceDefinition) {
-    config.register_source(definition.name.as_str(), SourceConfig::default(), Load);
+fn configure_vmstat_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    config.register_source(
+        definition.name.as_str(),
+        definition.source_config()?,
+        VmStat,
+    );
+
+    Ok(())
+}
+
+fn configure_cpu_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<CpuConfig>()?;
+
+    config.register_source(name, source_config, Cpu::new(options.per_core));
+
+    Ok(())
+}
+
+fn configure_network_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<NetworkConfig>()?;
+
+    config.register_source(
+        name,
+        source_config,
+        Network::new(options.includes, options.excludes).include_loopback(options.include_loopback),
+    );
+
+    Ok(())
+}
+
+fn configure_sockets_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<SocketsConfig>()?;
+
+    config.register_source(
+        name,
+        source_config,
+        Sockets::default()
+            .per_port(options.per_port)
+            .ports(options.ports),
+    );
+
+    Ok(())
+}
+
+fn configure_filesystems_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<FilesystemsConfig>()?;
+    let mut source = Filesystems::default()
+        .fs_types(options.fs_types)
+        .ignored_mount_points(options.ignored_mount_points)
+        .statvfs_timeout(Duration::from_secs(options.statvfs_timeout_in_secs));
+
+    if let Some(ignored_fs_types) = options.ignored_fs_types {
+        source = source.ignored_fs_types(ignored_fs_types);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_processes_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<ProcessesConfig>()?;
+    let matchers = options
+        .groups
+        .into_iter()
+        .map(|group| group.into_matcher())
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    config.register_source(name, source_config, Processes::new(matchers)?);
+
+    Ok(())
+}
+
+fn configure_cgroups_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<CgroupsConfig>()?;
+    let mut source = Cgroups::default()
+        .max_depth(options.max_depth)
+        .includes(options.includes)?;
+
+    if let Some(root) = options.root {
+        source = source.root(root);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_exec_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<ExecConfig>()?;
+    let mut source = Exec::new(options.command, options.format)
+        .args(options.args)
+        .env(options.env)
+        .working_dir(options.working_dir)
+        .timeout(Duration::from_secs(options.timeout_in_secs));
+
+    if let Some(category) = options.category {
+        source = source.category(category);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
 }
 
 fn configure_file_source(
@@ -106,19 +451,341 @@ fn configure_file_source(
     definition: SourceDefinition,
 ) -> eyre::Result<()> {
     let name = definition.name.clone();
+    let source_config = definition.source_config()?;
     let options = definition.parse_params::<FileConfig>()?;
 
     config.register_source(
         name,
-        SourceConfig::default(),
+        source_config,
         File::new(options.filepath, options.codec),
     );
 
     Ok(())
 }
 
+fn configure_http_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<HttpReceiverConfig>()?;
+    let mut source = HttpReceiver::new(options.address);
+
+    if let Some(token) = options.bearer_token {
+        source = source.bearer_token(token);
+    }
+
+    if let Some(max_body_size) = options.max_body_size {
+        source = source.max_body_size(max_body_size);
+    }
+
+    if let Some(category) = options.category {
+        source = source.category(category);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_fluent_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<FluentForwardConfig>()?;
+    let mut source = FluentForward::new(options.address);
+
+    if let Some(tls) = options.tls {
+        source = source.tls(tls.cert_path, tls.key_path)?;
+    }
+
+    if let Some(max_message_size) = options.max_message_size {
+        source = source.max_message_size(max_message_size);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_graphite_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<GraphiteReceiverConfig>()?;
+    let mut source = GraphiteReceiver::new(options.address, options.transport)
+        .templates(Templates::parse(options.templates)?);
+
+    if let Some(max_message_size) = options.max_message_size {
+        source = source.max_message_size(max_message_size);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_influx_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<InfluxReceiverConfig>()?;
+    let mut source =
+        InfluxReceiver::new(options.address, options.protocol).precision(options.precision);
+
+    if let Some(token) = options.token {
+        source = source.token(token);
+    }
+
+    if let Some(max_body_size) = options.max_body_size {
+        source = source.max_body_size(max_body_size);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_journal_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<JournalConfig>()?;
+    let mut source = Journal::default()
+        .units(options.units)
+        .matches(options.matches);
+
+    if let Some(directory) = options.directory {
+        source = source.directory(directory);
+    }
+
+    if let Some(priority) = options.priority {
+        source = source.max_priority(priority)?;
+    }
+
+    if let Some(cursor_path) = options.cursor_path {
+        source = source.cursor_path(cursor_path);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_kafka_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<KafkaConsumerConfig>()?;
+    let mut source =
+        KafkaConsumer::new(options.brokers.join(","), options.group_id, options.topics)
+            .payload(options.payload)
+            .encoding(options.encoding)
+            .offset_reset(options.offset_reset)
+            .options(options.options);
+
+    if let Some(category) = options.category {
+        source = source.category(category);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_kmsg_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<KmsgConfig>()?;
+    let mut source = Kmsg::default().read_existing(options.read_existing);
+
+    if let Some(path) = options.path {
+        source = source.path(path);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_syslog_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<SyslogConfig>()?;
+    let mut source = Syslog::new(options.address, options.transport);
+
+    if let Some(tls) = options.tls {
+        if let Transport::Udp = options.transport {
+            bail!("Syslog source '{}': TLS requires the TCP transport", name);
+        }
+
+        source = source.tls(tls.cert_path, tls.key_path)?;
+    }
+
+    if let Some(max_message_size) = options.max_message_size {
+        source = source.max_message_size(max_message_size);
+    }
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
+fn configure_wasm_source(
+    config: &mut Configuration,
+    definition: SourceDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let source_config = definition.source_config()?;
+    let options = definition.parse_params::<WasmSourceConfig>()?;
+    let source = WasmSource::new(name.as_str(), options.plugin.into_options())?;
+
+    config.register_source(name, source_config, source);
+
+    Ok(())
+}
+
 fn configure_console_sink(config: &mut Configuration, definition: SinkDefinition) {
     config.register_sink(definition.name.as_str(), SinkConfig::default(), Console);
+    config.register_log_sink(definition.name.as_str(), Console);
+}
+
+fn configure_elasticsearch_sink(
+    config: &mut Configuration,
+    definition: SinkDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<ElasticsearchConfig>()?;
+    let mut sink = Elasticsearch::new(params.url)
+        .action(params.action)
+        .batch_size(params.batch_size)
+        .max_batch_bytes(params.max_batch_bytes)
+        .period(Duration::from_secs(params.period_in_secs))
+        .retries(params.retries);
+
+    if let Some(index) = params.index {
+        sink = sink.index(index)?;
+    }
+
+    match (params.basic_auth, params.api_key) {
+        (Some(_), Some(_)) => bail!("Sink '{}' can't use both basic_auth and api_key", name),
+        (Some(auth), None) => sink = sink.basic_auth(auth.username, auth.password),
+        (None, Some(api_key)) => sink = sink.api_key(api_key),
+        (None, None) => {}
+    }
+
+    config.register_log_sink(name, sink);
+
+    Ok(())
+}
+
+fn configure_graphite_sink(
+    config: &mut Configuration,
+    definition: SinkDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<GraphiteSinkConfig>()?;
+    let mut sink = Graphite::new(params.address)
+        .templates(Templates::parse(params.templates)?)
+        .tagged(params.tagged);
+
+    if let Some(buffer_size) = params.buffer_size {
+        sink = sink.buffer_size(buffer_size);
+    }
+
+    config.register_sink(name, SinkConfig::default(), sink);
+
+    Ok(())
+}
+
+fn configure_influx_sink(
+    config: &mut Configuration,
+    definition: SinkDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<InfluxSinkConfig>()?;
+    let mut sink = Influx::new(params.url)
+        .batch_size(params.batch_size)
+        .period(Duration::from_secs(params.period_in_secs))
+        .gzip(params.gzip)
+        .retries(params.retries);
+
+    if let Some(token) = params.token {
+        sink = sink.token(token);
+    }
+
+    config.register_sink(name, SinkConfig::default(), sink);
+
+    Ok(())
+}
+
+/// Registers the sink for both metrics and logs.
+fn configure_kafka_sink(
+    config: &mut Configuration,
+    definition: SinkDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<KafkaSinkConfig>()?;
+    let mut sink = Kafka::new(params.brokers.join(","))
+        .key_tags(params.key_tags)
+        .encoding(params.encoding)
+        .compression(params.compression)
+        .acks(params.acks)
+        .timeout(Duration::from_secs(params.timeout_in_secs))
+        .options(params.options);
+
+    if let Some(topic) = params.topic {
+        sink = sink.topic(topic)?;
+    }
+
+    let sink = sink.build_producer()?;
+
+    config.register_sink(name.as_str(), SinkConfig::default(), sink.clone());
+    config.register_log_sink(name, sink);
+
+    Ok(())
+}
+
+fn configure_loki_sink(config: &mut Configuration, definition: SinkDefinition) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<LokiConfig>()?;
+    let mut sink = Loki::new(params.url)
+        .encoding(params.encoding)
+        .labels(params.labels)
+        .label_fields(params.label_fields)
+        .out_of_order(params.out_of_order)
+        .batch_size(params.batch_size)
+        .period(Duration::from_secs(params.period_in_secs))
+        .retries(params.retries);
+
+    if let Some(tenant_id) = params.tenant_id {
+        sink = sink.tenant_id(tenant_id);
+    }
+
+    if let Some(max_label_values) = params.max_label_values {
+        sink = sink.max_label_values(max_label_values);
+    }
+
+    config.register_log_sink(name, sink);
+
+    Ok(())
 }
 
 fn configure_stackdriver_metrics_sink(
@@ -137,6 +804,21 @@ fn configure_stackdriver_metrics_sink(
     Ok(())
 }
 
+fn configure_wasm_sink(config: &mut Configuration, definition: SinkDefinition) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<WasmSinkConfig>()?;
+    let sink = WasmSink::new(
+        name.as_str(),
+        params.plugin.into_options(),
+        params.batch_size,
+        Duration::from_secs(params.period_in_secs),
+    )?;
+
+    config.register_sink(name, SinkConfig::default(), sink);
+
+    Ok(())
+}
+
 fn configure_tags_transformer(
     config: &mut Configuration,
     definition: TransformerDefinition,
@@ -149,14 +831,115 @@ fn configure_tags_transformer(
     Ok(())
 }
 
+fn configure_rate_transformer(
+    config: &mut Configuration,
+    definition: TransformerDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<RateConfig>()?;
+    let mut rate = Rate::new(params.mode)
+        .metrics(params.metrics)
+        .wraparound(params.wraparound)
+        .suffix(params.suffix);
+
+    if let Some(stale_after) = params.stale_after {
+        rate = rate.stale_after(stale_after);
+    }
+
+    config.register_transformer(name, TransformerConfig::default(), rate);
+
+    Ok(())
+}
+
+fn configure_relabel_transformer(
+    config: &mut Configuration,
+    definition: TransformerDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<RelabelConfig>()?;
+    let rules = params
+        .rules
+        .into_iter()
+        .map(|rule| rule.into_rule())
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    config.register_transformer(name, TransformerConfig::default(), Relabel::new(rules));
+
+    Ok(())
+}
+
+fn configure_cardinality_transformer(
+    config: &mut Configuration,
+    definition: TransformerDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<CardinalityConfig>()?;
+
+    config.register_transformer(
+        name,
+        TransformerConfig::default(),
+        Cardinality::new(params.limit, params.action)
+            .report_interval(Duration::from_secs(params.report_interval_in_secs)),
+    );
+
+    Ok(())
+}
+
+fn configure_script_transformer(
+    config: &mut Configuration,
+    definition: TransformerDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<ScriptConfig>()?;
+    let (script_name, source) = params.load_source(name.as_str())?;
+
+    config.register_transformer(
+        name,
+        TransformerConfig::default(),
+        Script::new(script_name, source, params.limits())?,
+    );
+
+    Ok(())
+}
+
+fn configure_wasm_transformer(
+    config: &mut Configuration,
+    definition: TransformerDefinition,
+) -> eyre::Result<()> {
+    let name = definition.name.clone();
+    let params = definition.parse_params::<WasmTransformerConfig>()?;
+    let transformer = WasmTransformer::new(name.as_str(), params.plugin.into_options())?;
+
+    config.register_transformer(name, TransformerConfig::default(), transformer);
+
+    Ok(())
+}
+
 #[derive(Deserialize, Debug)]
 pub struct SourceDefinition {
     pub name: String,
+    /// Only used by polling sources.
+    pub interval_in_secs: Option<u64>,
+    /// Only used by polling sources.
+    pub jitter_in_secs: Option<u64>,
     #[serde(flatten)]
     pub params: Value,
 }
 
 impl SourceDefinition {
+    pub fn source_config(&self) -> eyre::Result<SourceConfig> {
+        let config = SourceConfig::default().jitter(self.jitter_in_secs.map(Duration::from_secs));
+
+        match self.interval_in_secs {
+            Some(0) => bail!(
+                "Source '{}': interval_in_secs must be greater than 0",
+                self.name
+            ),
+            Some(secs) => Ok(config.interval(Duration::from_secs(secs))),
+            None => Ok(config),
+        }
+    }
+
     pub fn parse_params<'de, P>(self) -> eyre::Result<P>
     where
         P: Deserialize<'de>,
@@ -0,0 +1,21 @@
+use eagle::transformers::cardinality::LimitAction;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct CardinalityConfig {
+    pub limit: usize,
+
+    #[serde(default = "default_action")]
+    pub action: LimitAction,
+
+    #[serde(default = "default_report_interval_in_secs")]
+    pub report_interval_in_secs: u64,
+}
+
+fn default_action() -> LimitAction {
+    LimitAction::Drop
+}
+
+fn default_report_interval_in_secs() -> u64 {
+    60
+}
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct CgroupsConfig {
+    /// Defaults to `/sys/fs/cgroup` when not set.
+    pub root: Option<PathBuf>,
+
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+
+    #[serde(default)]
+    pub includes: Vec<String>,
+}
+
+fn default_max_depth() -> usize {
+    3
+}
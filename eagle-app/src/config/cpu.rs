@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct CpuConfig {
+    #[serde(default)]
+    pub per_core: bool,
+}
@@ -0,0 +1,53 @@
+use eagle::sinks::elasticsearch::Action;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct BasicAuthConfig {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ElasticsearchConfig {
+    /// Cluster address, like `http://localhost:9200`.
+    pub url: String,
+
+    /// Like `logs-{tag}-%Y.%m.%d`, `{tag}` being a metadata or log field.
+    pub index: Option<String>,
+
+    #[serde(default)]
+    pub action: Action,
+
+    pub basic_auth: Option<BasicAuthConfig>,
+
+    /// Base64 encoded `id:api_key`.
+    pub api_key: Option<String>,
+
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+
+    #[serde(default = "default_max_batch_bytes")]
+    pub max_batch_bytes: usize,
+
+    #[serde(default = "default_period_in_secs")]
+    pub period_in_secs: u64,
+
+    #[serde(default = "default_retries")]
+    pub retries: usize,
+}
+
+fn default_batch_size() -> usize {
+    500
+}
+
+fn default_max_batch_bytes() -> usize {
+    5 * 1_024 * 1_024
+}
+
+fn default_period_in_secs() -> u64 {
+    1
+}
+
+fn default_retries() -> usize {
+    3
+}
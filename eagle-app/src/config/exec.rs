@@ -0,0 +1,28 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use eagle::sources::OutputFormat;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct ExecConfig {
+    pub command: String,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    pub working_dir: Option<PathBuf>,
+
+    #[serde(default = "default_timeout_in_secs")]
+    pub timeout_in_secs: u64,
+
+    pub format: OutputFormat,
+
+    pub category: Option<String>,
+}
+
+fn default_timeout_in_secs() -> u64 {
+    10
+}
@@ -0,0 +1,20 @@
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct FilesystemsConfig {
+    #[serde(default)]
+    pub fs_types: Vec<String>,
+
+    /// Defaults to common pseudo filesystems when not set.
+    pub ignored_fs_types: Option<Vec<String>>,
+
+    #[serde(default)]
+    pub ignored_mount_points: Vec<String>,
+
+    #[serde(default = "default_statvfs_timeout_in_secs")]
+    pub statvfs_timeout_in_secs: u64,
+}
+
+fn default_statvfs_timeout_in_secs() -> u64 {
+    5
+}
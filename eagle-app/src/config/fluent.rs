@@ -0,0 +1,12 @@
+use serde::Deserialize;
+
+use super::tls::TlsConfig;
+
+#[derive(Deserialize)]
+pub struct FluentForwardConfig {
+    pub address: String,
+
+    pub tls: Option<TlsConfig>,
+
+    pub max_message_size: Option<usize>,
+}
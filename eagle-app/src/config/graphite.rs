@@ -0,0 +1,29 @@
+use eagle::sources::Transport;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct GraphiteReceiverConfig {
+    pub address: String,
+
+    pub transport: Transport,
+
+    /// Rules like `[filter] template [tag=value,...]`, tried in order.
+    #[serde(default)]
+    pub templates: Vec<String>,
+
+    pub max_message_size: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphiteSinkConfig {
+    pub address: String,
+
+    #[serde(default)]
+    pub templates: Vec<String>,
+
+    /// Appends tags missing from the path as Graphite 1.1 tags.
+    #[serde(default)]
+    pub tagged: bool,
+
+    pub buffer_size: Option<usize>,
+}
@@ -0,0 +1,14 @@
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct HttpReceiverConfig {
+    pub address: String,
+
+    /// When set, requests must carry `Authorization: Bearer <token>`.
+    pub bearer_token: Option<String>,
+
+    pub max_body_size: Option<usize>,
+
+    /// Category of pushed metrics that don't carry one. Defaults to `http`.
+    pub category: Option<String>,
+}
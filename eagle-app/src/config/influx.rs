@@ -0,0 +1,53 @@
+use eagle::{formats::influx::Precision, sources::influx::Protocol};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct InfluxReceiverConfig {
+    pub address: String,
+
+    pub protocol: Protocol,
+
+    #[serde(default)]
+    pub precision: Precision,
+
+    /// Only used by the HTTP protocol.
+    pub token: Option<String>,
+
+    pub max_body_size: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct InfluxSinkConfig {
+    /// Write endpoint, including the database or bucket query parameters.
+    pub url: String,
+
+    pub token: Option<String>,
+
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+
+    #[serde(default = "default_period_in_secs")]
+    pub period_in_secs: u64,
+
+    #[serde(default = "default_gzip")]
+    pub gzip: bool,
+
+    #[serde(default = "default_retries")]
+    pub retries: usize,
+}
+
+fn default_batch_size() -> usize {
+    1_000
+}
+
+fn default_period_in_secs() -> u64 {
+    10
+}
+
+fn default_gzip() -> bool {
+    true
+}
+
+fn default_retries() -> usize {
+    3
+}
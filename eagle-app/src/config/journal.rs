@@ -0,0 +1,21 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct JournalConfig {
+    /// Defaults to `/var/log/journal` when not set.
+    pub directory: Option<PathBuf>,
+
+    #[serde(default)]
+    pub units: Vec<String>,
+
+    /// Either a syslog severity name like `warning` or its number.
+    pub priority: Option<String>,
+
+    #[serde(default)]
+    pub matches: HashMap<String, String>,
+
+    /// Without it, the source starts from the end of the journal on every restart.
+    pub cursor_path: Option<PathBuf>,
+}
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use eagle::{
+    formats::kafka::Encoding,
+    sinks::kafka::{Acks, Compression},
+    sources::kafka::{OffsetReset, Payload},
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct KafkaConsumerConfig {
+    /// `host:port` addresses of the bootstrap brokers.
+    pub brokers: Vec<String>,
+
+    pub group_id: String,
+
+    pub topics: Vec<String>,
+
+    #[serde(default)]
+    pub payload: Payload,
+
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    /// Category of JSON metrics that don't have one.
+    pub category: Option<String>,
+
+    #[serde(default)]
+    pub offset_reset: OffsetReset,
+
+    /// Extra librdkafka settings.
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+pub struct KafkaSinkConfig {
+    /// `host:port` addresses of the bootstrap brokers.
+    pub brokers: Vec<String>,
+
+    /// Topic template, like `eagle.{category}`.
+    pub topic: Option<String>,
+
+    /// Tags, or metadata fields for logs, whose values make the record key.
+    #[serde(default)]
+    pub key_tags: Vec<String>,
+
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    #[serde(default)]
+    pub compression: Compression,
+
+    #[serde(default)]
+    pub acks: Acks,
+
+    #[serde(default = "default_timeout_in_secs")]
+    pub timeout_in_secs: u64,
+
+    /// Extra librdkafka settings.
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+}
+
+fn default_timeout_in_secs() -> u64 {
+    30
+}
@@ -0,0 +1,12 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct KmsgConfig {
+    /// Defaults to `/dev/kmsg` when not set.
+    pub path: Option<PathBuf>,
+
+    #[serde(default)]
+    pub read_existing: bool,
+}
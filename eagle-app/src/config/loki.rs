@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+
+use eagle::sinks::loki::{Encoding, OutOfOrder};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct LokiConfig {
+    /// Push endpoint, like `http://localhost:3100/loki/api/v1/push`.
+    pub url: String,
+
+    pub tenant_id: Option<String>,
+
+    #[serde(default)]
+    pub encoding: Encoding,
+
+    /// Static labels added to every stream.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+
+    /// Metadata fields turned into labels.
+    #[serde(default)]
+    pub label_fields: Vec<String>,
+
+    pub max_label_values: Option<usize>,
+
+    #[serde(default)]
+    pub out_of_order: OutOfOrder,
+
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+
+    #[serde(default = "default_period_in_secs")]
+    pub period_in_secs: u64,
+
+    #[serde(default = "default_retries")]
+    pub retries: usize,
+}
+
+fn default_batch_size() -> usize {
+    1_000
+}
+
+fn default_period_in_secs() -> u64 {
+    1
+}
+
+fn default_retries() -> usize {
+    3
+}
@@ -0,0 +1,13 @@
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct NetworkConfig {
+    #[serde(default)]
+    pub includes: Vec<String>,
+
+    #[serde(default)]
+    pub excludes: Vec<String>,
+
+    #[serde(default)]
+    pub include_loopback: bool,
+}
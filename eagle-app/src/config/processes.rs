@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use eagle::sources::ProcessMatcher;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct ProcessesConfig {
+    pub groups: Vec<ProcessGroupConfig>,
+}
+
+#[derive(Deserialize)]
+pub struct ProcessGroupConfig {
+    pub group: String,
+
+    pub name: Option<String>,
+
+    pub cmdline: Option<String>,
+
+    pub user: Option<String>,
+
+    pub pid_file: Option<PathBuf>,
+
+    #[serde(default)]
+    pub rollup: bool,
+}
+
+impl ProcessGroupConfig {
+    pub fn into_matcher(self) -> eyre::Result<ProcessMatcher> {
+        let mut matcher = ProcessMatcher::new(self.group).rollup(self.rollup);
+
+        if let Some(name) = self.name {
+            matcher = matcher.name(name)?;
+        }
+
+        if let Some(cmdline) = self.cmdline {
+            matcher = matcher.cmdline(cmdline)?;
+        }
+
+        if let Some(user) = self.user {
+            matcher = matcher.user(user);
+        }
+
+        if let Some(pid_file) = self.pid_file {
+            matcher = matcher.pid_file(pid_file);
+        }
+
+        Ok(matcher)
+    }
+}
@@ -0,0 +1,17 @@
+use eagle::transformers::rate::RateMode;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct RateConfig {
+    pub mode: RateMode,
+
+    #[serde(default)]
+    pub metrics: Vec<String>,
+
+    pub wraparound: Option<f64>,
+
+    pub suffix: Option<String>,
+
+    /// Number of intervals after which a series that stopped reporting is forgotten.
+    pub stale_after: Option<u32>,
+}
@@ -0,0 +1,69 @@
+use eagle::transformers::relabel::{RelabelAction, RelabelRule};
+use eyre::bail;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct RelabelConfig {
+    pub rules: Vec<RelabelRuleConfig>,
+}
+
+#[derive(Deserialize)]
+pub struct RelabelRuleConfig {
+    pub action: RelabelAction,
+
+    #[serde(default)]
+    pub source_labels: Vec<String>,
+
+    pub separator: Option<String>,
+
+    pub regex: Option<String>,
+
+    pub target_label: Option<String>,
+
+    pub replacement: Option<String>,
+
+    pub modulus: Option<u64>,
+}
+
+impl RelabelRuleConfig {
+    pub fn into_rule(self) -> eyre::Result<RelabelRule> {
+        let writes_label = matches!(
+            self.action,
+            RelabelAction::Replace | RelabelAction::Lowercase | RelabelAction::HashMod
+        );
+
+        if writes_label && self.target_label.is_none() {
+            bail!(
+                "Relabel rules with the replace, lowercase or hashmod action need a 'target_label'"
+            );
+        }
+
+        if self.action == RelabelAction::HashMod && self.modulus.unwrap_or(0) == 0 {
+            bail!("Relabel rules with the hashmod action need a 'modulus' greater than 0");
+        }
+
+        let mut rule = RelabelRule::new(self.action).source_labels(self.source_labels);
+
+        if let Some(separator) = self.separator {
+            rule = rule.separator(separator);
+        }
+
+        if let Some(regex) = self.regex {
+            rule = rule.regex(regex)?;
+        }
+
+        if let Some(target_label) = self.target_label {
+            rule = rule.target_label(target_label);
+        }
+
+        if let Some(replacement) = self.replacement {
+            rule = rule.replacement(replacement);
+        }
+
+        if let Some(modulus) = self.modulus {
+            rule = rule.modulus(modulus);
+        }
+
+        Ok(rule)
+    }
+}
@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use eagle::transformers::script::ScriptLimits;
+use eyre::{bail, WrapErr};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct ScriptConfig {
+    pub source: Option<String>,
+
+    pub path: Option<PathBuf>,
+
+    pub max_instructions: Option<u64>,
+
+    pub max_memory: Option<usize>,
+}
+
+impl ScriptConfig {
+    /// Returns the script name used in error reports and its source code.
+    pub fn load_source(&self, name: &str) -> eyre::Result<(String, String)> {
+        match (self.source.as_ref(), self.path.as_ref()) {
+            (Some(source), None) => Ok((name.to_string(), source.clone())),
+            (None, Some(path)) => {
+                let source = std::fs::read_to_string(path)
+                    .wrap_err_with(|| format!("Error when reading script file {:?}", path))?;
+
+                Ok((path.to_string_lossy().to_string(), source))
+            }
+            _ => bail!(
+                "Script transformer '{}' needs either 'source' or 'path'",
+                name
+            ),
+        }
+    }
+
+    pub fn limits(&self) -> ScriptLimits {
+        let mut limits = ScriptLimits::default();
+
+        if let Some(max_instructions) = self.max_instructions {
+            limits.max_instructions = max_instructions;
+        }
+
+        if let Some(max_memory) = self.max_memory {
+            limits.max_memory = max_memory;
+        }
+
+        limits
+    }
+}
@@ -0,0 +1,10 @@
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct SocketsConfig {
+    #[serde(default)]
+    pub per_port: bool,
+
+    #[serde(default)]
+    pub ports: Vec<u16>,
+}
@@ -0,0 +1,16 @@
+use eagle::sources::Transport;
+use serde::Deserialize;
+
+use super::tls::TlsConfig;
+
+#[derive(Deserialize)]
+pub struct SyslogConfig {
+    pub address: String,
+
+    pub transport: Transport,
+
+    /// Only supported by the TCP transport.
+    pub tls: Option<TlsConfig>,
+
+    pub max_message_size: Option<usize>,
+}
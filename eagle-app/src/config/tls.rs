@@ -0,0 +1,10 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+/// PEM encoded certificate chain and private key.
+#[derive(Deserialize)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use eagle_wasm::PluginOptions;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct PluginConfig {
+    pub path: PathBuf,
+
+    pub fuel: Option<u64>,
+
+    pub max_memory: Option<usize>,
+}
+
+impl PluginConfig {
+    pub fn into_options(self) -> PluginOptions {
+        let mut options = PluginOptions::new(self.path);
+
+        if let Some(fuel) = self.fuel {
+            options = options.fuel(fuel);
+        }
+
+        if let Some(max_memory) = self.max_memory {
+            options = options.max_memory(max_memory);
+        }
+
+        options
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WasmSourceConfig {
+    #[serde(flatten)]
+    pub plugin: PluginConfig,
+}
+
+#[derive(Deserialize)]
+pub struct WasmTransformerConfig {
+    #[serde(flatten)]
+    pub plugin: PluginConfig,
+}
+
+#[derive(Deserialize)]
+pub struct WasmSinkConfig {
+    #[serde(flatten)]
+    pub plugin: PluginConfig,
+
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+
+    #[serde(default = "default_period_in_secs")]
+    pub period_in_secs: u64,
+}
+
+fn default_batch_size() -> usize {
+    200
+}
+
+fn default_period_in_secs() -> u64 {
+    10
+}
@@ -1,4 +1,8 @@
-use crate::{MetricFilter, MetricSink, Origin, Source, Transformer};
+use std::time::Duration;
+
+use crate::{
+    poll::DEFAULT_INTERVAL, LogSink, MetricFilter, MetricSink, Origin, Source, Transformer,
+};
 
 pub struct SinkConfig {
     pub filter: MetricFilter,
@@ -36,8 +40,40 @@ pub struct SinkDecl {
     pub sink: Box<dyn MetricSink + Send + 'static>,
 }
 
-#[derive(Default)]
-pub struct SourceConfig {}
+/// Log sinks receive every log.
+pub struct LogSinkDecl {
+    pub origin: Origin,
+    pub sink: Box<dyn LogSink + Send + 'static>,
+}
+
+pub struct SourceConfig {
+    pub interval: Duration,
+    /// Upper bound of a random delay applied before the first collection, so a fleet of agents
+    /// started at the same time doesn't collect in lockstep.
+    pub jitter: Option<Duration>,
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            jitter: None,
+        }
+    }
+}
+
+impl SourceConfig {
+    /// Panics if `interval` is zero.
+    pub fn interval(self, interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "A source interval can't be zero");
+
+        Self { interval, ..self }
+    }
+
+    pub fn jitter(self, jitter: Option<Duration>) -> Self {
+        Self { jitter, ..self }
+    }
+}
 
 pub struct SourceDecl {
     pub origin: Origin,
@@ -49,6 +85,7 @@ pub struct SourceDecl {
 pub struct Configuration {
     pub sources: Vec<SourceDecl>,
     pub sinks: Vec<SinkDecl>,
+    pub log_sinks: Vec<LogSinkDecl>,
     pub transformers: Vec<TransformerDecl>,
 }
 
@@ -83,6 +120,16 @@ impl Configuration {
         });
     }
 
+    pub fn register_log_sink<S>(&mut self, name: impl AsRef<str>, sink: S)
+    where
+        S: LogSink + Send + 'static,
+    {
+        self.log_sinks.push(LogSinkDecl {
+            origin: Origin::new(name),
+            sink: Box::new(sink),
+        });
+    }
+
     pub fn register_transformer<T>(
         &mut self,
         name: impl AsRef<str>,
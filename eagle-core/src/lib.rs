@@ -1,7 +1,9 @@
 pub mod config;
+pub mod poll;
 
 use chrono::{DateTime, Utc};
 use eyre::WrapErr;
+use poll::Schedule;
 use serde::Serialize;
 use serde_json::Value;
 use std::{collections::BTreeMap, sync::Arc};
@@ -67,9 +69,11 @@ impl EagleEndpoint {
     }
 }
 
+#[derive(Clone)]
 pub struct EagleClient {
     pub origin: Arc<Origin>,
     pub endpoint: EagleEndpoint,
+    pub schedule: Schedule,
 }
 
 impl EagleClient {
@@ -144,6 +148,8 @@ pub enum Event {
 pub enum MetricType {
     Counter,
     Gauge,
+    /// Counter that only carries the change since its previous value.
+    Delta,
 }
 
 /// We should have Metric and Runtime related metric info like
@@ -203,10 +209,25 @@ impl MetricBuilder {
         self
     }
 
+    pub fn delta(category: impl AsRef<str>, name: impl AsRef<str>, value: f64) -> Self {
+        Self {
+            name: name.as_ref().to_string(),
+            value,
+            r#type: MetricType::Delta,
+            category: category.as_ref().to_string(),
+            tags: Default::default(),
+            timestamp: Utc::now(),
+        }
+    }
+
     pub fn tags(self, tags: BTreeMap<String, String>) -> Self {
         Self { tags, ..self }
     }
 
+    pub fn timestamp(self, timestamp: DateTime<Utc>) -> Self {
+        Self { timestamp, ..self }
+    }
+
     pub fn build(self) -> Metric {
         Metric {
             name: self.name,
@@ -225,6 +246,12 @@ pub struct MetricEvent {
     pub metric: Arc<Metric>,
 }
 
+#[derive(Clone)]
+pub struct LogEvent {
+    pub origin: Arc<Origin>,
+    pub log: Arc<Log>,
+}
+
 pub struct MetricFilter {
     inner: Box<dyn Fn(&Origin, &Metric) -> bool + Send + Sync>,
 }
@@ -340,6 +367,15 @@ pub trait MetricSink {
     ) -> eyre::Result<()>;
 }
 
+#[async_trait::async_trait]
+pub trait LogSink {
+    async fn process(
+        &mut self,
+        origin: Arc<Origin>,
+        stream: EagleStream<LogEvent>,
+    ) -> eyre::Result<()>;
+}
+
 #[async_trait::async_trait]
 pub trait Source {
     async fn produce(&mut self, client: EagleClient) -> eyre::Result<()>;
@@ -347,4 +383,16 @@ pub trait Source {
 
 pub trait Transformer {
     fn transform(&mut self, origin: Arc<Origin>, metric: Metric) -> Option<Metric>;
+
+    /// Used by the engine. Transformers able to emit more than one metric out of
+    /// a single one override it.
+    fn transform_many(&mut self, origin: Arc<Origin>, metric: Metric) -> Vec<Metric> {
+        self.transform(origin, metric).into_iter().collect()
+    }
+
+    /// Used by the engine for logs. Transformers only dealing with metrics let
+    /// logs through untouched.
+    fn transform_log(&mut self, _origin: Arc<Origin>, log: Log) -> Vec<Log> {
+        vec![log]
+    }
 }
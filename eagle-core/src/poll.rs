@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::time::{Interval, MissedTickBehavior};
+
+use crate::{EagleClient, Metric, Source};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// When a polling source collects its metrics.
+#[derive(Clone, Copy, Debug)]
+pub struct Schedule {
+    pub interval: Duration,
+    pub jitter: Option<Duration>,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            jitter: None,
+        }
+    }
+}
+
+impl Schedule {
+    pub fn ticker(&self) -> Ticker {
+        Ticker {
+            interval: self.interval,
+            jitter: self.jitter,
+            clock: None,
+        }
+    }
+}
+
+/// Completes its first tick after a random delay up to the schedule jitter, then every interval.
+pub struct Ticker {
+    interval: Duration,
+    jitter: Option<Duration>,
+    clock: Option<Interval>,
+}
+
+impl Ticker {
+    pub async fn tick(&mut self) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.tick().await;
+            return;
+        }
+
+        if let Some(jitter) = self.jitter {
+            let delay = rand::thread_rng().gen_range(0..=jitter.as_millis() as u64);
+
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+
+        let mut clock = tokio::time::interval(self.interval);
+
+        // A slow collection shouldn't trigger a burst of catch-up collections.
+        clock.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        clock.tick().await;
+        self.clock = Some(clock);
+    }
+}
+
+/// Sources sampling metrics at a regular interval. The collection loop is handled for them,
+/// following the schedule set in their `SourceConfig`.
+#[async_trait::async_trait]
+pub trait PollingSource {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>>;
+}
+
+#[async_trait::async_trait]
+impl<P> Source for P
+where
+    P: PollingSource + Send,
+{
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        let mut ticker = client.schedule.ticker();
+
+        loop {
+            ticker.tick().await;
+
+            let metrics = self.poll().await?;
+
+            if !metrics.is_empty() {
+                client.send_metrics(metrics).await?;
+            }
+        }
+    }
+}
@@ -164,10 +164,12 @@ impl MetricSink for StackDriverMetrics {
                     let metric_kind = match metric.r#type {
                         MetricType::Gauge => MetricKind::Gauge,
                         MetricType::Counter => MetricKind::Cumulative,
+                        // Custom metrics can't be written as DELTA, each point is reported as is.
+                        MetricType::Delta => MetricKind::Gauge,
                     };
 
                     let end_time = crate::to_timestamp(metric.timestamp);
-                    let start_time = if metric.r#type == MetricType::Gauge {
+                    let start_time = if metric.r#type != MetricType::Counter {
                         end_time.clone()
                     } else {
                         crate::to_timestamp(started.time())
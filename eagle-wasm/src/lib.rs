@@ -0,0 +1,42 @@
+//! WebAssembly plugins for eagle.
+//!
+//! Plugins are core WebAssembly modules without WASI, so they can only reach
+//! what the host gives them. Every call runs with a bounded amount of fuel and
+//! memory can't grow past the configured limit.
+//!
+//! # ABI version 1
+//!
+//! Data crosses the boundary as UTF-8 JSON in the plugin linear memory. A
+//! metric is an object with `name`, `value`, `type` (`counter`, `gauge` or
+//! `delta`), `category`, `tags` and `timestamp` (milliseconds since epoch,
+//! optional when produced by a plugin). Buffers returned by the plugin are
+//! packed in a `i64` as `(ptr << 32) | len`, `0` meaning no data.
+//!
+//! Every plugin exports:
+//! * `memory`
+//! * `eagle_abi_version() -> i32`, returning `1`
+//! * `eagle_alloc(len: i32) -> i32`
+//! * `eagle_dealloc(ptr: i32, len: i32)`
+//!
+//! Then depending on the component:
+//! * Transformer: `eagle_transform(ptr: i32, len: i32) -> i64`, takes a metric
+//!   and returns an array of metrics. An empty array drops the metric.
+//! * Source: `eagle_poll() -> i64`, called on every tick and returns an array of
+//!   metrics.
+//! * Sink: `eagle_flush(ptr: i32, len: i32) -> i32`, takes an array of metrics
+//!   and returns `0` on success.
+//!
+//! The host owns buffers it allocated through `eagle_alloc` and frees buffers
+//! returned by the plugin once read. Plugins can import
+//! `eagle.log(level: i32, ptr: i32, len: i32)` to log a message, levels going
+//! from `0` (trace) to `4` (error).
+mod plugin;
+mod sink;
+mod source;
+mod transformer;
+mod wire;
+
+pub use plugin::PluginOptions;
+pub use sink::WasmSink;
+pub use source::WasmSource;
+pub use transformer::WasmTransformer;
@@ -0,0 +1,473 @@
+use std::path::{Path, PathBuf};
+
+use eyre::{bail, eyre, WrapErr};
+use serde::{de::DeserializeOwned, Serialize};
+use wasmi::{
+    Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc,
+};
+
+pub const ABI_VERSION: i32 = 1;
+
+pub struct PluginOptions {
+    pub(crate) path: PathBuf,
+    pub(crate) fuel: u64,
+    pub(crate) max_memory: usize,
+}
+
+impl PluginOptions {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            fuel: 10_000_000,
+            max_memory: 64 * 1_024 * 1_024,
+        }
+    }
+
+    /// Amount of fuel, roughly WebAssembly instructions, a single call can use.
+    pub fn fuel(self, fuel: u64) -> Self {
+        Self { fuel, ..self }
+    }
+
+    pub fn max_memory(self, max_memory: usize) -> Self {
+        Self { max_memory, ..self }
+    }
+}
+
+struct HostState {
+    name: String,
+    limits: StoreLimits,
+}
+
+pub struct Plugin {
+    name: String,
+    store: Store<HostState>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    dealloc: TypedFunc<(i32, i32), ()>,
+    fuel: u64,
+    fuel_added: u64,
+}
+
+fn log(caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32) {
+    let memory = match caller.get_export("memory").and_then(Extern::into_memory) {
+        Some(memory) => memory,
+        None => return,
+    };
+
+    let buffer = match guest_buffer(memory.data(&caller), ptr, len) {
+        Some(buffer) => buffer,
+        None => return,
+    };
+
+    let name = caller.data().name.as_str();
+    let message = String::from_utf8_lossy(buffer);
+
+    match level {
+        0 => tracing::trace!(target = name, "{}", message),
+        1 => tracing::debug!(target = name, "{}", message),
+        2 => tracing::info!(target = name, "{}", message),
+        3 => tracing::warn!(target = name, "{}", message),
+        _ => tracing::error!(target = name, "{}", message),
+    }
+}
+
+/// The part of the plugin memory a guest pointer and length refer to, if it's in bounds. Both
+/// are taken as unsigned, like WebAssembly does.
+fn guest_buffer(memory: &[u8], ptr: i32, len: i32) -> Option<&[u8]> {
+    let start = ptr as u32 as usize;
+
+    memory.get(start..start.checked_add(len as u32 as usize)?)
+}
+
+impl Plugin {
+    pub fn load(name: impl AsRef<str>, options: &PluginOptions) -> eyre::Result<Self> {
+        let name = name.as_ref().to_string();
+        let bytes = std::fs::read(options.path.as_path())
+            .wrap_err_with(|| format!("Error when reading plugin {:?}", options.path))?;
+
+        let mut config = Config::default();
+        config.consume_fuel(true);
+
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes.as_slice())
+            .wrap_err_with(|| format!("Invalid WebAssembly module {:?}", options.path))?;
+
+        let mut store = Store::new(
+            &engine,
+            HostState {
+                name: name.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(options.max_memory)
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap("eagle", "log", log)
+            .wrap_err("Error when registering host functions")?;
+
+        let mut plugin = Self::instantiate(name, store, &linker, &module, options.fuel)
+            .wrap_err_with(|| format!("Error when loading plugin {:?}", options.path))?;
+
+        let version = plugin.typed_func::<(), i32>("eagle_abi_version")?;
+        let version = plugin.call(version, ())?;
+
+        if version != ABI_VERSION {
+            bail!(
+                "Plugin {:?} targets ABI version {} but only version {} is supported",
+                options.path,
+                version,
+                ABI_VERSION
+            );
+        }
+
+        Ok(plugin)
+    }
+
+    fn instantiate(
+        name: String,
+        mut store: Store<HostState>,
+        linker: &Linker<HostState>,
+        module: &Module,
+        fuel: u64,
+    ) -> eyre::Result<Self> {
+        store
+            .add_fuel(fuel)
+            .map_err(|e| eyre!("Error when adding fuel: {}", e))?;
+
+        let instance = linker.instantiate(&mut store, module)?.start(&mut store)?;
+
+        let memory = match instance.get_memory(&store, "memory") {
+            Some(memory) => memory,
+            None => bail!("Plugin doesn't export its memory"),
+        };
+
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "eagle_alloc")?;
+        let dealloc = instance.get_typed_func::<(i32, i32), ()>(&store, "eagle_dealloc")?;
+
+        Ok(Self {
+            name,
+            store,
+            instance,
+            memory,
+            alloc,
+            dealloc,
+            fuel,
+            fuel_added: fuel,
+        })
+    }
+
+    pub fn typed_func<Params, Results>(
+        &self,
+        name: &str,
+    ) -> eyre::Result<TypedFunc<Params, Results>>
+    where
+        Params: wasmi::WasmParams,
+        Results: wasmi::WasmResults,
+    {
+        self.instance
+            .get_typed_func::<Params, Results>(&self.store, name)
+            .wrap_err_with(|| format!("Plugin '{}' doesn't export '{}'", self.name, name))
+    }
+
+    /// Calls a plugin function, giving it back its fuel allowance first.
+    pub fn call<Params, Results>(
+        &mut self,
+        func: TypedFunc<Params, Results>,
+        params: Params,
+    ) -> eyre::Result<Results>
+    where
+        Params: wasmi::WasmParams,
+        Results: wasmi::WasmResults,
+    {
+        let consumed = self.store.fuel_consumed().unwrap_or_default();
+        let remaining = self.fuel_added.saturating_sub(consumed);
+
+        if remaining < self.fuel {
+            self.store
+                .add_fuel(self.fuel - remaining)
+                .map_err(|e| eyre!("Error when adding fuel: {}", e))?;
+            self.fuel_added += self.fuel - remaining;
+        }
+
+        func.call(&mut self.store, params)
+            .wrap_err_with(|| format!("Plugin '{}' call failed", self.name))
+    }
+
+    /// Copies a value as JSON into the plugin memory. The caller is responsible for freeing it.
+    pub fn write_json<A: Serialize>(&mut self, value: &A) -> eyre::Result<(i32, i32)> {
+        let bytes = serde_json::to_vec(value).wrap_err("Error when serializing plugin input")?;
+        let len = bytes.len() as i32;
+        let ptr = self.call(self.alloc, len)?;
+
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, bytes.as_slice())
+            .map_err(|e| eyre!("Plugin returned an invalid buffer: {}", e))?;
+
+        Ok((ptr, len))
+    }
+
+    /// Reads a JSON value the plugin returned as a packed pointer and frees it.
+    pub fn read_json<A: DeserializeOwned>(&mut self, packed: i64) -> eyre::Result<Option<A>> {
+        if packed == 0 {
+            return Ok(None);
+        }
+
+        let ptr = (packed >> 32) as i32;
+        let len = packed as i32;
+        let buffer = guest_buffer(self.memory.data(&self.store), ptr, len)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| {
+                eyre!(
+                    "Plugin returned an invalid buffer of {} bytes at {}",
+                    len as u32,
+                    ptr as u32
+                )
+            })?;
+
+        self.free(ptr, len)?;
+
+        serde_json::from_slice(buffer.as_slice())
+            .map(Some)
+            .wrap_err("Plugin returned invalid JSON")
+    }
+
+    pub fn free(&mut self, ptr: i32, len: i32) -> eyre::Result<()> {
+        self.call(self.dealloc, (ptr, len))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// Writes a plugin to a temporary file, made of `body` along with a bump allocator and the
+    /// other exports the ABI requires. `eagle_dealloc` only counts calls, returned by `freed`.
+    pub(crate) fn plugin_file(name: &str, abi_version: i32, pages: u32, body: &str) -> PathBuf {
+        let source = format!(
+            r#"
+            (module
+              (import "eagle" "log" (func $log (param i32 i32 i32)))
+              (memory (export "memory") {pages})
+              (global $next (mut i32) (i32.const 1024))
+              (global $freed (mut i32) (i32.const 0))
+              (func (export "eagle_abi_version") (result i32) (i32.const {abi_version}))
+              (func (export "eagle_alloc") (param $len i32) (result i32)
+                (local $ptr i32)
+                (local.set $ptr (global.get $next))
+                (global.set $next (i32.add (global.get $next) (local.get $len)))
+                (local.get $ptr))
+              (func (export "eagle_dealloc") (param i32 i32)
+                (global.set $freed (i32.add (global.get $freed) (i32.const 1))))
+              (func (export "freed") (result i32) (global.get $freed))
+              {body})
+            "#
+        );
+
+        module_file(name, source.as_str())
+    }
+
+    /// Compiles a WAT module to a temporary file.
+    fn module_file(name: &str, source: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("eagle-wasm-{}-{}.wasm", name, std::process::id()));
+
+        std::fs::write(path.as_path(), wat::parse_str(source).unwrap()).unwrap();
+
+        path
+    }
+
+    /// A WAT data segment at offset 0 holding `value` as JSON, and the packed pointer to it.
+    pub(crate) fn json_data(value: &Value) -> (String, i64) {
+        let json = value.to_string();
+        let segment = format!(
+            r#"(data (i32.const 0) "{}")"#,
+            json.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+
+        (segment, json.len() as i64)
+    }
+
+    fn load(name: &str, body: &str) -> Plugin {
+        Plugin::load(
+            name,
+            &PluginOptions::new(plugin_file(name, ABI_VERSION, 1, body)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trips_json() {
+        let mut plugin = load(
+            "round-trip",
+            r#"
+            (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+              (i64.or
+                (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                (i64.extend_i32_u (local.get $len))))
+            (func (export "nothing") (result i64) (i64.const 0))
+            "#,
+        );
+
+        let echo = plugin.typed_func::<(i32, i32), i64>("echo").unwrap();
+        let freed = plugin.typed_func::<(), i32>("freed").unwrap();
+        let values = vec![
+            json!({ "name": "up", "value": 1.5, "tags": { "host": "web-1" } }),
+            json!([1, "two", null]),
+            json!("été"),
+        ];
+
+        for (index, value) in values.into_iter().enumerate() {
+            let (ptr, len) = plugin.write_json(&value).unwrap();
+
+            assert_eq!(len as usize, value.to_string().len());
+            assert!(ptr >= 1_024, "allocating {}", value);
+
+            let packed = plugin.call(echo, (ptr, len)).unwrap();
+
+            assert_eq!(packed, ((ptr as i64) << 32) | len as i64);
+            assert_eq!(plugin.read_json::<Value>(packed).unwrap(), Some(value));
+            assert_eq!(plugin.call(freed, ()).unwrap(), index as i32 + 1);
+        }
+
+        let nothing = plugin.typed_func::<(), i64>("nothing").unwrap();
+        let packed = plugin.call(nothing, ()).unwrap();
+
+        assert_eq!(plugin.read_json::<Value>(packed).unwrap(), None);
+        assert_eq!(plugin.call(freed, ()).unwrap(), 3);
+    }
+
+    #[test]
+    fn rejects_other_abi_versions() {
+        let path = plugin_file("abi", ABI_VERSION + 1, 1, "");
+        let error = Plugin::load("abi", &PluginOptions::new(path))
+            .err()
+            .unwrap();
+
+        assert!(
+            format!("{:#}", error).contains("targets ABI version 2"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_plugins() {
+        let path = module_file("incomplete", r#"(module (memory (export "memory") 1))"#);
+
+        assert!(Plugin::load("incomplete", &PluginOptions::new(path)).is_err());
+        assert!(Plugin::load("missing", &PluginOptions::new("/nonexistent.wasm")).is_err());
+    }
+
+    #[test]
+    fn runs_out_of_fuel() {
+        let path = plugin_file(
+            "fuel",
+            ABI_VERSION,
+            1,
+            r#"
+            (func (export "spin") (loop $forever (br $forever)))
+            (func (export "count") (param $n i32)
+              (loop $next
+                (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                (br_if $next (i32.gt_s (local.get $n) (i32.const 0)))))
+            "#,
+        );
+
+        let mut plugin = Plugin::load("fuel", &PluginOptions::new(path).fuel(10_000)).unwrap();
+        let spin = plugin.typed_func::<(), ()>("spin").unwrap();
+        let count = plugin.typed_func::<i32, ()>("count").unwrap();
+
+        assert!(plugin.call(spin, ()).is_err());
+
+        // Every call gets its allowance back, even after one used all of it.
+        for _ in 0..3 {
+            assert!(plugin.call(count, 1_000).is_ok());
+            assert!(plugin.call(spin, ()).is_err());
+        }
+
+        assert!(plugin.call(count, 100_000).is_err());
+    }
+
+    #[test]
+    fn limits_memory() {
+        let page = 64 * 1_024;
+        let grow = r#"
+            (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+        "#;
+
+        let path = plugin_file("memory", ABI_VERSION, 1, grow);
+        let mut plugin =
+            Plugin::load("memory", &PluginOptions::new(path).max_memory(4 * page)).unwrap();
+        let grow = plugin.typed_func::<i32, i32>("grow").unwrap();
+
+        assert_eq!(plugin.call(grow, 2).unwrap(), 1);
+        assert_eq!(plugin.call(grow, 2).unwrap(), -1);
+        assert_eq!(plugin.call(grow, 1).unwrap(), 3);
+        assert_eq!(plugin.call(grow, 1).unwrap(), -1);
+
+        let path = plugin_file("initial-memory", ABI_VERSION, 8, "");
+
+        assert!(Plugin::load(
+            "initial-memory",
+            &PluginOptions::new(path).max_memory(4 * page)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_buffers() {
+        let mut plugin = load(
+            "bounds",
+            r#"
+            (func (export "returned") (param i64) (result i64) (local.get 0))
+            (func (export "log_everything")
+              (call $log (i32.const 2) (i32.const 0) (i32.const 0x7fffffff))
+              (call $log (i32.const 2) (i32.const -1) (i32.const 16)))
+            "#,
+        );
+
+        let returned = plugin.typed_func::<i64, i64>("returned").unwrap();
+        let page = 64 * 1_024i64;
+        let cases = vec![
+            (page << 32) | 1,
+            ((page - 4) << 32) | 8,
+            16,
+            (16 << 32) | 0xffff_ffff,
+            -1,
+        ];
+
+        for packed in cases {
+            let packed = plugin.call(returned, packed).unwrap();
+
+            assert!(
+                plugin.read_json::<Value>(packed).is_err(),
+                "reading {:#x}",
+                packed
+            );
+        }
+
+        let log_everything = plugin.typed_func::<(), ()>("log_everything").unwrap();
+
+        assert!(plugin.call(log_everything, ()).is_ok());
+
+        // An allocator handing out memory past the end.
+        let source = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "eagle_abi_version") (result i32) (i32.const 1))
+              (func (export "eagle_alloc") (param i32) (result i32) (i32.const -8))
+              (func (export "eagle_dealloc") (param i32 i32)))
+        "#;
+        let path = module_file("bad-alloc", source);
+        let mut plugin = Plugin::load("bad-alloc", &PluginOptions::new(path)).unwrap();
+
+        assert!(plugin.write_json(&json!({ "name": "up" })).is_err());
+    }
+}
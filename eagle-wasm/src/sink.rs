@@ -0,0 +1,143 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eagle_core::{EagleMsg, EagleStream, MetricEvent, MetricSink, Origin, Recv};
+use eyre::bail;
+use wasmi::TypedFunc;
+
+use crate::{
+    plugin::{Plugin, PluginOptions},
+    wire::WireMetric,
+};
+
+pub struct WasmSink {
+    plugin: Plugin,
+    flush: TypedFunc<(i32, i32), i32>,
+    batch_size: usize,
+    period: Duration,
+}
+
+impl WasmSink {
+    pub fn new(
+        name: impl AsRef<str>,
+        options: PluginOptions,
+        batch_size: usize,
+        period: Duration,
+    ) -> eyre::Result<Self> {
+        let plugin = Plugin::load(name, &options)?;
+        let flush = plugin.typed_func("eagle_flush")?;
+
+        Ok(Self {
+            plugin,
+            flush,
+            batch_size,
+            period,
+        })
+    }
+
+    fn send(&mut self, batch: &[WireMetric]) -> eyre::Result<()> {
+        let (ptr, len) = self.plugin.write_json(&batch)?;
+        let outcome = self.plugin.call(self.flush, (ptr, len));
+
+        self.plugin.free(ptr, len)?;
+
+        let code = outcome?;
+        if code != 0 {
+            bail!("Plugin failed to flush {} metrics: {}", batch.len(), code);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl MetricSink for WasmSink {
+    async fn process(
+        &mut self,
+        origin: Arc<Origin>,
+        mut stream: EagleStream<MetricEvent>,
+    ) -> eyre::Result<()> {
+        let mut clock = Instant::now();
+        let mut batch = Vec::with_capacity(self.batch_size);
+
+        while let Recv::Available(msg) = stream.recv().await {
+            let shutdown = match msg {
+                EagleMsg::Tick => {
+                    if clock.elapsed() < self.period || batch.is_empty() {
+                        continue;
+                    }
+
+                    false
+                }
+
+                EagleMsg::Msg(event) => {
+                    batch.push(WireMetric::from(event.metric.as_ref()));
+
+                    if batch.len() < self.batch_size {
+                        continue;
+                    }
+
+                    false
+                }
+
+                EagleMsg::Shutdown => true,
+            };
+
+            if !batch.is_empty() {
+                if let Err(e) = self.send(batch.as_slice()) {
+                    tracing::error!(target = origin.instance_id(), "{:?}", e);
+                }
+
+                batch.clear();
+            }
+
+            clock = Instant::now();
+
+            if shutdown {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eagle_core::MetricBuilder;
+
+    use super::*;
+    use crate::plugin::{tests::plugin_file, ABI_VERSION};
+
+    /// The plugin fails batches of more than 100 bytes.
+    fn sink() -> WasmSink {
+        let path = plugin_file(
+            "sink",
+            ABI_VERSION,
+            1,
+            r#"
+            (func (export "eagle_flush") (param $ptr i32) (param $len i32) (result i32)
+              (i32.gt_u (local.get $len) (i32.const 100)))
+            "#,
+        );
+
+        WasmSink::new("sink", PluginOptions::new(path), 10, Duration::from_secs(1)).unwrap()
+    }
+
+    #[test]
+    fn reports_flush_failures() {
+        let mut sink = sink();
+        let metric = WireMetric::from(&MetricBuilder::gauge("cpu", "usage", 1f64).build());
+
+        assert!(sink.send(&[]).is_ok());
+        assert!(sink.send(std::slice::from_ref(&metric)).is_ok());
+
+        let batch = (0..5)
+            .map(|_| WireMetric::from(&MetricBuilder::gauge("cpu", "usage", 1f64).build()))
+            .collect::<Vec<_>>();
+
+        assert!(sink.send(batch.as_slice()).is_err());
+    }
+}
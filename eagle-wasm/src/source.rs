@@ -0,0 +1,67 @@
+use eagle_core::{poll::PollingSource, Metric};
+use wasmi::TypedFunc;
+
+use crate::{
+    plugin::{Plugin, PluginOptions},
+    wire::WireMetric,
+};
+
+pub struct WasmSource {
+    plugin: Plugin,
+    poll: TypedFunc<(), i64>,
+}
+
+impl WasmSource {
+    pub fn new(name: impl AsRef<str>, options: PluginOptions) -> eyre::Result<Self> {
+        let plugin = Plugin::load(name, &options)?;
+        let poll = plugin.typed_func("eagle_poll")?;
+
+        Ok(Self { plugin, poll })
+    }
+}
+
+#[async_trait::async_trait]
+impl PollingSource for WasmSource {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let packed = self.plugin.call(self.poll, ())?;
+        let metrics = self
+            .plugin
+            .read_json::<Vec<WireMetric>>(packed)?
+            .unwrap_or_default();
+
+        Ok(metrics.into_iter().map(WireMetric::into_metric).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::plugin::{
+        tests::{json_data, plugin_file},
+        ABI_VERSION,
+    };
+
+    #[tokio::test]
+    async fn polls_plugin_metrics() {
+        let (data, packed) = json_data(&json!([
+            { "name": "up", "value": 1, "type": "gauge", "category": "wasm" },
+        ]));
+
+        let body = format!(
+            r#"{} (func (export "eagle_poll") (result i64) (i64.const {}))"#,
+            data, packed
+        );
+
+        let path = plugin_file("source", ABI_VERSION, 1, body.as_str());
+        let mut source = WasmSource::new("source", PluginOptions::new(path)).unwrap();
+
+        for _ in 0..2 {
+            let metrics = source.poll().await.unwrap();
+
+            assert_eq!(metrics.len(), 1);
+            assert_eq!(metrics[0].name, "up");
+        }
+    }
+}
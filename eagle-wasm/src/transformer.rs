@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use eagle_core::{Metric, Origin, Transformer};
+use wasmi::TypedFunc;
+
+use crate::{
+    plugin::{Plugin, PluginOptions},
+    wire::WireMetric,
+};
+
+pub struct WasmTransformer {
+    plugin: Plugin,
+    transform: TypedFunc<(i32, i32), i64>,
+}
+
+impl WasmTransformer {
+    pub fn new(name: impl AsRef<str>, options: PluginOptions) -> eyre::Result<Self> {
+        let plugin = Plugin::load(name, &options)?;
+        let transform = plugin.typed_func("eagle_transform")?;
+
+        Ok(Self { plugin, transform })
+    }
+
+    fn run(&mut self, metric: &Metric) -> eyre::Result<Vec<Metric>> {
+        let (ptr, len) = self.plugin.write_json(&WireMetric::from(metric))?;
+        let outcome = self.plugin.call(self.transform, (ptr, len));
+
+        self.plugin.free(ptr, len)?;
+
+        let metrics = self
+            .plugin
+            .read_json::<Vec<WireMetric>>(outcome?)?
+            .unwrap_or_default();
+
+        Ok(metrics.into_iter().map(WireMetric::into_metric).collect())
+    }
+}
+
+impl Transformer for WasmTransformer {
+    /// Extra metrics returned by the plugin are only available through
+    /// `transform_many`.
+    fn transform(&mut self, origin: Arc<Origin>, metric: Metric) -> Option<Metric> {
+        self.transform_many(origin, metric).into_iter().next()
+    }
+
+    fn transform_many(&mut self, origin: Arc<Origin>, metric: Metric) -> Vec<Metric> {
+        match self.run(&metric) {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                tracing::error!(
+                    target = origin.instance_id(),
+                    "Metric {}:{} left untouched: {:?}",
+                    metric.category,
+                    metric.name,
+                    e
+                );
+
+                vec![metric]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eagle_core::{MetricBuilder, MetricType};
+    use serde_json::json;
+
+    use super::*;
+    use crate::plugin::{
+        tests::{json_data, plugin_file},
+        ABI_VERSION,
+    };
+
+    fn transformer(name: &str, body: &str) -> WasmTransformer {
+        let path = plugin_file(name, ABI_VERSION, 1, body);
+
+        WasmTransformer::new(name, PluginOptions::new(path)).unwrap()
+    }
+
+    #[test]
+    fn returns_plugin_metrics() {
+        let (data, packed) = json_data(&json!([
+            { "name": "up", "value": 1, "type": "gauge", "category": "wasm", "timestamp": 1_700_000_000_000i64 },
+            { "name": "hits", "value": 2.5, "type": "delta", "category": "wasm", "tags": { "host": "web-1" } },
+        ]));
+
+        let mut transformer = transformer(
+            "transformer",
+            format!(
+                r#"{} (func (export "eagle_transform") (param i32 i32) (result i64) (i64.const {}))"#,
+                data, packed
+            )
+            .as_str(),
+        );
+
+        let metrics = transformer.transform_many(
+            Arc::new(Origin::new("test")),
+            MetricBuilder::counter("http", "requests", 1f64).build(),
+        );
+
+        let metrics = metrics
+            .iter()
+            .map(|metric| {
+                (
+                    metric.category.as_str(),
+                    metric.name.as_str(),
+                    metric.value,
+                    metric.r#type,
+                    metric.tags.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            metrics,
+            vec![
+                ("wasm", "up", 1f64, MetricType::Gauge, 0),
+                ("wasm", "hits", 2.5, MetricType::Delta, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_metrics_when_the_plugin_fails() {
+        let mut transformer = transformer(
+            "failing-transformer",
+            r#"(func (export "eagle_transform") (param i32 i32) (result i64) (unreachable))"#,
+        );
+
+        let metric = transformer
+            .transform(
+                Arc::new(Origin::new("test")),
+                MetricBuilder::counter("http", "requests", 1f64).build(),
+            )
+            .unwrap();
+
+        assert_eq!(metric.name, "requests");
+        assert_eq!(metric.value, 1f64);
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+
+use chrono::{TimeZone, Utc};
+use eagle_core::{Metric, MetricType};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum WireType {
+    Counter,
+    Gauge,
+    Delta,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WireMetric {
+    name: String,
+    value: f64,
+    r#type: WireType,
+    category: String,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    timestamp: Option<i64>,
+}
+
+impl From<&Metric> for WireMetric {
+    fn from(metric: &Metric) -> Self {
+        Self {
+            name: metric.name.clone(),
+            value: metric.value,
+            r#type: match metric.r#type {
+                MetricType::Counter => WireType::Counter,
+                MetricType::Gauge => WireType::Gauge,
+                MetricType::Delta => WireType::Delta,
+            },
+            category: metric.category.clone(),
+            tags: metric.tags.clone(),
+            timestamp: Some(metric.timestamp.timestamp_millis()),
+        }
+    }
+}
+
+impl WireMetric {
+    pub fn into_metric(self) -> Metric {
+        let timestamp = self
+            .timestamp
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+            .unwrap_or_else(Utc::now);
+
+        Metric {
+            name: self.name,
+            value: self.value,
+            r#type: match self.r#type {
+                WireType::Counter => MetricType::Counter,
+                WireType::Gauge => MetricType::Gauge,
+                WireType::Delta => MetricType::Delta,
+            },
+            category: self.category,
+            tags: self.tags,
+            timestamp,
+        }
+    }
+}
@@ -1,9 +1,12 @@
 use std::sync::Arc;
 
-use eagle_core::{config::Configuration, EagleEndpoint, EagleEvent, Event};
+use eagle_core::{config::Configuration, EagleEndpoint, EagleEvent, Event, LogEvent};
 use tokio::{runtime::Handle, sync::mpsc, task::JoinHandle};
 
-use self::{sink::spawn_sink, source::spawn_source};
+use self::{
+    sink::{spawn_log_sink, spawn_sink},
+    source::spawn_source,
+};
 
 mod sink;
 mod source;
@@ -52,6 +55,12 @@ impl VSpec {
             .map(|decl| spawn_sink(handle, decl))
             .collect::<Vec<_>>();
 
+        let mut log_sinks = conf
+            .log_sinks
+            .into_iter()
+            .map(|decl| spawn_log_sink(handle, decl))
+            .collect::<Vec<_>>();
+
         let _sources = conf
             .sources
             .into_iter()
@@ -70,33 +79,33 @@ impl VSpec {
                         let origin = event.origin.clone();
                         let metric_name = metric.name.clone();
                         let metric_category = metric.category.clone();
-                        let mut metric = Some(metric);
+                        let mut metrics = vec![metric];
 
                         for decl in transformers.iter_mut() {
-                            match metric.take() {
-                                None => break,
-                                Some(m) => {
-                                    tracing::debug!(
-                                        target = "main-process",
-                                        "Calling transformer {}",
-                                        decl.origin.instance_id()
-                                    );
+                            if metrics.is_empty() {
+                                break;
+                            }
 
-                                    metric = decl.transformer.transform(origin.clone(), m);
+                            tracing::debug!(
+                                target = "main-process",
+                                "Calling transformer {}",
+                                decl.origin.instance_id()
+                            );
 
-                                    tracing::debug!(
-                                        target = "main-process",
-                                        "transformer {} completed. passed: {}",
-                                        decl.origin.instance_id(),
-                                        metric.is_some()
-                                    );
-                                }
-                            }
+                            metrics = metrics
+                                .into_iter()
+                                .flat_map(|m| decl.transformer.transform_many(origin.clone(), m))
+                                .collect();
+
+                            tracing::debug!(
+                                target = "main-process",
+                                "transformer {} completed. passed: {}",
+                                decl.origin.instance_id(),
+                                metrics.len()
+                            );
                         }
 
-                        let metric = if let Some(metric) = metric {
-                            Arc::new(metric)
-                        } else {
+                        if metrics.is_empty() {
                             tracing::warn!(
                                 target = "main-process",
                                 "Metric {}:{} was filtered out by transformers",
@@ -104,13 +113,58 @@ impl VSpec {
                                 metric_name,
                             );
                             continue;
-                        };
+                        }
+
+                        for metric in metrics.into_iter().map(Arc::new) {
+                            for sink in sinks.iter_mut() {
+                                if sink.is_handled(event.origin.as_ref(), metric.as_ref()) {
+                                    if !sink.send_metric(event.origin.clone(), metric.clone()).await
+                                    {
+                                        // TODO - Means that a sink did and we might consider restarting or
+                                        // shutdown the damn application completly.
+                                        tracing::error!(
+                                            target = "main-process",
+                                            "Sink {} died",
+                                            sink.name()
+                                        );
+
+                                        deads.push(sink.id());
+                                    }
+                                }
+                            }
+                        }
+
+                        // We remove all dead sinks from the sink roaster.
+                        if !deads.is_empty() {
+                            sinks.retain(|s| !deads.contains(&s.id()));
+                            deads.clear();
+                        }
+                    }
+
+                    Event::Log(log) => {
+                        let origin = event.origin.clone();
+                        let mut logs = vec![log];
+
+                        for decl in transformers.iter_mut() {
+                            if logs.is_empty() {
+                                break;
+                            }
 
-                        for sink in sinks.iter_mut() {
-                            if sink.is_handled(event.origin.as_ref(), metric.as_ref()) {
-                                if !sink.send_metric(event.origin.clone(), metric.clone()).await {
-                                    // TODO - Means that a sink did and we might consider restarting or
-                                    // shutdown the damn application completly.
+                            logs = logs
+                                .into_iter()
+                                .flat_map(|l| decl.transformer.transform_log(origin.clone(), l))
+                                .collect();
+                        }
+
+                        // Unlike metrics, filtering logs out is common enough not to warn about it.
+                        for log in logs.into_iter().map(Arc::new) {
+                            for sink in log_sinks.iter_mut() {
+                                let event = LogEvent {
+                                    origin: origin.clone(),
+                                    log: log.clone(),
+                                };
+
+                                if !sink.send_log(event).await {
                                     tracing::error!(
                                         target = "main-process",
                                         "Sink {} died",
@@ -122,17 +176,12 @@ impl VSpec {
                             }
                         }
 
-                        // We remove all dead sinks from the sink roaster.
                         if !deads.is_empty() {
-                            sinks.retain(|s| !deads.contains(&s.id()));
+                            log_sinks.retain(|s| !deads.contains(&s.id()));
                             deads.clear();
                         }
                     }
 
-                    Event::Log(log) => {
-                        tracing::info!(target = "main-process", "Log received >>> {:?}", log);
-                    }
-
                     Event::Tick => {}
 
                     Event::Shutdown => {
@@ -140,6 +189,10 @@ impl VSpec {
                             sink.shutdown().await;
                         }
 
+                        for sink in log_sinks {
+                            sink.shutdown().await;
+                        }
+
                         errored = false;
                         break;
                     }
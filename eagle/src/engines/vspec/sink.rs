@@ -1,8 +1,8 @@
-use std::{sync::Arc, time::Instant};
+use std::{future::Future, sync::Arc, time::Instant};
 
 use eagle_core::{
-    config::{SinkConfig, SinkDecl},
-    eagle_channel, EagleSink, Metric, MetricEvent, Origin,
+    config::{LogSinkDecl, SinkConfig, SinkDecl},
+    eagle_channel, EagleSink, EagleStream, LogEvent, Metric, MetricEvent, Origin,
 };
 use tokio::{runtime::Handle, task::JoinHandle, time::Duration};
 use uuid::Uuid;
@@ -34,38 +34,73 @@ impl SinkState {
     }
 
     pub async fn shutdown(self) {
-        self.client.shutdown().await;
-
-        match tokio::time::timeout(Duration::from_secs(10), self.handle).await {
-            Ok(outcome) => {
-                if let Err(e) = outcome {
-                    tracing::error!(
-                        target = "main-process",
-                        "Sink '{}' ended unexpectedly: {}",
-                        self.origin.instance_id(),
-                        e
-                    );
-                }
-            }
-            Err(_) => {
+        shutdown(self.origin.as_ref(), self.client, self.handle).await;
+    }
+
+    pub fn name(&self) -> &str {
+        self.origin.instance_id()
+    }
+}
+
+pub struct LogSinkState {
+    origin: Arc<Origin>,
+    client: EagleSink<LogEvent>,
+    handle: JoinHandle<()>,
+}
+
+impl LogSinkState {
+    pub fn id(&self) -> Uuid {
+        self.origin.id
+    }
+
+    pub async fn send_log(&mut self, event: LogEvent) -> bool {
+        self.client.send_msg(event).await
+    }
+
+    pub async fn shutdown(self) {
+        shutdown(self.origin.as_ref(), self.client, self.handle).await;
+    }
+
+    pub fn name(&self) -> &str {
+        self.origin.instance_id()
+    }
+}
+
+async fn shutdown<A>(origin: &Origin, client: EagleSink<A>, handle: JoinHandle<()>) {
+    client.shutdown().await;
+
+    match tokio::time::timeout(Duration::from_secs(10), handle).await {
+        Ok(outcome) => {
+            if let Err(e) = outcome {
                 tracing::error!(
                     target = "main-process",
-                    "Sink '{}' timeout at shutting down in a timely manner",
-                    self.origin.instance_id(),
+                    "Sink '{}' ended unexpectedly: {}",
+                    origin.instance_id(),
+                    e
                 );
             }
         }
-    }
-
-    pub fn name(&self) -> &str {
-        self.origin.instance_id()
+        Err(_) => {
+            tracing::error!(
+                target = "main-process",
+                "Sink '{}' timeout at shutting down in a timely manner",
+                origin.instance_id(),
+            );
+        }
     }
 }
 
-pub fn spawn_sink(handle: &Handle, mut decl: SinkDecl) -> SinkState {
-    let sink_origin = Arc::new(decl.origin);
-    let origin = sink_origin.clone();
-    let config = decl.config;
+/// Runs a sink process fed by the returned channel, which also receives a tick every 30ms.
+fn spawn_process<A, F, P>(
+    handle: &Handle,
+    origin: Arc<Origin>,
+    process: F,
+) -> (EagleSink<A>, JoinHandle<()>)
+where
+    A: Clone + Send + 'static,
+    F: FnOnce(EagleStream<A>) -> P,
+    P: Future<Output = eyre::Result<()>> + Send + 'static,
+{
     let (client, eagle_stream) = eagle_channel(500);
 
     let client_cloned = client.clone();
@@ -80,24 +115,51 @@ pub fn spawn_sink(handle: &Handle, mut decl: SinkDecl) -> SinkState {
         }
     });
 
+    let process = process(eagle_stream);
     let handle = handle.spawn(async move {
-        tracing::info!(target = sink_origin.instance_id(), "Sink started");
-        if let Err(e) = decl.sink.process(sink_origin.clone(), eagle_stream).await {
+        tracing::info!(target = origin.instance_id(), "Sink started");
+        if let Err(e) = process.await {
             tracing::error!(
-                target = sink_origin.instance_id(),
+                target = origin.instance_id(),
                 "Sink exited with an unexpected error: {}",
                 e
             );
         } else {
-            tracing::info!(target = sink_origin.instance_id(), "Sink exited");
+            tracing::info!(target = origin.instance_id(), "Sink exited");
         }
     });
 
+    (client, handle)
+}
+
+pub fn spawn_sink(handle: &Handle, decl: SinkDecl) -> SinkState {
+    let origin = Arc::new(decl.origin);
+    let sink_origin = origin.clone();
+    let mut sink = decl.sink;
+    let (client, handle) = spawn_process(handle, origin.clone(), move |stream| async move {
+        sink.process(sink_origin, stream).await
+    });
+
     SinkState {
         origin,
         client,
         handle,
         last_time: None,
-        config,
+        config: decl.config,
+    }
+}
+
+pub fn spawn_log_sink(handle: &Handle, decl: LogSinkDecl) -> LogSinkState {
+    let origin = Arc::new(decl.origin);
+    let sink_origin = origin.clone();
+    let mut sink = decl.sink;
+    let (client, handle) = spawn_process(handle, origin.clone(), move |stream| async move {
+        sink.process(sink_origin, stream).await
+    });
+
+    LogSinkState {
+        origin,
+        client,
+        handle,
     }
 }
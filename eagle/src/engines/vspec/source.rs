@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use eagle_core::{config::SourceDecl, EagleClient, EagleEndpoint, Origin};
+use eagle_core::{config::SourceDecl, poll::Schedule, EagleClient, EagleEndpoint, Origin};
 use tokio::{runtime::Handle, task::JoinHandle};
 
 pub struct SourceState {
@@ -10,6 +10,10 @@ pub struct SourceState {
 
 pub fn spawn_source(handle: &Handle, decl: SourceDecl, endpoint: EagleEndpoint) -> SourceState {
     let mut source = decl.source;
+    let schedule = Schedule {
+        interval: decl.config.interval,
+        jitter: decl.config.jitter,
+    };
     let origin = Arc::new(decl.origin);
     let cloned_origin = origin.clone();
 
@@ -17,6 +21,7 @@ pub fn spawn_source(handle: &Handle, decl: SourceDecl, endpoint: EagleEndpoint)
         let client = EagleClient {
             origin: cloned_origin,
             endpoint,
+            schedule,
         };
 
         let instance_id = client.origin.instance_id().to_string();
@@ -0,0 +1,6 @@
+pub mod graphite;
+pub mod influx;
+pub mod json;
+pub mod kafka;
+pub mod nagios;
+pub mod prometheus;
@@ -0,0 +1,717 @@
+use std::collections::BTreeMap;
+
+use chrono::{TimeZone, Utc};
+use eagle_core::{Metric, MetricBuilder};
+use eyre::{bail, eyre, WrapErr};
+
+/// Category of metrics whose path no template matches, the whole path being their name.
+pub const DEFAULT_CATEGORY: &str = "graphite";
+
+#[derive(Clone, Debug)]
+enum Part {
+    Skip,
+    Category,
+    Name,
+    Tag(String),
+}
+
+/// Maps dotted Graphite paths to a category, a name and tags, and back. Templates are written
+/// as `[filter] template [tag=value,...]`, like Telegraf and InfluxDB do:
+///
+/// * The filter is a dotted pattern the start of the path must match, `*` matching any
+///   characters within a segment.
+/// * Each template segment says what the path segment at the same position is: `category`,
+///   `name`, a tag key, or nothing when left empty. Repeated `category` or `name` segments are
+///   joined with dots, and a `*` suffix on the last one, as in `name*`, makes it take all the
+///   remaining path segments.
+/// * The tags are added to every metric the template matches.
+///
+/// For example `servers.* .host.category.name*` turns `servers.web01.cpu.load.1m` into the
+/// `load.1m` metric of the `cpu` category, tagged with `host=web01`.
+#[derive(Clone, Debug)]
+pub struct Template {
+    filter: Vec<String>,
+    parts: Vec<Part>,
+    greedy: bool,
+    tags: BTreeMap<String, String>,
+}
+
+impl Template {
+    pub fn parse(rule: &str) -> eyre::Result<Self> {
+        let sections = rule.split_whitespace().collect::<Vec<_>>();
+        let (filter, template, tags) = match sections.as_slice() {
+            [template] => (None, *template, None),
+            [template, tags] if tags.contains('=') => (None, *template, Some(*tags)),
+            [filter, template] => (Some(*filter), *template, None),
+            [filter, template, tags] => (Some(*filter), *template, Some(*tags)),
+            _ => bail!("Expected an optional filter, a template and optional tags"),
+        };
+
+        let (template, greedy) = match template.strip_suffix('*') {
+            Some(template) => (template, true),
+            None => (template, false),
+        };
+
+        let mut parts = Vec::new();
+        let mut has_name = false;
+
+        for segment in template.split('.') {
+            let part = match segment {
+                "" => Part::Skip,
+                "category" => Part::Category,
+                "name" => Part::Name,
+                key if key.contains('*') => bail!("Only the last segment can end with '*'"),
+                key => Part::Tag(key.to_string()),
+            };
+
+            has_name |= matches!(part, Part::Name);
+            parts.push(part);
+        }
+
+        if !has_name {
+            bail!("Template must have a 'name' segment");
+        }
+
+        if greedy && matches!(parts.last(), Some(Part::Skip)) {
+            bail!("Template can't end with an empty '*' segment");
+        }
+
+        let mut default_tags = BTreeMap::new();
+
+        for tag in tags.into_iter().flat_map(|tags| tags.split(',')) {
+            let (key, value) = tag
+                .split_once('=')
+                .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+                .ok_or_else(|| eyre!("Invalid tag '{}'", tag))?;
+
+            default_tags.insert(key.to_string(), value.to_string());
+        }
+
+        Ok(Self {
+            filter: filter
+                .map(|filter| filter.split('.').map(str::to_string).collect())
+                .unwrap_or_default(),
+            parts,
+            greedy,
+            tags: default_tags,
+        })
+    }
+
+    fn matches(&self, segments: &[&str]) -> bool {
+        self.filter.len() <= segments.len()
+            && self
+                .filter
+                .iter()
+                .zip(segments)
+                .all(|(pattern, segment)| glob(pattern, segment))
+    }
+
+    fn apply(&self, segments: &[&str]) -> eyre::Result<(String, String, BTreeMap<String, String>)> {
+        let mut category = Vec::new();
+        let mut name = Vec::new();
+        let mut tags = self.tags.clone();
+
+        for (index, segment) in segments.iter().enumerate() {
+            let part = match self.parts.get(index) {
+                Some(part) => part,
+                None if self.greedy => self.parts.last().unwrap(),
+                None => break,
+            };
+
+            match part {
+                Part::Skip => {}
+                Part::Category => category.push(*segment),
+                Part::Name => name.push(*segment),
+                Part::Tag(key) => {
+                    tags.entry(key.clone())
+                        .and_modify(|value| {
+                            value.push('.');
+                            value.push_str(segment);
+                        })
+                        .or_insert_with(|| segment.to_string());
+                }
+            }
+        }
+
+        if name.is_empty() {
+            bail!("Path has no segment for the name");
+        }
+
+        let category = if category.is_empty() {
+            DEFAULT_CATEGORY.to_string()
+        } else {
+            category.join(".")
+        };
+
+        Ok((category, name.join("."), tags))
+    }
+
+    /// Builds the path of a metric, when it carries every tag the template needs. Also returns
+    /// the tags that aren't part of the path.
+    fn format<'a>(&self, metric: &'a Metric) -> Option<(String, Vec<(&'a str, &'a str)>)> {
+        let is_tagged = |(key, value): (&String, &String)| metric.tags.get(key) == Some(value);
+
+        if !self.tags.iter().all(is_tagged) {
+            return None;
+        }
+
+        // Paths parsed with a template lacking a category segment get the default category, so
+        // metrics of other categories can't be told apart once formatted with it.
+        let has_category = self.parts.iter().any(|part| matches!(part, Part::Category));
+
+        if !has_category && metric.category != DEFAULT_CATEGORY {
+            return None;
+        }
+
+        let mut categories = Split::new(metric.category.as_str(), &self.parts, Part::Category)?;
+        let mut names = Split::new(metric.name.as_str(), &self.parts, Part::Name)?;
+        let mut segments = Vec::with_capacity(self.parts.len());
+
+        for (index, part) in self.parts.iter().enumerate() {
+            let segment = match part {
+                Part::Category => categories.next(),
+                Part::Name => names.next(),
+                Part::Tag(key) => sanitize(metric.tags.get(key)?.as_str(), &['.', ' ']),
+                // Only a literal filter segment tells what a skipped segment was.
+                Part::Skip => self
+                    .filter
+                    .get(index)
+                    .filter(|pattern| !pattern.contains('*'))?
+                    .clone(),
+            };
+
+            segments.push(segment);
+        }
+
+        let path = segments.join(".");
+
+        if !self.matches(path.split('.').collect::<Vec<_>>().as_slice()) {
+            return None;
+        }
+
+        let remaining = metric
+            .tags
+            .iter()
+            .filter(|(key, _)| {
+                !self.tags.contains_key(key.as_str())
+                    && !self
+                        .parts
+                        .iter()
+                        .any(|part| matches!(part, Part::Tag(tag) if tag == *key))
+            })
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+
+        Some((path, remaining))
+    }
+}
+
+/// Spreads the dotted segments of a value over the template parts holding it, the last one
+/// taking whatever remains.
+struct Split<'a> {
+    segments: std::str::Split<'a, char>,
+    remaining: usize,
+}
+
+impl<'a> Split<'a> {
+    fn new(value: &'a str, parts: &[Part], kind: Part) -> Option<Self> {
+        let count = parts
+            .iter()
+            .filter(|part| std::mem::discriminant(*part) == std::mem::discriminant(&kind))
+            .count();
+
+        if count > 0 && value.split('.').count() < count {
+            return None;
+        }
+
+        Some(Self {
+            segments: value.split('.'),
+            remaining: count,
+        })
+    }
+
+    fn next(&mut self) -> String {
+        self.remaining -= 1;
+
+        if self.remaining > 0 {
+            return sanitize(self.segments.next().unwrap_or_default(), &[' ']);
+        }
+
+        sanitize(
+            self.segments
+                .by_ref()
+                .collect::<Vec<_>>()
+                .join(".")
+                .as_str(),
+            &[' '],
+        )
+    }
+}
+
+/// Ordered list of templates, the first one matching a path or a metric being used.
+#[derive(Clone, Debug, Default)]
+pub struct Templates(Vec<Template>);
+
+impl Templates {
+    pub fn parse<I>(rules: I) -> eyre::Result<Self>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut templates = Vec::new();
+
+        for rule in rules {
+            let rule = rule.as_ref();
+
+            templates.push(
+                Template::parse(rule).wrap_err_with(|| format!("Invalid template '{}'", rule))?,
+            );
+        }
+
+        Ok(Self(templates))
+    }
+
+    /// Parses a `path value [timestamp]` line, the timestamp being in seconds and defaulting to
+    /// now when missing or negative. Paths can carry Graphite tags, as in `path;tag=value`.
+    /// Blank lines give `None`.
+    pub fn parse_line(&self, line: &str) -> eyre::Result<Option<Metric>> {
+        let line = line.trim();
+
+        if line.is_empty() {
+            return Ok(None);
+        }
+
+        let sections = line.split_whitespace().collect::<Vec<_>>();
+        let (path, value, timestamp) = match sections.as_slice() {
+            [path, value] => (*path, *value, None),
+            [path, value, timestamp] => (*path, *value, Some(*timestamp)),
+            _ => bail!("Expected a path, a value and an optional timestamp"),
+        };
+
+        let mut path_tags = path.split(';');
+        let path = path_tags.next().unwrap_or_default();
+        let segments = path.split('.').collect::<Vec<_>>();
+
+        if segments.iter().any(|segment| segment.is_empty()) {
+            bail!("Invalid path '{}'", path);
+        }
+
+        let (category, name, mut tags) =
+            match self.0.iter().find(|template| template.matches(&segments)) {
+                Some(template) => template.apply(&segments)?,
+                None => (
+                    DEFAULT_CATEGORY.to_string(),
+                    path.to_string(),
+                    BTreeMap::new(),
+                ),
+            };
+
+        for tag in path_tags {
+            let (key, value) = tag
+                .split_once('=')
+                .ok_or_else(|| eyre!("Invalid tag '{}'", tag))?;
+
+            tags.insert(key.to_string(), value.to_string());
+        }
+
+        let value = value
+            .parse::<f64>()
+            .wrap_err_with(|| format!("Invalid value '{}'", value))?;
+
+        let mut metric = MetricBuilder::gauge(category, name, value).tags(tags);
+
+        if let Some(timestamp) = timestamp {
+            let seconds = timestamp
+                .parse::<f64>()
+                .wrap_err_with(|| format!("Invalid timestamp '{}'", timestamp))?;
+
+            if seconds >= 0f64 {
+                metric = metric.timestamp(Utc.timestamp_nanos((seconds * 1e9) as i64));
+            }
+        }
+
+        Ok(Some(metric.build()))
+    }
+
+    /// Formats a metric as a `path value timestamp` line, ending with a newline. Without a
+    /// matching template, the path is the category followed by the name, or only the name for
+    /// the default category so paths received without a template are sent back unchanged.
+    /// When `tagged` is set, tags not already in the path are appended as Graphite tags, which
+    /// requires Graphite 1.1 or later, otherwise they are dropped.
+    ///
+    /// Returns `None` for values Graphite can't store.
+    pub fn format(&self, metric: &Metric, tagged: bool) -> Option<String> {
+        if !metric.value.is_finite() {
+            return None;
+        }
+
+        let (mut path, remaining) = self
+            .0
+            .iter()
+            .find_map(|template| template.format(metric))
+            .unwrap_or_else(|| {
+                let path = if metric.category.is_empty() || metric.category == DEFAULT_CATEGORY {
+                    sanitize(metric.name.as_str(), &[' '])
+                } else {
+                    sanitize(
+                        format!("{}.{}", metric.category, metric.name).as_str(),
+                        &[' '],
+                    )
+                };
+
+                let tags = metric
+                    .tags
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value.as_str()))
+                    .collect();
+
+                (path, tags)
+            });
+
+        if tagged {
+            for (key, value) in remaining {
+                if value.is_empty() {
+                    continue;
+                }
+
+                path.push(';');
+                path.push_str(sanitize(key, &[';', '=', ' ']).as_str());
+                path.push('=');
+                path.push_str(sanitize(value, &[';', ' ']).as_str());
+            }
+        }
+
+        Some(format!(
+            "{} {} {}\n",
+            path,
+            metric.value,
+            metric.timestamp.timestamp()
+        ))
+    }
+}
+
+fn sanitize(input: &str, forbidden: &[char]) -> String {
+    input.replace(forbidden, "_")
+}
+
+/// Matches `input` against `pattern`, where `*` matches any characters.
+fn glob(pattern: &str, input: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == input,
+        Some((prefix, rest)) => input.strip_prefix(prefix).is_some_and(|input| {
+            (0..=input.len())
+                .filter(|index| input.is_char_boundary(*index))
+                .any(|index| glob(rest, &input[index..]))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn metric(category: &str, name: &str, pairs: &[(&str, &str)]) -> Metric {
+        MetricBuilder::gauge(category, name, 1f64)
+            .tags(tags(pairs))
+            .timestamp(Utc.timestamp_opt(1_700_000_000, 0).unwrap())
+            .build()
+    }
+
+    #[test]
+    fn parses_templates() {
+        let cases = [
+            ("name", 0, 1, false, vec![]),
+            (".host.category.name*", 0, 4, true, vec![]),
+            ("servers.* .host.category.name*", 2, 4, true, vec![]),
+            (
+                "host.name env=prod,dc=eu",
+                0,
+                2,
+                false,
+                vec![("dc", "eu"), ("env", "prod")],
+            ),
+            (
+                "servers.* host.name env=prod",
+                2,
+                2,
+                false,
+                vec![("env", "prod")],
+            ),
+        ];
+
+        for (rule, filter, parts, greedy, expected) in cases {
+            let template = Template::parse(rule).unwrap();
+
+            assert_eq!(template.filter.len(), filter, "{}", rule);
+            assert_eq!(template.parts.len(), parts, "{}", rule);
+            assert_eq!(template.greedy, greedy, "{}", rule);
+            assert_eq!(template.tags, tags(&expected), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        let cases = [
+            ("", "Expected an optional filter"),
+            ("a b c d", "Expected an optional filter"),
+            ("host.category", "must have a 'name' segment"),
+            ("servers.* host", "must have a 'name' segment"),
+            ("ho*st.name", "Only the last segment"),
+            ("name.*", "can't end with an empty '*' segment"),
+            ("name env=", "Invalid tag 'env='"),
+            ("name =prod", "Invalid tag '=prod'"),
+            ("servers name env", "Invalid tag 'env'"),
+        ];
+
+        for (rule, error) in cases {
+            let actual = format!("{}", Template::parse(rule).unwrap_err());
+
+            assert!(actual.contains(error), "{}: {}", rule, actual);
+        }
+    }
+
+    #[test]
+    fn applies_templates() {
+        let cases = [
+            (
+                "servers.* .host.category.name*",
+                "servers.web01.cpu.load.1m",
+                ("cpu", "load.1m", vec![("host", "web01")]),
+            ),
+            (
+                ".host.category.name",
+                "servers.web01.cpu.load.1m",
+                ("cpu", "load", vec![("host", "web01")]),
+            ),
+            (
+                "category.category.name.name",
+                "a.b.c.d",
+                ("a.b", "c.d", vec![]),
+            ),
+            (
+                "host.host.name",
+                "web.01.cpu",
+                ("graphite", "cpu", vec![("host", "web.01")]),
+            ),
+            (
+                "name.host env=prod",
+                "cpu.web01",
+                ("graphite", "cpu", vec![("env", "prod"), ("host", "web01")]),
+            ),
+            // Path segments take over the default tags.
+            (
+                "host.name host=default",
+                "web01.cpu",
+                ("graphite", "cpu", vec![("host", "default.web01")]),
+            ),
+        ];
+
+        for (rule, path, (category, name, expected)) in cases {
+            let template = Template::parse(rule).unwrap();
+            let segments = path.split('.').collect::<Vec<_>>();
+
+            assert_eq!(
+                template.apply(&segments).unwrap(),
+                (category.to_string(), name.to_string(), tags(&expected)),
+                "{} {}",
+                rule,
+                path
+            );
+        }
+
+        let template = Template::parse("host.name").unwrap();
+
+        assert!(template.apply(&["web01"]).is_err());
+    }
+
+    #[test]
+    fn formats_with_templates() {
+        let cases = [
+            (
+                "servers.* .host.category.name*",
+                metric("cpu", "load.1m", &[("host", "web01"), ("region", "eu")]),
+                Some(("servers.web01.cpu.load.1m", vec![("region", "eu")])),
+            ),
+            (
+                "host.category.name",
+                metric("cpu", "load", &[("host", "web 01.eu")]),
+                Some(("web_01_eu.cpu.load", vec![])),
+            ),
+            (
+                "category.category.name.name",
+                metric("a.b", "c.d.e", &[]),
+                Some(("a.b.c.d.e", vec![])),
+            ),
+            // Not enough category segments.
+            ("category.category.name", metric("cpu", "load", &[]), None),
+            // Missing tag.
+            ("host.category.name", metric("cpu", "load", &[]), None),
+            // A wildcard doesn't tell what the skipped segment was.
+            ("serv* .category.name", metric("cpu", "load", &[]), None),
+            // The path must match the filter.
+            (
+                "servers.web* .host.category.name",
+                metric("cpu", "load", &[("host", "db01")]),
+                None,
+            ),
+            (
+                "servers.web* .host.category.name",
+                metric("cpu", "load", &[("host", "web01")]),
+                Some(("servers.web01.cpu.load", vec![])),
+            ),
+            // Default tags must be there, and aren't repeated.
+            (
+                "host.category.name env=prod",
+                metric("cpu", "load", &[("host", "a"), ("env", "dev")]),
+                None,
+            ),
+            (
+                "host.category.name env=prod",
+                metric("cpu", "load", &[("host", "a"), ("env", "prod")]),
+                Some(("a.cpu.load", vec![])),
+            ),
+            // Without a category segment, only the default category can be formatted.
+            ("host.name", metric("cpu", "load", &[("host", "a")]), None),
+            (
+                "host.name",
+                metric(DEFAULT_CATEGORY, "load", &[("host", "a")]),
+                Some(("a.load", vec![])),
+            ),
+        ];
+
+        for (rule, metric, expected) in cases {
+            let template = Template::parse(rule).unwrap();
+            let expected = expected.map(|(path, remaining)| (path.to_string(), remaining));
+
+            assert_eq!(template.format(&metric), expected, "{}", rule);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let cases: &[(&[&str], &str, bool)] = &[
+            (&[], "cpu.load 1.5 1700000000", false),
+            (&[], "cpu.load;host=web01;region=eu 1 1700000000", true),
+            (
+                &["servers.* .host.category.name*"],
+                "servers.web01.cpu.load.1m -2 1700000000",
+                false,
+            ),
+            (
+                &["dc.* .dc.category.name env=prod"],
+                "dc.eu.cpu.load;rack=r1 0.25 1700000000",
+                true,
+            ),
+        ];
+
+        for (rules, line, tagged) in cases {
+            let templates = Templates::parse(rules.iter()).unwrap();
+            let metric = templates.parse_line(line).unwrap().unwrap();
+
+            assert_eq!(
+                templates.format(&metric, *tagged),
+                Some(format!("{}\n", line)),
+                "{:?}",
+                rules
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_metrics() {
+        let templates = Templates::parse(["servers.* .host.category.name*", "host.name"]).unwrap();
+        let cases = [
+            metric("cpu", "load.1m", &[("host", "web01")]),
+            metric(DEFAULT_CATEGORY, "uptime", &[("host", "web01")]),
+        ];
+
+        for metric in cases {
+            let line = templates.format(&metric, false).unwrap();
+            let parsed = templates.parse_line(line.as_str()).unwrap().unwrap();
+
+            assert_eq!(parsed.category, metric.category, "{}", line);
+            assert_eq!(parsed.name, metric.name, "{}", line);
+            assert_eq!(parsed.tags, metric.tags, "{}", line);
+            assert_eq!(parsed.timestamp, metric.timestamp, "{}", line);
+        }
+    }
+
+    #[test]
+    fn formats_without_templates() {
+        let templates = Templates::default();
+        let cases = [
+            (
+                metric("cpu", "load", &[("host", "a")]),
+                false,
+                "cpu.load 1 1700000000\n",
+            ),
+            (
+                metric("cpu", "load", &[("host", "a b")]),
+                true,
+                "cpu.load;host=a_b 1 1700000000\n",
+            ),
+            (
+                metric("", "load avg", &[]),
+                false,
+                "load_avg 1 1700000000\n",
+            ),
+            (metric("graphite", "x.y", &[]), false, "x.y 1 1700000000\n"),
+        ];
+
+        for (metric, tagged, expected) in cases {
+            assert_eq!(templates.format(&metric, tagged).unwrap(), expected);
+        }
+
+        let mut infinite = metric("cpu", "load", &[]);
+
+        infinite.value = f64::INFINITY;
+        assert!(templates.format(&infinite, false).is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let templates = Templates::default();
+
+        for line in [
+            "cpu",
+            "cpu 1 2 3",
+            "cpu..load 1",
+            "cpu x",
+            "cpu 1 later",
+            "cpu;tag 1",
+        ] {
+            assert!(templates.parse_line(line).is_err(), "{}", line);
+        }
+
+        assert!(templates.parse_line("  ").unwrap().is_none());
+    }
+
+    #[test]
+    fn matches_globs() {
+        let cases = [
+            ("*", "", true),
+            ("*", "web01", true),
+            ("web*", "web01", true),
+            ("*01", "web01", true),
+            ("w*b*1", "web01", true),
+            ("w**1", "web01", true),
+            ("web", "web01", false),
+            ("web01*x", "web01", false),
+            ("*x*", "web", false),
+            ("*é", "été", true),
+            ("é*", "ete", false),
+            ("*t*", "été", true),
+        ];
+
+        for (pattern, input, expected) in cases {
+            assert_eq!(glob(pattern, input), expected, "{} {}", pattern, input);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::BTreeMap;
+
+use chrono::{TimeZone, Utc};
+use eagle_core::{Metric, MetricBuilder};
+use eyre::{bail, eyre, WrapErr};
+use serde::Deserialize;
+
+/// Unit of line protocol timestamps.
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+pub enum Precision {
+    #[default]
+    #[serde(rename = "ns")]
+    Nanoseconds,
+    #[serde(rename = "us")]
+    Microseconds,
+    #[serde(rename = "ms")]
+    Milliseconds,
+    #[serde(rename = "s")]
+    Seconds,
+}
+
+impl Precision {
+    pub fn parse(value: &str) -> eyre::Result<Self> {
+        match value {
+            "ns" | "n" => Ok(Precision::Nanoseconds),
+            "us" | "u" => Ok(Precision::Microseconds),
+            "ms" => Ok(Precision::Milliseconds),
+            "s" => Ok(Precision::Seconds),
+            unknown => bail!("Unknown timestamp precision '{}'", unknown),
+        }
+    }
+
+    fn to_nanos(self, value: i64) -> i64 {
+        match self {
+            Precision::Nanoseconds => value,
+            Precision::Microseconds => value.saturating_mul(1_000),
+            Precision::Milliseconds => value.saturating_mul(1_000_000),
+            Precision::Seconds => value.saturating_mul(1_000_000_000),
+        }
+    }
+}
+
+/// Parses InfluxDB line protocol. The measurement is used as the metric category and each
+/// numeric or boolean field becomes a gauge named after the field key.
+pub fn parse(input: &str, precision: Precision) -> eyre::Result<Vec<Metric>> {
+    let mut metrics = Vec::new();
+
+    for (index, line) in input.lines().enumerate() {
+        let parsed = parse_line(line, precision)
+            .wrap_err_with(|| format!("Invalid line protocol at line {}", index + 1))?;
+
+        metrics.extend(parsed);
+    }
+
+    Ok(metrics)
+}
+
+pub fn parse_line(line: &str, precision: Precision) -> eyre::Result<Vec<Metric>> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(Vec::new());
+    }
+
+    let sections = split_unescaped(line, ' ');
+    let (series, fields, timestamp) = match sections.as_slice() {
+        [series, fields] => (*series, *fields, None),
+        [series, fields, timestamp] => (*series, *fields, Some(*timestamp)),
+        _ => bail!("Expected a measurement, a field set and an optional timestamp"),
+    };
+
+    let mut series = split_unescaped(series, ',').into_iter();
+    let measurement = unescape(series.next().unwrap_or_default());
+
+    if measurement.is_empty() {
+        bail!("Missing measurement");
+    }
+
+    let mut tags = BTreeMap::new();
+
+    for tag in series {
+        let (key, value) = split_key_value(tag).ok_or_else(|| eyre!("Invalid tag '{}'", tag))?;
+
+        tags.insert(unescape(key), unescape(value));
+    }
+
+    let timestamp = match timestamp {
+        Some(timestamp) => {
+            let value = timestamp
+                .parse::<i64>()
+                .wrap_err_with(|| format!("Invalid timestamp '{}'", timestamp))?;
+
+            Some(Utc.timestamp_nanos(precision.to_nanos(value)))
+        }
+
+        None => None,
+    };
+
+    let mut metrics = Vec::new();
+
+    for field in split_unescaped(fields, ',') {
+        let (key, value) =
+            split_key_value(field).ok_or_else(|| eyre!("Invalid field '{}'", field))?;
+
+        let value = match parse_field_value(value)? {
+            Some(value) => value,
+            // String fields can't be represented as metrics.
+            None => continue,
+        };
+
+        let mut metric =
+            MetricBuilder::gauge(measurement.as_str(), unescape(key), value).tags(tags.clone());
+
+        if let Some(timestamp) = timestamp {
+            metric = metric.timestamp(timestamp);
+        }
+
+        metrics.push(metric.build());
+    }
+
+    Ok(metrics)
+}
+
+fn parse_field_value(value: &str) -> eyre::Result<Option<f64>> {
+    if value.starts_with('"') {
+        return Ok(None);
+    }
+
+    let value = match value {
+        "t" | "T" | "true" | "True" | "TRUE" => 1f64,
+        "f" | "F" | "false" | "False" | "FALSE" => 0f64,
+        _ => {
+            let number = value
+                .strip_suffix('i')
+                .or_else(|| value.strip_suffix('u'))
+                .unwrap_or(value);
+
+            number
+                .parse::<f64>()
+                .wrap_err_with(|| format!("Invalid field value '{}'", value))?
+        }
+    };
+
+    Ok(Some(value))
+}
+
+fn split_key_value(input: &str) -> Option<(&str, &str)> {
+    match split_unescaped(input, '=').as_slice() {
+        [key, value] if !key.is_empty() => Some((key, value)),
+        _ => None,
+    }
+}
+
+/// Splits on `separator` when it's neither escaped with a backslash nor within a quoted
+/// field value.
+fn split_unescaped(input: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    let mut quoted = false;
+    let mut previous = None;
+
+    for (index, c) in input.char_indices() {
+        if escaped {
+            escaped = false;
+            previous = Some(c);
+            continue;
+        }
+
+        match c {
+            '\\' => escaped = true,
+            // Only field values can be quoted, quotes are literal anywhere else.
+            '"' if quoted || previous == Some('=') => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&input[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+
+        previous = Some(c);
+    }
+
+    parts.push(&input[start..]);
+    parts
+}
+
+fn unescape(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                output.push(next);
+            }
+        } else {
+            output.push(c);
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Category, name, value and tags.
+    type Expected<'a> = (&'a str, &'a str, f64, &'a [(&'a str, &'a str)]);
+
+    fn assert_metrics(input: &str, metrics: &[Metric], expected: &[Expected]) {
+        assert_eq!(metrics.len(), expected.len(), "{}", input);
+
+        for (metric, (category, name, value, tags)) in metrics.iter().zip(expected) {
+            let tags = tags
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<BTreeMap<_, _>>();
+
+            assert_eq!(metric.category, *category, "{}", input);
+            assert_eq!(metric.name, *name, "{}", input);
+            assert_eq!(metric.value, *value, "{}", input);
+            assert_eq!(metric.tags, tags, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_lines() {
+        let cases: &[(&str, &[Expected])] = &[
+            ("cpu usage=0.5", &[("cpu", "usage", 0.5, &[])]),
+            (
+                "cpu,host=a,region=eu usage=0.5,idle=99.5",
+                &[
+                    ("cpu", "usage", 0.5, &[("host", "a"), ("region", "eu")]),
+                    ("cpu", "idle", 99.5, &[("host", "a"), ("region", "eu")]),
+                ],
+            ),
+            (
+                "disk\\ io,path=C:\\,\\ data,my\\=tag=x\\ y read\\ bytes=1",
+                &[(
+                    "disk io",
+                    "read bytes",
+                    1f64,
+                    &[("my=tag", "x y"), ("path", "C:, data")],
+                )],
+            ),
+            (
+                "net bytes=42i,errors=7u,drops=-3i",
+                &[
+                    ("net", "bytes", 42f64, &[]),
+                    ("net", "errors", 7f64, &[]),
+                    ("net", "drops", -3f64, &[]),
+                ],
+            ),
+            (
+                "svc up=t,down=FALSE,ok=true",
+                &[
+                    ("svc", "up", 1f64, &[]),
+                    ("svc", "down", 0f64, &[]),
+                    ("svc", "ok", 1f64, &[]),
+                ],
+            ),
+            // String fields are skipped, even when they hold separators or escaped quotes.
+            (
+                r#"log msg="a, b=c \"d\" e",count=2i"#,
+                &[("log", "count", 2f64, &[])],
+            ),
+            ("# a comment", &[]),
+            ("   ", &[]),
+        ];
+
+        for (input, expected) in cases {
+            let metrics = parse(input, Precision::default()).unwrap();
+
+            assert_metrics(input, &metrics, expected);
+        }
+    }
+
+    #[test]
+    fn applies_precision() {
+        let cases = [
+            (
+                Precision::Nanoseconds,
+                "1700000000123456789",
+                1_700_000_000_123i64,
+            ),
+            (
+                Precision::Microseconds,
+                "1700000000123456",
+                1_700_000_000_123,
+            ),
+            (Precision::Milliseconds, "1700000000123", 1_700_000_000_123),
+            (Precision::Seconds, "1700000000", 1_700_000_000_000),
+        ];
+
+        for (precision, timestamp, millis) in cases {
+            let line = format!("cpu usage=1 {}", timestamp);
+            let metrics = parse(line.as_str(), precision).unwrap();
+
+            assert_eq!(metrics[0].timestamp.timestamp_millis(), millis, "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let cases = [
+            "cpu",
+            "cpu usage=1 123 extra",
+            ",host=a usage=1",
+            "cpu,host usage=1",
+            "cpu usage",
+            "cpu usage=abc",
+            "cpu usage=1x",
+            "cpu usage=1 later",
+        ];
+
+        for input in cases {
+            let error = parse(input, Precision::default()).unwrap_err();
+
+            assert!(
+                format!("{:?}", error).contains("at line 1"),
+                "{}: {:?}",
+                input,
+                error
+            );
+        }
+    }
+}
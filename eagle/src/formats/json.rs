@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeZone, Utc};
+use eagle_core::{Metric, MetricBuilder};
+use eyre::WrapErr;
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsonMetricType {
+    Counter,
+    #[default]
+    Gauge,
+    Delta,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonTimestamp {
+    /// Milliseconds since the Unix epoch.
+    Millis(i64),
+    Rfc3339(String),
+}
+
+/// A metric as sent by external producers. Only `name` and `value` are required.
+#[derive(Deserialize)]
+pub struct JsonMetric {
+    name: String,
+    value: f64,
+    #[serde(default)]
+    r#type: JsonMetricType,
+    category: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    timestamp: Option<JsonTimestamp>,
+}
+
+impl JsonMetric {
+    /// `category` is used when the metric doesn't specify one.
+    pub fn into_metric(self, category: &str) -> Metric {
+        let category = self.category.as_deref().unwrap_or(category);
+        let mut metric = match self.r#type {
+            JsonMetricType::Counter => MetricBuilder::counter(category, self.name, self.value),
+            JsonMetricType::Gauge => MetricBuilder::gauge(category, self.name, self.value),
+            JsonMetricType::Delta => MetricBuilder::delta(category, self.name, self.value),
+        };
+
+        let timestamp = match self.timestamp {
+            Some(JsonTimestamp::Millis(millis)) => Utc.timestamp_millis_opt(millis).single(),
+            Some(JsonTimestamp::Rfc3339(timestamp)) => DateTime::parse_from_rfc3339(&timestamp)
+                .ok()
+                .map(|timestamp| timestamp.with_timezone(&Utc)),
+            None => None,
+        };
+
+        if let Some(timestamp) = timestamp {
+            metric = metric.timestamp(timestamp);
+        }
+
+        metric.tags(self.tags).build()
+    }
+}
+
+/// Parses a single JSON metric or an array of them.
+pub fn parse(category: &str, input: &str) -> eyre::Result<Vec<Metric>> {
+    let value = serde_json::from_str::<Value>(input).wrap_err("Invalid JSON")?;
+
+    parse_value(category, value)
+}
+
+pub fn parse_value(category: &str, value: Value) -> eyre::Result<Vec<Metric>> {
+    let metrics = match value {
+        Value::Array(_) => serde_json::from_value::<Vec<JsonMetric>>(value),
+        value => serde_json::from_value::<JsonMetric>(value).map(|metric| vec![metric]),
+    }
+    .wrap_err("Invalid JSON metric")?;
+
+    Ok(metrics
+        .into_iter()
+        .map(|metric| metric.into_metric(category))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use eagle_core::MetricType;
+
+    use super::*;
+
+    /// Category, name, value and type.
+    type Expected<'a> = (&'a str, &'a str, f64, MetricType);
+
+    #[test]
+    fn parses_metrics() {
+        let cases: &[(&str, &[Expected])] = &[
+            (
+                r#"{"name": "up", "value": 1}"#,
+                &[("app", "up", 1f64, MetricType::Gauge)],
+            ),
+            (
+                r#"{"name": "hits", "value": 3, "type": "counter", "category": "web"}"#,
+                &[("web", "hits", 3f64, MetricType::Counter)],
+            ),
+            (
+                r#"[
+                    {"name": "a", "value": 1.5, "type": "delta"},
+                    {"name": "b", "value": -2, "type": "gauge"}
+                ]"#,
+                &[
+                    ("app", "a", 1.5, MetricType::Delta),
+                    ("app", "b", -2f64, MetricType::Gauge),
+                ],
+            ),
+            ("[]", &[]),
+        ];
+
+        for (input, expected) in cases {
+            let metrics = parse("app", input).unwrap();
+            let actual = metrics
+                .iter()
+                .map(|metric| {
+                    (
+                        metric.category.as_str(),
+                        metric.name.as_str(),
+                        metric.value,
+                        metric.r#type,
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(actual, expected.to_vec(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_tags_and_timestamps() {
+        let cases = [
+            (r#""timestamp": 1700000000123"#, 1_700_000_000_123i64),
+            (
+                r#""timestamp": "2023-11-14T22:13:20.123Z""#,
+                1_700_000_000_123,
+            ),
+            (
+                r#""timestamp": "2023-11-15T00:13:20.123+02:00""#,
+                1_700_000_000_123,
+            ),
+        ];
+
+        for (timestamp, millis) in cases {
+            let input = format!(
+                r#"{{"name": "up", "value": 1, "tags": {{"host": "a \"b\""}}, {}}}"#,
+                timestamp
+            );
+
+            let metrics = parse("app", input.as_str()).unwrap();
+
+            assert_eq!(metrics[0].timestamp.timestamp_millis(), millis, "{}", input);
+            assert_eq!(metrics[0].tags["host"], "a \"b\"");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_metrics() {
+        let cases = [
+            "",
+            "{",
+            "42",
+            r#"{"value": 1}"#,
+            r#"{"name": "up"}"#,
+            r#"{"name": "up", "value": "1"}"#,
+            r#"{"name": "up", "value": 1, "type": "histogram"}"#,
+            r#"[{"name": "up", "value": 1}, {"name": "down"}]"#,
+        ];
+
+        for input in cases {
+            assert!(parse("app", input).is_err(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeZone, Utc};
+use eagle_core::{Log, Metric, MetricBuilder, MetricType};
+use eyre::WrapErr;
+use prost::Message;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::formats::json;
+
+/// How metrics and logs are written to Kafka records.
+///
+/// With `json`, a metric is an object in the format `formats::json` parses, plus a `source`
+/// field, and a log is an object with `source`, `timestamp`, `metadata` and `log` fields.
+///
+/// With `protobuf`, records follow this schema:
+///
+/// ```proto
+/// syntax = "proto3";
+///
+/// import "google/protobuf/struct.proto";
+/// import "google/protobuf/timestamp.proto";
+///
+/// enum MetricType {
+///   GAUGE = 0;
+///   COUNTER = 1;
+///   DELTA = 2;
+/// }
+///
+/// message Metric {
+///   string source = 1;
+///   string category = 2;
+///   string name = 3;
+///   double value = 4;
+///   MetricType type = 5;
+///   map<string, string> tags = 6;
+///   google.protobuf.Timestamp timestamp = 7;
+/// }
+///
+/// message Log {
+///   string source = 1;
+///   google.protobuf.Timestamp timestamp = 2;
+///   google.protobuf.Value log = 3;
+///   google.protobuf.Value metadata = 4;
+/// }
+/// ```
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Json,
+    Protobuf,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+enum ProtoMetricType {
+    Gauge = 0,
+    Counter = 1,
+    Delta = 2,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoMetric {
+    #[prost(string, tag = "1")]
+    source: String,
+    #[prost(string, tag = "2")]
+    category: String,
+    #[prost(string, tag = "3")]
+    name: String,
+    #[prost(double, tag = "4")]
+    value: f64,
+    #[prost(enumeration = "ProtoMetricType", tag = "5")]
+    r#type: i32,
+    #[prost(btree_map = "string, string", tag = "6")]
+    tags: BTreeMap<String, String>,
+    #[prost(message, optional, tag = "7")]
+    timestamp: Option<prost_types::Timestamp>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoLog {
+    #[prost(string, tag = "1")]
+    source: String,
+    #[prost(message, optional, tag = "2")]
+    timestamp: Option<prost_types::Timestamp>,
+    #[prost(message, optional, tag = "3")]
+    log: Option<prost_types::Value>,
+    #[prost(message, optional, tag = "4")]
+    metadata: Option<prost_types::Value>,
+}
+
+pub fn encode_metric(encoding: Encoding, source: &str, metric: &Metric) -> Vec<u8> {
+    match encoding {
+        Encoding::Json => {
+            let r#type = match metric.r#type {
+                MetricType::Counter => "counter",
+                MetricType::Gauge => "gauge",
+                MetricType::Delta => "delta",
+            };
+
+            json!({
+                "source": source,
+                "category": metric.category,
+                "name": metric.name,
+                "value": metric.value,
+                "type": r#type,
+                "tags": metric.tags,
+                "timestamp": metric.timestamp.timestamp_millis(),
+            })
+            .to_string()
+            .into_bytes()
+        }
+
+        Encoding::Protobuf => {
+            let r#type = match metric.r#type {
+                MetricType::Counter => ProtoMetricType::Counter,
+                MetricType::Gauge => ProtoMetricType::Gauge,
+                MetricType::Delta => ProtoMetricType::Delta,
+            };
+
+            ProtoMetric {
+                source: source.to_string(),
+                category: metric.category.clone(),
+                name: metric.name.clone(),
+                value: metric.value,
+                r#type: r#type as i32,
+                tags: metric.tags.clone(),
+                timestamp: Some(to_proto_timestamp(metric.timestamp)),
+            }
+            .encode_to_vec()
+        }
+    }
+}
+
+pub fn encode_log(
+    encoding: Encoding,
+    source: &str,
+    timestamp: DateTime<Utc>,
+    log: &Log,
+) -> Vec<u8> {
+    match encoding {
+        Encoding::Json => json!({
+            "source": source,
+            "timestamp": timestamp.to_rfc3339(),
+            "metadata": log.metadata,
+            "log": log.inner.as_ref(),
+        })
+        .to_string()
+        .into_bytes(),
+
+        Encoding::Protobuf => ProtoLog {
+            source: source.to_string(),
+            timestamp: Some(to_proto_timestamp(timestamp)),
+            log: Some(to_proto_value(log.inner.as_ref())),
+            metadata: Some(to_proto_value(&log.metadata)),
+        }
+        .encode_to_vec(),
+    }
+}
+
+/// Decodes the metrics of a record, `category` being used when a JSON metric doesn't have
+/// one. JSON records can hold a single metric or an array of them.
+pub fn decode_metrics(
+    encoding: Encoding,
+    category: &str,
+    payload: &[u8],
+) -> eyre::Result<Vec<Metric>> {
+    match encoding {
+        Encoding::Json => {
+            let value = serde_json::from_slice::<Value>(payload).wrap_err("Invalid JSON")?;
+
+            json::parse_value(category, value)
+        }
+
+        Encoding::Protobuf => {
+            let metric = ProtoMetric::decode(payload).wrap_err("Invalid protobuf metric")?;
+            let mut builder = match ProtoMetricType::from_i32(metric.r#type) {
+                Some(ProtoMetricType::Counter) => {
+                    MetricBuilder::counter(metric.category, metric.name, metric.value)
+                }
+                Some(ProtoMetricType::Delta) => {
+                    MetricBuilder::delta(metric.category, metric.name, metric.value)
+                }
+                Some(ProtoMetricType::Gauge) | None => {
+                    MetricBuilder::gauge(metric.category, metric.name, metric.value)
+                }
+            };
+
+            if let Some(timestamp) = metric.timestamp.and_then(from_proto_timestamp) {
+                builder = builder.timestamp(timestamp);
+            }
+
+            Ok(vec![builder.tags(metric.tags).build()])
+        }
+    }
+}
+
+/// Decodes the log of a record and its metadata, if any, the record timestamp being added to
+/// the metadata unless it already has one. JSON records that aren't objects as written by
+/// `encode_log` are taken as the log itself, and as strings when they aren't valid JSON.
+pub fn decode_log(encoding: Encoding, payload: &[u8]) -> eyre::Result<(Value, Value)> {
+    match encoding {
+        Encoding::Json => {
+            let value = serde_json::from_slice::<Value>(payload)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(payload).into_owned()));
+
+            match value {
+                Value::Object(mut fields) if is_log_record(&fields) => {
+                    let timestamp = fields.remove("timestamp");
+                    let metadata = fields.remove("metadata").unwrap_or_default();
+
+                    Ok((
+                        fields.remove("log").unwrap_or_default(),
+                        with_timestamp(metadata, timestamp),
+                    ))
+                }
+                value => Ok((value, Value::Null)),
+            }
+        }
+
+        Encoding::Protobuf => {
+            let log = ProtoLog::decode(payload).wrap_err("Invalid protobuf log")?;
+            let timestamp = log
+                .timestamp
+                .and_then(from_proto_timestamp)
+                .map(|timestamp| Value::String(timestamp.to_rfc3339()));
+
+            Ok((
+                log.log.map(from_proto_value).unwrap_or_default(),
+                with_timestamp(
+                    log.metadata.map(from_proto_value).unwrap_or_default(),
+                    timestamp,
+                ),
+            ))
+        }
+    }
+}
+
+/// Whether a JSON object is a log written by `encode_log`, rather than a log produced by
+/// something else.
+fn is_log_record(fields: &Map<String, Value>) -> bool {
+    fields.contains_key("log")
+        && fields
+            .keys()
+            .all(|key| matches!(key.as_str(), "source" | "timestamp" | "metadata" | "log"))
+}
+
+fn with_timestamp(metadata: Value, timestamp: Option<Value>) -> Value {
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => return metadata,
+    };
+
+    let mut fields = match metadata {
+        Value::Object(fields) => fields,
+        Value::Null => Map::new(),
+        metadata => return metadata,
+    };
+
+    fields.entry("timestamp").or_insert(timestamp);
+
+    Value::Object(fields)
+}
+
+fn to_proto_timestamp(timestamp: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: timestamp.timestamp(),
+        nanos: timestamp.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn from_proto_timestamp(timestamp: prost_types::Timestamp) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(timestamp.seconds, timestamp.nanos.max(0) as u32)
+        .single()
+}
+
+fn to_proto_value(value: &Value) -> prost_types::Value {
+    use prost_types::value::Kind;
+
+    let kind = match value {
+        Value::Null => Kind::NullValue(0),
+        Value::Bool(value) => Kind::BoolValue(*value),
+        Value::Number(value) => Kind::NumberValue(value.as_f64().unwrap_or_default()),
+        Value::String(value) => Kind::StringValue(value.clone()),
+        Value::Array(values) => Kind::ListValue(prost_types::ListValue {
+            values: values.iter().map(to_proto_value).collect(),
+        }),
+        Value::Object(fields) => Kind::StructValue(prost_types::Struct {
+            fields: fields
+                .iter()
+                .map(|(key, value)| (key.clone(), to_proto_value(value)))
+                .collect(),
+        }),
+    };
+
+    prost_types::Value { kind: Some(kind) }
+}
+
+fn from_proto_value(value: prost_types::Value) -> Value {
+    use prost_types::value::Kind;
+
+    match value.kind {
+        None | Some(Kind::NullValue(_)) => Value::Null,
+        Some(Kind::BoolValue(value)) => Value::Bool(value),
+        // Protobuf only has doubles, integers are given back as such.
+        Some(Kind::NumberValue(value)) if value.fract() == 0f64 && value.abs() < 9e15 => {
+            json!(value as i64)
+        }
+        Some(Kind::NumberValue(value)) => json!(value),
+        Some(Kind::StringValue(value)) => Value::String(value),
+        Some(Kind::ListValue(list)) => {
+            Value::Array(list.values.into_iter().map(from_proto_value).collect())
+        }
+        Some(Kind::StructValue(fields)) => Value::Object(
+            fields
+                .fields
+                .into_iter()
+                .map(|(key, value)| (key, from_proto_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn metrics() -> Vec<Metric> {
+        let timestamp = Utc.timestamp_millis(1_700_000_000_123);
+
+        vec![
+            MetricBuilder::gauge("cpu", "usage", 12.5)
+                .add_tag("host", "web-1")
+                .timestamp(timestamp)
+                .build(),
+            MetricBuilder::counter("http", "requests", 42f64)
+                .timestamp(timestamp)
+                .build(),
+            MetricBuilder::delta("http", "errors", -3f64)
+                .add_tag("status", "500")
+                .add_tag("path", "/")
+                .timestamp(timestamp)
+                .build(),
+        ]
+    }
+
+    /// Category, name, value, type, tags and timestamp.
+    type Fields<'a> = (
+        &'a str,
+        &'a str,
+        f64,
+        MetricType,
+        &'a BTreeMap<String, String>,
+        DateTime<Utc>,
+    );
+
+    fn fields(metric: &Metric) -> Fields<'_> {
+        (
+            metric.category.as_str(),
+            metric.name.as_str(),
+            metric.value,
+            metric.r#type,
+            &metric.tags,
+            metric.timestamp,
+        )
+    }
+
+    #[test]
+    fn round_trips_metrics() {
+        for encoding in [Encoding::Json, Encoding::Protobuf] {
+            for metric in metrics() {
+                let payload = encode_metric(encoding, "app", &metric);
+                let decoded = decode_metrics(encoding, "default", payload.as_slice()).unwrap();
+
+                assert_eq!(decoded.len(), 1);
+                assert_eq!(
+                    fields(&decoded[0]),
+                    fields(&metric),
+                    "decoding {:?}",
+                    metric
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_protobuf_metrics() {
+        let metric = &metrics()[2];
+        let decoded =
+            ProtoMetric::decode(encode_metric(Encoding::Protobuf, "app", metric).as_slice())
+                .unwrap();
+
+        assert_eq!(
+            decoded,
+            ProtoMetric {
+                source: "app".to_string(),
+                category: "http".to_string(),
+                name: "errors".to_string(),
+                value: -3f64,
+                r#type: ProtoMetricType::Delta as i32,
+                tags: metric.tags.clone(),
+                timestamp: Some(prost_types::Timestamp {
+                    seconds: 1_700_000_000,
+                    nanos: 123_000_000,
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn round_trips_logs() {
+        let timestamp = Utc.timestamp_millis(1_700_000_000_123);
+        let cases = [
+            (json!("a line"), json!({ "tag": "app" })),
+            (
+                json!({ "message": "hello", "count": 3, "ratio": 0.5, "tags": ["a", null, true] }),
+                json!({ "tag": "app", "timestamp": "2023-01-01T00:00:00+00:00" }),
+            ),
+            (json!({ "log": "a field named log" }), json!({})),
+            (json!(null), json!(null)),
+        ];
+
+        for encoding in [Encoding::Json, Encoding::Protobuf] {
+            for (inner, metadata) in cases.iter() {
+                let log = Log {
+                    inner: Arc::new(inner.clone()),
+                    metadata: metadata.clone(),
+                };
+
+                let payload = encode_log(encoding, "app", timestamp, &log);
+                let (decoded, decoded_metadata) = decode_log(encoding, payload.as_slice()).unwrap();
+
+                // The record timestamp is only added when the metadata doesn't have one.
+                let mut expected_metadata = metadata.as_object().cloned().unwrap_or_default();
+                expected_metadata
+                    .entry("timestamp")
+                    .or_insert_with(|| json!(timestamp.to_rfc3339()));
+
+                assert_eq!(&decoded, inner, "decoding {}", inner);
+                assert_eq!(
+                    decoded_metadata,
+                    Value::Object(expected_metadata),
+                    "decoding the metadata of {}",
+                    inner
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_other_json_logs() {
+        let cases = vec![
+            (r#"{"message": "hello"}"#, json!({ "message": "hello" })),
+            (
+                r#"{"log": "hello", "stream": "stdout"}"#,
+                json!({ "log": "hello", "stream": "stdout" }),
+            ),
+            (r#"{"source": "app"}"#, json!({ "source": "app" })),
+            ("[1, 2]", json!([1, 2])),
+            ("not json", json!("not json")),
+        ];
+
+        for (payload, expected) in cases {
+            assert_eq!(
+                decode_log(Encoding::Json, payload.as_bytes()).unwrap(),
+                (expected, Value::Null),
+                "decoding {}",
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_protobuf() {
+        assert!(decode_metrics(Encoding::Protobuf, "default", &[0xff, 0xff]).is_err());
+        assert!(decode_log(Encoding::Protobuf, &[0xff, 0xff]).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use eagle_core::{Metric, MetricBuilder};
+use eyre::{eyre, WrapErr};
+
+/// Parses Nagios plugin output, i.e. `TEXT | 'label'=value[UOM];[warn];[crit];[min];[max] ...`.
+/// Performance data can also follow a `|` in the long output lines.
+///
+/// Each label is reported as a gauge, or a counter when its unit is `c`, along with
+/// `<label>_warning`, `<label>_critical`, `<label>_min` and `<label>_max` when those are plain
+/// numbers. The unit is kept in the `unit` tag.
+pub fn parse(category: &str, input: &str) -> eyre::Result<Vec<Metric>> {
+    let mut perfdata = Vec::new();
+    let mut lines = input.lines();
+
+    if let Some((_, data)) = lines.next().and_then(|line| line.split_once('|')) {
+        perfdata.push(data);
+    }
+
+    // Once the long output reaches a `|`, everything after it is performance data.
+    let mut in_perfdata = false;
+
+    for line in lines {
+        if in_perfdata {
+            perfdata.push(line);
+        } else if let Some((_, data)) = line.split_once('|') {
+            in_perfdata = true;
+            perfdata.push(data);
+        }
+    }
+
+    let mut metrics = Vec::new();
+
+    for data in perfdata {
+        for item in split_perfdata(data) {
+            metrics.extend(
+                parse_item(category, item.as_str())
+                    .wrap_err_with(|| format!("Invalid performance data '{}'", item))?,
+            );
+        }
+    }
+
+    Ok(metrics)
+}
+
+/// Splits on spaces, labels being allowed to contain spaces when single-quoted.
+fn split_perfdata(data: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in data.chars() {
+        match c {
+            '\'' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    items.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        items.push(current);
+    }
+
+    items
+}
+
+fn parse_item(category: &str, item: &str) -> eyre::Result<Vec<Metric>> {
+    let (label, data) = item.rsplit_once('=').ok_or_else(|| eyre!("Missing '='"))?;
+
+    // Two consecutive quotes in a quoted label stand for a literal quote.
+    let label = label
+        .strip_prefix('\'')
+        .and_then(|label| label.strip_suffix('\''))
+        .map(|label| label.replace("''", "'"))
+        .unwrap_or_else(|| label.to_string());
+
+    let mut fields = data.split(';');
+    let value = fields.next().unwrap_or_default();
+    let unit_start = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+
+    let (number, unit) = value.split_at(unit_start);
+
+    // `U` means the actual value couldn't be determined.
+    if number.is_empty() && unit == "U" {
+        return Ok(Vec::new());
+    }
+
+    let number = number
+        .parse::<f64>()
+        .wrap_err_with(|| format!("Invalid value '{}'", value))?;
+
+    let with_unit = |metric: MetricBuilder| {
+        if unit.is_empty() {
+            metric.build()
+        } else {
+            metric.add_tag("unit", unit).build()
+        }
+    };
+
+    let mut metrics = vec![with_unit(if unit == "c" {
+        MetricBuilder::counter(category, label.as_str(), number)
+    } else {
+        MetricBuilder::gauge(category, label.as_str(), number)
+    })];
+
+    for (suffix, threshold) in ["warning", "critical", "min", "max"].iter().zip(fields) {
+        // Ranges like `10:20` or `@10:20` are not reported.
+        if let Ok(threshold) = threshold.parse::<f64>() {
+            metrics.push(with_unit(MetricBuilder::gauge(
+                category,
+                format!("{}_{}", label, suffix),
+                threshold,
+            )));
+        }
+    }
+
+    Ok(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use eagle_core::MetricType;
+
+    use super::*;
+
+    /// Name, value, type and unit.
+    type Expected<'a> = (&'a str, f64, MetricType, Option<&'a str>);
+
+    fn summary(metrics: &[Metric]) -> Vec<Expected<'_>> {
+        metrics
+            .iter()
+            .map(|metric| {
+                (
+                    metric.name.as_str(),
+                    metric.value,
+                    metric.r#type,
+                    metric.tags.get("unit").map(String::as_str),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_perfdata() {
+        let cases: &[(&str, &[Expected])] = &[
+            ("OK - all good", &[]),
+            ("OK | time=0.5", &[("time", 0.5, MetricType::Gauge, None)]),
+            (
+                "OK | time=0.5s;1;2;0;10",
+                &[
+                    ("time", 0.5, MetricType::Gauge, Some("s")),
+                    ("time_warning", 1f64, MetricType::Gauge, Some("s")),
+                    ("time_critical", 2f64, MetricType::Gauge, Some("s")),
+                    ("time_min", 0f64, MetricType::Gauge, Some("s")),
+                    ("time_max", 10f64, MetricType::Gauge, Some("s")),
+                ],
+            ),
+            (
+                "DISK OK | /=2643MB;5948;5958;0;5968 usage=45%",
+                &[
+                    ("/", 2643f64, MetricType::Gauge, Some("MB")),
+                    ("/_warning", 5948f64, MetricType::Gauge, Some("MB")),
+                    ("/_critical", 5958f64, MetricType::Gauge, Some("MB")),
+                    ("/_min", 0f64, MetricType::Gauge, Some("MB")),
+                    ("/_max", 5968f64, MetricType::Gauge, Some("MB")),
+                    ("usage", 45f64, MetricType::Gauge, Some("%")),
+                ],
+            ),
+            (
+                "OK | packets=1234c;;;0",
+                &[
+                    ("packets", 1234f64, MetricType::Counter, Some("c")),
+                    ("packets_min", 0f64, MetricType::Gauge, Some("c")),
+                ],
+            ),
+            // Ranges aren't reported, plain thresholds next to them are.
+            (
+                "OK | load=1.5;10:20;@5:~;-1;",
+                &[
+                    ("load", 1.5, MetricType::Gauge, None),
+                    ("load_min", -1f64, MetricType::Gauge, None),
+                ],
+            ),
+            (
+                "OK | 'free space'=10GB 'it''s'=1",
+                &[
+                    ("free space", 10f64, MetricType::Gauge, Some("GB")),
+                    ("it's", 1f64, MetricType::Gauge, None),
+                ],
+            ),
+            ("UNKNOWN | time=U;1;2", &[]),
+        ];
+
+        for (input, expected) in cases {
+            let metrics = parse("nagios", input).unwrap();
+
+            assert_eq!(summary(&metrics), expected.to_vec(), "{}", input);
+            assert!(metrics.iter().all(|metric| metric.category == "nagios"));
+        }
+    }
+
+    #[test]
+    fn parses_long_output() {
+        let input = "OK - first | a=1\nsecond line\nthird line | b=2\nc=3;4\n";
+        let metrics = parse("nagios", input).unwrap();
+        let names = metrics
+            .iter()
+            .map(|metric| metric.name.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["a", "b", "c", "c_warning"]);
+    }
+
+    #[test]
+    fn rejects_invalid_perfdata() {
+        for input in ["OK | time", "OK | time=abc", "OK | time=;1"] {
+            assert!(parse("nagios", input).is_err(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{TimeZone, Utc};
+use eagle_core::{Metric, MetricBuilder};
+use eyre::{bail, eyre, WrapErr};
+
+/// Suffixes of the series a histogram or a summary is made of.
+const FAMILY_SUFFIXES: &[&str] = &["_bucket", "_sum", "_count"];
+
+/// Parses the Prometheus text exposition format.
+pub fn parse(category: &str, input: &str) -> eyre::Result<Vec<Metric>> {
+    let mut types = HashMap::<String, String>::new();
+    let mut metrics = Vec::new();
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut parts = comment.split_whitespace();
+
+            if parts.next() == Some("TYPE") {
+                if let (Some(name), Some(r#type)) = (parts.next(), parts.next()) {
+                    types.insert(name.to_string(), r#type.to_string());
+                }
+            }
+
+            continue;
+        }
+
+        let metric = parse_sample(category, line, &types)
+            .wrap_err_with(|| format!("Invalid Prometheus sample at line {}", index + 1))?;
+
+        metrics.push(metric);
+    }
+
+    Ok(metrics)
+}
+
+fn family_type<'a>(name: &str, types: &'a HashMap<String, String>) -> Option<&'a str> {
+    if let Some(r#type) = types.get(name) {
+        return Some(r#type.as_str());
+    }
+
+    FAMILY_SUFFIXES
+        .iter()
+        .filter_map(|suffix| name.strip_suffix(suffix))
+        .find_map(|family| types.get(family))
+        .map(|r#type| r#type.as_str())
+}
+
+fn parse_sample(
+    category: &str,
+    line: &str,
+    types: &HashMap<String, String>,
+) -> eyre::Result<Metric> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .ok_or_else(|| eyre!("Missing sample value"))?;
+
+    let name = &line[..name_end];
+    let mut rest = &line[name_end..];
+    let mut tags = BTreeMap::new();
+
+    if rest.starts_with('{') {
+        let (labels, remaining) = parse_labels(&rest[1..])?;
+
+        tags = labels;
+        rest = remaining;
+    }
+
+    let mut fields = rest.split_whitespace();
+    let value = fields
+        .next()
+        .ok_or_else(|| eyre!("Missing sample value"))?
+        .parse::<f64>()
+        .wrap_err("Invalid sample value")?;
+
+    let is_counter = match family_type(name, types) {
+        Some("counter") => true,
+        // Buckets, sums and counts only ever go up, quantiles don't.
+        Some("histogram") | Some("summary") => {
+            FAMILY_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        }
+        _ => false,
+    };
+
+    let mut metric = if is_counter {
+        MetricBuilder::counter(category, name, value)
+    } else {
+        MetricBuilder::gauge(category, name, value)
+    };
+
+    if let Some(timestamp) = fields.next() {
+        let millis = timestamp
+            .parse::<i64>()
+            .wrap_err("Invalid sample timestamp")?;
+
+        if let Some(timestamp) = Utc.timestamp_millis_opt(millis).single() {
+            metric = metric.timestamp(timestamp);
+        }
+    }
+
+    Ok(metric.tags(tags).build())
+}
+
+/// Parses labels up to the closing brace and returns what remains after it.
+fn parse_labels(input: &str) -> eyre::Result<(BTreeMap<String, String>, &str)> {
+    let mut labels = BTreeMap::new();
+    let mut rest = input.trim_start();
+
+    loop {
+        if let Some(remaining) = rest.strip_prefix('}') {
+            return Ok((labels, remaining));
+        }
+
+        let (name, remaining) = rest
+            .split_once('=')
+            .ok_or_else(|| eyre!("Invalid label in '{}'", input))?;
+
+        let remaining = remaining
+            .trim_start()
+            .strip_prefix('"')
+            .ok_or_else(|| eyre!("Label value of '{}' must be quoted", name.trim()))?;
+
+        let mut value = String::new();
+        let mut chars = remaining.char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, c)) => value.push(c),
+                    None => bail!("Unterminated label value"),
+                },
+                Some((index, '"')) => break index,
+                Some((_, c)) => value.push(c),
+                None => bail!("Unterminated label value"),
+            }
+        };
+
+        labels.insert(name.trim().to_string(), value);
+        rest = remaining[end + 1..].trim_start();
+
+        if let Some(remaining) = rest.strip_prefix(',') {
+            rest = remaining.trim_start();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eagle_core::MetricType;
+
+    use super::*;
+
+    /// Input, name, value, type and tags.
+    type Case<'a> = (&'a str, &'a str, f64, MetricType, &'a [(&'a str, &'a str)]);
+
+    fn tags(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_samples() {
+        let cases: &[Case] = &[
+            ("up 1", "up", 1f64, MetricType::Gauge, &[]),
+            ("temp -3.5e1", "temp", -35f64, MetricType::Gauge, &[]),
+            (
+                r#"http{method="GET",code="200"} 3"#,
+                "http",
+                3f64,
+                MetricType::Gauge,
+                &[("method", "GET"), ("code", "200")],
+            ),
+            (
+                r#"http{ method = "GET" , } 3"#,
+                "http",
+                3f64,
+                MetricType::Gauge,
+                &[("method", "GET")],
+            ),
+            (
+                r#"msg{text="say \"hi\"",path="C:\\dir",lines="a\nb"} 1"#,
+                "msg",
+                1f64,
+                MetricType::Gauge,
+                &[
+                    ("text", "say \"hi\""),
+                    ("path", "C:\\dir"),
+                    ("lines", "a\nb"),
+                ],
+            ),
+            (
+                r#"msg{text="a} b,c=d"} 1"#,
+                "msg",
+                1f64,
+                MetricType::Gauge,
+                &[("text", "a} b,c=d")],
+            ),
+            ("empty{} 2", "empty", 2f64, MetricType::Gauge, &[]),
+            ("nan NaN", "nan", f64::NAN, MetricType::Gauge, &[]),
+        ];
+
+        for (input, name, value, r#type, expected) in cases {
+            let metrics = parse("app", input).unwrap();
+
+            assert_eq!(metrics.len(), 1, "{}", input);
+
+            let metric = &metrics[0];
+
+            assert_eq!(metric.category, "app", "{}", input);
+            assert_eq!(metric.name, *name, "{}", input);
+            assert!(
+                metric.value == *value || (value.is_nan() && metric.value.is_nan()),
+                "{}",
+                input
+            );
+            assert_eq!(metric.r#type, *r#type, "{}", input);
+            assert_eq!(metric.tags, tags(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn uses_type_lines() {
+        let input = r#"
+# HELP requests_total Total requests, with "quotes" and # signs.
+# TYPE requests_total counter
+requests_total{code="200"} 10
+requests_total{code="500"} 2 1700000000000
+
+# TYPE latency histogram
+latency_bucket{le="0.5"} 4
+latency_bucket{le="+Inf"} 5
+latency_sum 1.5
+latency_count 5
+
+# TYPE rpc summary
+rpc{quantile="0.99"} 0.2
+rpc_sum 12
+rpc_count 40
+
+# TYPE in_flight gauge
+in_flight 3
+untyped 1
+"#;
+
+        let metrics = parse("app", input).unwrap();
+        let types = metrics
+            .iter()
+            .map(|metric| (metric.name.as_str(), metric.r#type))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            types,
+            vec![
+                ("requests_total", MetricType::Counter),
+                ("requests_total", MetricType::Counter),
+                ("latency_bucket", MetricType::Counter),
+                ("latency_bucket", MetricType::Counter),
+                ("latency_sum", MetricType::Counter),
+                ("latency_count", MetricType::Counter),
+                ("rpc", MetricType::Gauge),
+                ("rpc_sum", MetricType::Counter),
+                ("rpc_count", MetricType::Counter),
+                ("in_flight", MetricType::Gauge),
+                ("untyped", MetricType::Gauge),
+            ]
+        );
+
+        assert_eq!(metrics[1].timestamp.timestamp_millis(), 1_700_000_000_000);
+        assert_eq!(metrics[3].tags, tags(&[("le", "+Inf")]));
+    }
+
+    #[test]
+    fn rejects_invalid_samples() {
+        let cases = [
+            "up",
+            "up abc",
+            "up 1 later",
+            r#"up{job=web} 1"#,
+            r#"up{job="web} 1"#,
+            r#"up{job} 1"#,
+        ];
+
+        for input in cases {
+            let error = parse("app", input).unwrap_err();
+
+            assert!(
+                format!("{:?}", error).contains("at line 1"),
+                "{}: {:?}",
+                input,
+                error
+            );
+        }
+    }
+}
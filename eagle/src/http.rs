@@ -0,0 +1,214 @@
+use std::{io::Read, io::Write, time::Duration};
+
+use eyre::WrapErr;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use hyper::{
+    body::{Bytes, HttpBody},
+    client::HttpConnector,
+    header::{AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH},
+    Body, Client, Request, Response, StatusCode,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use sha2::{Digest, Sha256};
+
+pub type HttpClient = Client<HttpsConnector<HttpConnector>>;
+
+/// Client for both `http` and `https` URLs, trusting the system certificate store.
+pub fn client() -> HttpClient {
+    let connector = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+
+    Client::builder().build(connector)
+}
+
+pub fn gzip(input: &[u8]) -> eyre::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+    encoder.write_all(input)?;
+    encoder.finish().wrap_err("Error when compressing body")
+}
+
+/// Sends a request built by `make_request` until it gets a response that's worth keeping, and
+/// returns its status and body. Connection errors, `429 Too Many Requests` and server errors
+/// are retried up to `retries` times, waiting twice as long after each attempt.
+pub async fn send_with_retries<F>(
+    client: &HttpClient,
+    make_request: F,
+    retries: usize,
+) -> eyre::Result<(StatusCode, Bytes)>
+where
+    F: Fn() -> eyre::Result<Request<Body>>,
+{
+    let mut backoff = Duration::from_millis(500);
+    let mut attempt = 0;
+
+    loop {
+        let outcome = match client.request(make_request()?).await {
+            Ok(response) => {
+                let status = response.status();
+                let body = hyper::body::to_bytes(response.into_body()).await;
+
+                body.map(|body| (status, body))
+            }
+            Err(e) => Err(e),
+        };
+
+        let retryable = match &outcome {
+            Ok((status, _)) => *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+            Err(_) => true,
+        };
+
+        if !retryable || attempt >= retries {
+            return outcome.wrap_err("Error when sending HTTP request");
+        }
+
+        attempt += 1;
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+pub fn response(status: StatusCode, message: impl Into<String>) -> Response<Body> {
+    let mut response = Response::new(Body::from(message.into()));
+
+    *response.status_mut() = status;
+    response
+}
+
+/// Whether the request carries `Authorization: Bearer <token>`, or `Token <token>` as InfluxDB
+/// clients send it. Everything is authorized when there is no token to check.
+pub fn is_authorized<B>(request: &Request<B>, token: Option<&str>) -> bool {
+    let token = match token {
+        Some(token) => token,
+        None => return true,
+    };
+
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            value
+                .strip_prefix("Bearer ")
+                .or_else(|| value.strip_prefix("Token "))
+        })
+        .is_some_and(|candidate| constant_time_eq(candidate, token))
+}
+
+/// Compares SHA-256 digests of both sides without stopping at the first different byte, so
+/// response times tell neither how much of a token was right nor how long it is.
+fn constant_time_eq(left: &str, right: &str) -> bool {
+    let left = Sha256::digest(left.as_bytes());
+    let right = Sha256::digest(right.as_bytes());
+
+    left.iter()
+        .zip(right.iter())
+        .fold(0u8, |diff, (left, right)| diff | (left ^ right))
+        == 0
+}
+
+/// Reads the whole request body, decompressing it when it's gzip encoded. Both the body as
+/// sent and once decompressed must fit in `max_body_size`. Errors are returned as the response
+/// to send back.
+pub async fn read_body(
+    request: Request<Body>,
+    max_body_size: usize,
+) -> Result<Vec<u8>, Response<Body>> {
+    let too_large = || {
+        response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Body exceeds {} bytes", max_body_size),
+        )
+    };
+
+    let content_length = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+
+    if content_length.is_some_and(|length| length > max_body_size) {
+        return Err(too_large());
+    }
+
+    let encoding = request
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase());
+
+    let gzip = match encoding.as_deref() {
+        None | Some("identity") => false,
+        Some("gzip") | Some("x-gzip") => true,
+        Some(other) => {
+            return Err(response(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Unsupported content encoding '{}'", other),
+            ))
+        }
+    };
+
+    let mut body = request.into_body();
+    let mut bytes = Vec::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| {
+            response(
+                StatusCode::BAD_REQUEST,
+                format!("Error when reading body: {}", e),
+            )
+        })?;
+
+        if bytes.len() + chunk.len() > max_body_size {
+            return Err(too_large());
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    if !gzip {
+        return Ok(bytes);
+    }
+
+    let mut decompressed = Vec::new();
+
+    GzDecoder::new(bytes.as_slice())
+        .take(max_body_size as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| response(StatusCode::BAD_REQUEST, format!("Invalid gzip body: {}", e)))?;
+
+    if decompressed.len() > max_body_size {
+        return Err(too_large());
+    }
+
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_tokens() {
+        let cases = [
+            ("secret", "secret", true),
+            ("secret", "secreT", false),
+            ("secret", "secret2", false),
+            ("secret", "", false),
+            ("", "", true),
+        ];
+
+        for (left, right, expected) in cases {
+            assert_eq!(
+                constant_time_eq(left, right),
+                expected,
+                "{} {}",
+                left,
+                right
+            );
+        }
+    }
+}
@@ -1,4 +1,8 @@
 pub mod engines;
+pub mod formats;
+pub mod http;
+pub mod lines;
 pub mod sinks;
 pub mod sources;
+pub mod tls;
 pub mod transformers;
@@ -0,0 +1,271 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use eagle_core::{EagleClient, Metric};
+use eyre::{bail, WrapErr};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
+    net::{TcpListener, UdpSocket},
+};
+
+/// Receives UDP datagrams on `address`, each holding one or more lines parsed by `parse`.
+/// Datagrams larger than `max_size` are dropped, as they were truncated to fit it.
+pub async fn listen_udp<F>(
+    address: &str,
+    client: &EagleClient,
+    max_size: usize,
+    parse: F,
+) -> eyre::Result<()>
+where
+    F: Fn(&str) -> eyre::Result<Vec<Metric>>,
+{
+    let socket = UdpSocket::bind(address)
+        .await
+        .wrap_err_with(|| format!("Error when binding UDP socket on {}", address))?;
+
+    receive_datagrams(&socket, client, max_size, &parse).await
+}
+
+/// Accepts TCP connections on `address`, each sending lines parsed by `parse`. A connection is
+/// closed as soon as it sends a line larger than `max_size`.
+pub async fn listen_tcp<F>(
+    address: &str,
+    client: &EagleClient,
+    max_size: usize,
+    parse: F,
+) -> eyre::Result<()>
+where
+    F: Fn(&str) -> eyre::Result<Vec<Metric>> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(address)
+        .await
+        .wrap_err_with(|| format!("Error when binding TCP listener on {}", address))?;
+
+    let parse = Arc::new(parse);
+
+    loop {
+        let (stream, peer) = listener
+            .accept()
+            .await
+            .wrap_err("Error when accepting TCP connection")?;
+
+        let client = client.clone();
+        let parse = parse.clone();
+
+        tokio::spawn(async move {
+            let result = receive_lines(stream, peer, &client, max_size, parse.as_ref()).await;
+
+            if let Err(e) = result {
+                tracing::warn!(
+                    target = client.origin().instance_id(),
+                    "Connection from {} closed: {:?}",
+                    peer,
+                    e
+                );
+            }
+        });
+    }
+}
+
+async fn receive_datagrams<F>(
+    socket: &UdpSocket,
+    client: &EagleClient,
+    max_size: usize,
+    parse: &F,
+) -> eyre::Result<()>
+where
+    F: Fn(&str) -> eyre::Result<Vec<Metric>>,
+{
+    // One extra byte tells datagrams that were truncated to fit the buffer.
+    let mut buffer = vec![0u8; max_size + 1];
+
+    loop {
+        let (size, peer) = socket
+            .recv_from(&mut buffer)
+            .await
+            .wrap_err("Error when receiving UDP datagram")?;
+
+        if size > max_size {
+            tracing::warn!(
+                target = client.origin().instance_id(),
+                "Datagram from {} dropped, it exceeds the size limit of {} bytes",
+                peer,
+                max_size
+            );
+
+            continue;
+        }
+
+        let input = String::from_utf8_lossy(&buffer[..size]);
+        let mut metrics = Vec::new();
+
+        for line in input.lines() {
+            parse_line_lenient(client, line, peer, parse, &mut metrics);
+        }
+
+        if !metrics.is_empty() {
+            client.send_metrics(metrics).await?;
+        }
+    }
+}
+
+async fn receive_lines<R, F>(
+    stream: R,
+    peer: SocketAddr,
+    client: &EagleClient,
+    max_size: usize,
+    parse: &F,
+) -> eyre::Result<()>
+where
+    R: AsyncRead + Unpin,
+    F: Fn(&str) -> eyre::Result<Vec<Metric>>,
+{
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+
+        (&mut reader)
+            .take(max_size as u64 + 1)
+            .read_until(b'\n', &mut line)
+            .await?;
+
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        if line.len() > max_size {
+            bail!("Line exceeds the size limit of {} bytes", max_size);
+        }
+
+        let mut metrics = Vec::new();
+
+        parse_line_lenient(
+            client,
+            String::from_utf8_lossy(&line).as_ref(),
+            peer,
+            parse,
+            &mut metrics,
+        );
+
+        if !metrics.is_empty() {
+            client.send_metrics(metrics).await?;
+        }
+    }
+}
+
+/// There is no way to report errors back over UDP and TCP, so invalid lines are only logged.
+fn parse_line_lenient<F>(
+    client: &EagleClient,
+    line: &str,
+    peer: SocketAddr,
+    parse: &F,
+    metrics: &mut Vec<Metric>,
+) where
+    F: Fn(&str) -> eyre::Result<Vec<Metric>>,
+{
+    match parse(line) {
+        Ok(parsed) => metrics.extend(parsed),
+        Err(e) => tracing::warn!(
+            target = client.origin().instance_id(),
+            "Invalid line '{}' from {}: {:#}",
+            line.trim_end(),
+            peer,
+            e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eagle_core::{poll::Schedule, EagleEndpoint, EagleEvent, Event, MetricBuilder, Origin};
+    use eyre::eyre;
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    fn client() -> (EagleClient, UnboundedReceiver<EagleEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let client = EagleClient {
+            origin: Arc::new(Origin::new("lines")),
+            endpoint: EagleEndpoint::new(sender),
+            schedule: Schedule::default(),
+        };
+
+        (client, receiver)
+    }
+
+    /// Takes each line as the name of a gauge, and rejects those starting with `!`.
+    fn parse(line: &str) -> eyre::Result<Vec<Metric>> {
+        match line.trim() {
+            "" => Ok(Vec::new()),
+            line if line.starts_with('!') => Err(eyre!("Invalid")),
+            line => Ok(vec![MetricBuilder::gauge("test", line, 1.0).build()]),
+        }
+    }
+
+    fn names(receiver: &mut UnboundedReceiver<EagleEvent>) -> Vec<String> {
+        let mut names = Vec::new();
+
+        while let Ok(event) = receiver.try_recv() {
+            match event.event {
+                Event::Metric(metric) => names.push(metric.name),
+                other => panic!("Unexpected event {:?}", other),
+            }
+        }
+
+        names
+    }
+
+    #[tokio::test]
+    async fn splits_datagrams_into_lines() {
+        let (client, mut receiver) = client();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        tokio::spawn(async move { receive_datagrams(&socket, &client, 16, &parse).await });
+
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let datagrams = ["a\n!b\r\nc", "this one is too long", "d\n"];
+
+        for datagram in datagrams {
+            peer.send_to(datagram.as_bytes(), address).await.unwrap();
+        }
+
+        let mut received = Vec::new();
+
+        for _ in 0..3 {
+            match receiver.recv().await.unwrap().event {
+                Event::Metric(metric) => received.push(metric.name),
+                other => panic!("Unexpected event {:?}", other),
+            }
+        }
+
+        assert_eq!(received, vec!["a", "c", "d"]);
+        assert!(names(&mut receiver).is_empty());
+    }
+
+    #[tokio::test]
+    async fn reads_lines_up_to_the_limit() {
+        let (client, mut receiver) = client();
+        let peer = SocketAddr::from(([127, 0, 0, 1], 4000));
+
+        let input: &[u8] = b"a\n!b\n\nc";
+        receive_lines(input, peer, &client, 16, &parse)
+            .await
+            .unwrap();
+
+        assert_eq!(names(&mut receiver), vec!["a", "c"]);
+
+        let input: &[u8] = b"a\nthis one is too long\nb\n";
+        let error = receive_lines(input, peer, &client, 16, &parse)
+            .await
+            .unwrap_err();
+
+        assert!(
+            format!("{}", error).contains("size limit of 16 bytes"),
+            "{}",
+            error
+        );
+        assert_eq!(names(&mut receiver), vec!["a"]);
+    }
+}
@@ -1,3 +1,26 @@
 mod console;
+pub mod elasticsearch;
+mod graphite;
+mod influx;
+pub mod kafka;
+pub mod loki;
+
+use chrono::{DateTime, Utc};
+use eagle_core::Log;
 
 pub use console::Console;
+pub use elasticsearch::Elasticsearch;
+pub use graphite::Graphite;
+pub use influx::Influx;
+pub use kafka::Kafka;
+pub use loki::Loki;
+
+/// When a log happened according to its `timestamp` metadata field, if it's an RFC 3339 date.
+/// Falls back to now otherwise.
+pub(crate) fn log_timestamp(log: &Log) -> DateTime<Utc> {
+    log.metadata
+        .get("timestamp")
+        .and_then(|value| value.as_str())
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map_or_else(Utc::now, |value| value.with_timezone(&Utc))
+}
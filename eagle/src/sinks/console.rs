@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use eagle_core::{EagleMsg, EagleStream, MetricEvent, MetricSink, Origin, Recv};
+use eagle_core::{EagleMsg, EagleStream, LogEvent, LogSink, MetricEvent, MetricSink, Origin, Recv};
 
 pub struct Console;
 
@@ -31,3 +31,32 @@ impl MetricSink for Console {
         Ok(())
     }
 }
+
+#[async_trait::async_trait]
+impl LogSink for Console {
+    async fn process(
+        &mut self,
+        _: Arc<Origin>,
+        mut stream: EagleStream<LogEvent>,
+    ) -> eyre::Result<()> {
+        while let Recv::Available(msg) = stream.recv().await {
+            match msg {
+                EagleMsg::Tick => {}
+                EagleMsg::Shutdown => {
+                    break;
+                }
+                EagleMsg::Msg(event) => {
+                    println!(
+                        "Source '{source}', InstanceId '{instance_id}', Log: {log}, Metadata: {metadata}",
+                        source = event.origin.name,
+                        instance_id = event.origin.instance_id,
+                        log = event.log.inner,
+                        metadata = event.log.metadata,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
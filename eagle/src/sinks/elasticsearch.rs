@@ -0,0 +1,580 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Utc,
+};
+use eagle_core::{EagleMsg, EagleStream, Log, LogEvent, LogSink, Origin, Recv};
+use eyre::{bail, eyre};
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Body, Method, Request,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    http::{self, HttpClient},
+    sinks::log_timestamp,
+};
+
+/// Bulk action used to write documents.
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    #[default]
+    Index,
+    /// Required by data streams.
+    Create,
+}
+
+#[derive(Debug, PartialEq)]
+enum IndexPart {
+    /// Formatted with the event timestamp, as in `%Y.%m.%d`.
+    Date(String),
+    /// Replaced by a metadata or log field, as in `{tag}`.
+    Field(String),
+}
+
+/// A bulk item, i.e. its action line and its document line.
+struct BulkItem {
+    action: String,
+    document: String,
+}
+
+impl BulkItem {
+    fn size(&self) -> usize {
+        self.action.len() + self.document.len() + 2
+    }
+}
+
+/// Writes logs to Elasticsearch or OpenSearch through the `_bulk` API.
+///
+/// Logs that are JSON objects are the documents, other values end up in their `message`
+/// field. Documents get an `@timestamp` field unless they already have one, and the log
+/// metadata is kept in their `metadata` field.
+///
+/// Items rejected because the cluster is overloaded are retried on their own, while items
+/// rejected for any other reason, like a mapping conflict, are dropped.
+pub struct Elasticsearch {
+    url: String,
+    index: Vec<IndexPart>,
+    action: Action,
+    authorization: Option<String>,
+    batch_size: usize,
+    max_batch_bytes: usize,
+    period: Duration,
+    retries: usize,
+}
+
+impl Elasticsearch {
+    /// `url` is the cluster address, like `http://localhost:9200`.
+    pub fn new(url: impl AsRef<str>) -> Self {
+        Self {
+            url: format!("{}/_bulk", url.as_ref().trim_end_matches('/')),
+            index: parse_index("eagle-%Y.%m.%d").unwrap(),
+            action: Action::default(),
+            authorization: None,
+            batch_size: 500,
+            max_batch_bytes: 5 * 1_024 * 1_024,
+            period: Duration::from_secs(1),
+            retries: 3,
+        }
+    }
+
+    /// Index name template, where `strftime` specifiers like `%Y.%m.%d` are replaced using the
+    /// event timestamp and `{field}` by a metadata field, or a log field when the metadata
+    /// doesn't have it. Missing fields are replaced by `unknown`. Defaults to `eagle-%Y.%m.%d`.
+    pub fn index(self, template: impl AsRef<str>) -> eyre::Result<Self> {
+        Ok(Self {
+            index: parse_index(template.as_ref())?,
+            ..self
+        })
+    }
+
+    pub fn action(self, action: Action) -> Self {
+        Self { action, ..self }
+    }
+
+    pub fn basic_auth(self, username: impl AsRef<str>, password: impl AsRef<str>) -> Self {
+        let credentials = format!("{}:{}", username.as_ref(), password.as_ref());
+
+        Self {
+            authorization: Some(format!("Basic {}", base64::encode(credentials))),
+            ..self
+        }
+    }
+
+    /// `api_key` is the base64 encoded `id:api_key`, what Elasticsearch returns as `encoded`
+    /// when creating a key.
+    pub fn api_key(self, api_key: impl AsRef<str>) -> Self {
+        Self {
+            authorization: Some(format!("ApiKey {}", api_key.as_ref())),
+            ..self
+        }
+    }
+
+    /// A batch is written as soon as it holds that many documents.
+    pub fn batch_size(self, batch_size: usize) -> Self {
+        Self { batch_size, ..self }
+    }
+
+    /// A batch is written as soon as its body reaches that many bytes.
+    pub fn max_batch_bytes(self, max_batch_bytes: usize) -> Self {
+        Self {
+            max_batch_bytes,
+            ..self
+        }
+    }
+
+    /// Pending documents are written at least that often.
+    pub fn period(self, period: Duration) -> Self {
+        Self { period, ..self }
+    }
+
+    /// Applies both to whole requests and to the items of a batch that were rejected.
+    pub fn retries(self, retries: usize) -> Self {
+        Self { retries, ..self }
+    }
+
+    fn item(&self, log: &Log) -> BulkItem {
+        let timestamp = log_timestamp(log);
+        let index = self.index_name(log, timestamp);
+        let action = match self.action {
+            Action::Index => "index",
+            Action::Create => "create",
+        };
+
+        let mut document = match log.inner.as_ref() {
+            Value::Object(fields) => fields.clone(),
+            value => {
+                let mut fields = serde_json::Map::new();
+
+                fields.insert("message".to_string(), value.clone());
+                fields
+            }
+        };
+
+        document
+            .entry("@timestamp")
+            .or_insert_with(|| Value::String(timestamp.to_rfc3339()));
+
+        if log.metadata.as_object().is_some_and(|map| !map.is_empty()) {
+            document.insert("metadata".to_string(), log.metadata.clone());
+        }
+
+        BulkItem {
+            action: json!({ action: { "_index": index } }).to_string(),
+            document: Value::Object(document).to_string(),
+        }
+    }
+
+    fn index_name(&self, log: &Log, timestamp: DateTime<Utc>) -> String {
+        let mut name = String::new();
+
+        for part in self.index.iter() {
+            match part {
+                IndexPart::Date(format) => {
+                    name.push_str(timestamp.format(format).to_string().as_str())
+                }
+                IndexPart::Field(field) => {
+                    let value = log
+                        .metadata
+                        .get(field.as_str())
+                        .or_else(|| log.inner.get(field.as_str()));
+
+                    match value {
+                        Some(Value::String(value)) => name.push_str(value),
+                        Some(value @ Value::Number(_)) | Some(value @ Value::Bool(_)) => {
+                            name.push_str(value.to_string().as_str())
+                        }
+                        _ => name.push_str("unknown"),
+                    }
+                }
+            }
+        }
+
+        sanitize_index(name.as_str())
+    }
+
+    fn request(&self, body: &[u8]) -> eyre::Result<Request<Body>> {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.as_str())
+            .header(CONTENT_TYPE, "application/x-ndjson");
+
+        if let Some(authorization) = self.authorization.as_ref() {
+            request = request.header(AUTHORIZATION, authorization.as_str());
+        }
+
+        Ok(request.body(Body::from(body.to_vec()))?)
+    }
+
+    async fn write(&self, origin: &Origin, client: &HttpClient, mut items: Vec<BulkItem>) {
+        let mut backoff = Duration::from_millis(500);
+        let mut attempt = 0;
+
+        loop {
+            let mut body = Vec::new();
+
+            for item in items.iter() {
+                body.extend_from_slice(item.action.as_bytes());
+                body.push(b'\n');
+                body.extend_from_slice(item.document.as_bytes());
+                body.push(b'\n');
+            }
+
+            let outcome = http::send_with_retries(client, || self.request(&body), self.retries)
+                .await
+                .and_then(|(status, body)| {
+                    if !status.is_success() {
+                        bail!(
+                            "Bulk request failed with status {}: {}",
+                            status,
+                            String::from_utf8_lossy(&body)
+                        );
+                    }
+
+                    failed_items(origin, body.as_ref(), items.len())
+                });
+
+            let retryable = match outcome {
+                Ok(retryable) => retryable,
+                Err(e) => {
+                    tracing::error!(
+                        target = origin.instance_id(),
+                        "Error when writing to Elasticsearch: {:?}",
+                        e
+                    );
+
+                    metrics::counter!("elasticsearch.sink.failures", items.len() as u64);
+                    return;
+                }
+            };
+
+            metrics::counter!(
+                "elasticsearch.sink.successes",
+                (items.len() - retryable.len()) as u64
+            );
+
+            if retryable.is_empty() {
+                return;
+            }
+
+            if attempt >= self.retries {
+                tracing::error!(
+                    target = origin.instance_id(),
+                    "Giving up on {} documents rejected by Elasticsearch",
+                    retryable.len()
+                );
+
+                metrics::counter!("elasticsearch.sink.failures", retryable.len() as u64);
+                return;
+            }
+
+            items = items
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| retryable.contains(index))
+                .map(|(_, item)| item)
+                .collect();
+
+            attempt += 1;
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BulkResponse {
+    errors: bool,
+    #[serde(default)]
+    items: Vec<serde_json::Map<String, Value>>,
+}
+
+/// Returns the positions of the items worth retrying, i.e. those rejected with `429 Too Many
+/// Requests` or a server error. Other failures are logged and counted.
+fn failed_items(origin: &Origin, body: &[u8], count: usize) -> eyre::Result<HashSet<usize>> {
+    let response = serde_json::from_slice::<BulkResponse>(body)
+        .map_err(|e| eyre!("Invalid bulk response: {}", e))?;
+
+    if !response.errors {
+        return Ok(HashSet::new());
+    }
+
+    if response.items.len() != count {
+        bail!(
+            "Bulk response has {} items instead of {}",
+            response.items.len(),
+            count
+        );
+    }
+
+    let mut retryable = HashSet::new();
+
+    for (index, item) in response.items.iter().enumerate() {
+        // Each item is an object whose only key is the action.
+        let result = match item.values().next() {
+            Some(result) => result,
+            None => continue,
+        };
+
+        let status = result.get("status").and_then(Value::as_u64).unwrap_or(0);
+
+        if (200..300).contains(&status) {
+            continue;
+        }
+
+        if status == 429 || status >= 500 {
+            retryable.insert(index);
+            continue;
+        }
+
+        tracing::error!(
+            target = origin.instance_id(),
+            "Elasticsearch rejected a document with status {}: {}",
+            status,
+            result.get("error").cloned().unwrap_or_default()
+        );
+
+        metrics::counter!("elasticsearch.sink.failures", 1);
+    }
+
+    Ok(retryable)
+}
+
+fn parse_index(template: &str) -> eyre::Result<Vec<IndexPart>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+
+    while !rest.is_empty() {
+        let (date, field) = match rest.split_once('{') {
+            Some((date, remaining)) => {
+                let (field, remaining) = remaining
+                    .split_once('}')
+                    .ok_or_else(|| eyre!("Unclosed '{{' in index '{}'", template))?;
+
+                rest = remaining;
+                (date, Some(field))
+            }
+            None => (std::mem::take(&mut rest), None),
+        };
+
+        if !date.is_empty() {
+            if StrftimeItems::new(date).any(|item| matches!(item, Item::Error)) {
+                bail!("Invalid date format in index '{}'", template);
+            }
+
+            parts.push(IndexPart::Date(date.to_string()));
+        }
+
+        if let Some(field) = field {
+            if field.is_empty() {
+                bail!("Empty field name in index '{}'", template);
+            }
+
+            parts.push(IndexPart::Field(field.to_string()));
+        }
+    }
+
+    Ok(parts)
+}
+
+/// Index names must be lowercase, can't contain some characters and can't start with `-`, `_`
+/// or `+`.
+fn sanitize_index(name: &str) -> String {
+    let name = name.to_lowercase().replace(
+        ['\\', '/', '*', '?', '"', '<', '>', '|', ' ', ',', '#', ':'],
+        "_",
+    );
+
+    name.trim_start_matches(['-', '_', '+']).to_string()
+}
+
+#[async_trait::async_trait]
+impl LogSink for Elasticsearch {
+    async fn process(
+        &mut self,
+        origin: Arc<Origin>,
+        mut stream: EagleStream<LogEvent>,
+    ) -> eyre::Result<()> {
+        let client = http::client();
+        let mut clock = Instant::now();
+        let mut items = Vec::new();
+        let mut bytes = 0usize;
+
+        while let Recv::Available(msg) = stream.recv().await {
+            let shutdown = match msg {
+                EagleMsg::Tick => {
+                    if items.is_empty() || clock.elapsed() < self.period {
+                        continue;
+                    }
+
+                    false
+                }
+                EagleMsg::Msg(event) => {
+                    let item = self.item(event.log.as_ref());
+
+                    bytes += item.size();
+                    items.push(item);
+
+                    if items.len() < self.batch_size && bytes < self.max_batch_bytes {
+                        continue;
+                    }
+
+                    false
+                }
+                EagleMsg::Shutdown => true,
+            };
+
+            if !items.is_empty() {
+                self.write(&origin, &client, std::mem::take(&mut items))
+                    .await;
+            }
+
+            bytes = 0;
+            clock = Instant::now();
+
+            if shutdown {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn log(inner: Value, metadata: Value) -> Log {
+        Log {
+            inner: Arc::new(inner),
+            metadata,
+        }
+    }
+
+    #[test]
+    fn parses_indices() {
+        let date = |format: &str| IndexPart::Date(format.to_string());
+        let field = |name: &str| IndexPart::Field(name.to_string());
+
+        let cases = vec![
+            ("eagle", vec![date("eagle")]),
+            ("eagle-%Y.%m.%d", vec![date("eagle-%Y.%m.%d")]),
+            (
+                "logs-{tag}-%Y",
+                vec![date("logs-"), field("tag"), date("-%Y")],
+            ),
+            (
+                "{source}{host.name}",
+                vec![field("source"), field("host.name")],
+            ),
+        ];
+
+        for (template, expected) in cases {
+            assert_eq!(
+                parse_index(template).unwrap(),
+                expected,
+                "parsing {}",
+                template
+            );
+        }
+
+        for template in ["logs-{tag", "logs-{}", "logs-%Q", "logs-%"] {
+            assert!(parse_index(template).is_err(), "rejecting {}", template);
+        }
+    }
+
+    #[test]
+    fn sanitizes_indices() {
+        let cases = vec![
+            ("eagle-2024.01.02", "eagle-2024.01.02"),
+            ("Eagle-Logs", "eagle-logs"),
+            ("logs/app:web 1", "logs_app_web_1"),
+            (r#"a\b*c?d"e<f>g|h,i#j"#, "a_b_c_d_e_f_g_h_i_j"),
+            ("_logs", "logs"),
+            ("-+_logs-", "logs-"),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(sanitize_index(name), expected, "sanitizing {}", name);
+        }
+    }
+
+    #[test]
+    fn names_indices() {
+        let timestamp = Utc.ymd(2024, 1, 2).and_hms(3, 4, 5);
+        let cases = vec![
+            ("eagle-%Y.%m.%d", json!({}), json!({}), "eagle-2024.01.02"),
+            (
+                "logs-{tag}-%Y",
+                json!({ "tag": "Web/App" }),
+                json!({ "tag": "ignored" }),
+                "logs-web_app-2024",
+            ),
+            ("logs-{level}", json!({}), json!({ "level": 3 }), "logs-3"),
+            ("logs-{tag}", json!({}), json!("line"), "logs-unknown"),
+            ("{tag}", json!({ "tag": "_private" }), json!({}), "private"),
+        ];
+
+        for (template, metadata, inner, expected) in cases {
+            let sink = Elasticsearch::new("http://localhost:9200")
+                .index(template)
+                .unwrap();
+
+            assert_eq!(
+                sink.index_name(&log(inner, metadata), timestamp),
+                expected,
+                "naming with {}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn finds_retryable_items() {
+        let origin = Origin::new("elasticsearch");
+        let cases = vec![
+            (json!({ "errors": false, "items": [] }), 2, vec![]),
+            (
+                json!({
+                    "errors": true,
+                    "items": [
+                        { "index": { "status": 201 } },
+                        { "index": { "status": 429, "error": { "type": "es_rejected_execution_exception" } } },
+                        { "index": { "status": 400, "error": { "type": "mapper_parsing_exception" } } },
+                        { "create": { "status": 503 } },
+                        { "create": { "status": 409 } },
+                        {},
+                    ],
+                }),
+                6,
+                vec![1, 3],
+            ),
+        ];
+
+        for (response, count, expected) in cases {
+            let body = response.to_string();
+
+            assert_eq!(
+                failed_items(&origin, body.as_bytes(), count).unwrap(),
+                expected.into_iter().collect::<HashSet<_>>(),
+                "reading {}",
+                body
+            );
+        }
+
+        let mismatched = json!({ "errors": true, "items": [{ "index": { "status": 429 } }] });
+
+        assert!(failed_items(&origin, mismatched.to_string().as_bytes(), 2).is_err());
+        assert!(failed_items(&origin, b"not json", 1).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eagle_core::{EagleMsg, EagleStream, MetricEvent, MetricSink, Origin, Recv};
+use eyre::{eyre, WrapErr};
+use tokio::{io::AsyncWriteExt, net::TcpStream};
+
+use crate::formats::graphite::Templates;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Writes metrics to a Carbon server over TCP with the Graphite plaintext protocol, paths being
+/// built by the first matching template, see `formats::graphite::Template`.
+///
+/// When the connection drops, metrics are kept while reconnecting, waiting longer after each
+/// failed attempt. Once `buffer_size` lines are pending, new metrics are dropped.
+pub struct Graphite {
+    address: String,
+    templates: Templates,
+    tagged: bool,
+    buffer_size: usize,
+}
+
+impl Graphite {
+    pub fn new(address: impl AsRef<str>) -> Self {
+        Self {
+            address: address.as_ref().to_string(),
+            templates: Templates::default(),
+            tagged: false,
+            buffer_size: 100_000,
+        }
+    }
+
+    pub fn templates(self, templates: Templates) -> Self {
+        Self { templates, ..self }
+    }
+
+    /// Appends tags that aren't part of the path as Graphite tags, as in `path;tag=value`.
+    /// Requires Graphite 1.1 or later.
+    pub fn tagged(self, tagged: bool) -> Self {
+        Self { tagged, ..self }
+    }
+
+    pub fn buffer_size(self, buffer_size: usize) -> Self {
+        Self {
+            buffer_size,
+            ..self
+        }
+    }
+}
+
+struct Connection {
+    stream: Option<TcpStream>,
+    backoff: Duration,
+    retry_at: Instant,
+}
+
+impl Connection {
+    fn new() -> Self {
+        Self {
+            stream: None,
+            backoff: MIN_BACKOFF,
+            retry_at: Instant::now(),
+        }
+    }
+
+    fn failed(&mut self) {
+        self.stream = None;
+        self.retry_at = Instant::now() + self.backoff;
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+
+    /// Writes everything or nothing is considered written, so lines might be sent twice after
+    /// a failure.
+    async fn send(&mut self, address: &str, lines: &str) -> eyre::Result<()> {
+        if self.stream.is_none() {
+            let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+                .await
+                .map_err(|_| eyre!("Timed out when connecting to {}", address))?
+                .wrap_err_with(|| format!("Error when connecting to {}", address))?;
+
+            self.stream = Some(stream);
+        }
+
+        let stream = self.stream.as_mut().unwrap();
+
+        stream.write_all(lines.as_bytes()).await?;
+        stream.flush().await?;
+        self.backoff = MIN_BACKOFF;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl MetricSink for Graphite {
+    async fn process(
+        &mut self,
+        origin: Arc<Origin>,
+        mut stream: EagleStream<MetricEvent>,
+    ) -> eyre::Result<()> {
+        let mut connection = Connection::new();
+        let mut lines = String::new();
+        let mut buffered = 0usize;
+
+        while let Recv::Available(msg) = stream.recv().await {
+            let shutdown = match msg {
+                EagleMsg::Tick => false,
+                EagleMsg::Msg(event) => {
+                    if buffered >= self.buffer_size {
+                        metrics::counter!("graphite.sink.dropped", 1);
+                    } else if let Some(line) = self.templates.format(&event.metric, self.tagged) {
+                        lines.push_str(line.as_str());
+                        buffered += 1;
+                    }
+
+                    continue;
+                }
+                EagleMsg::Shutdown => true,
+            };
+
+            let waiting = Instant::now() < connection.retry_at;
+
+            // On shutdown, one last attempt is made even when waiting to reconnect.
+            if !lines.is_empty() && (shutdown || !waiting) {
+                match connection.send(self.address.as_str(), lines.as_str()).await {
+                    Ok(()) => {
+                        metrics::counter!("graphite.sink.successes", buffered as u64);
+                        lines.clear();
+                        buffered = 0;
+                    }
+
+                    Err(e) => {
+                        tracing::error!(
+                            target = origin.instance_id(),
+                            "Error when writing to Graphite, retrying in {:?}: {:?}",
+                            connection.backoff,
+                            e
+                        );
+
+                        connection.failed();
+                    }
+                }
+            }
+
+            if shutdown {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
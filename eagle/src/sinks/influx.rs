@@ -0,0 +1,280 @@
+use std::{
+    fmt::Write,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eagle_core::{EagleMsg, EagleStream, Metric, MetricEvent, MetricSink, Origin, Recv};
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE},
+    Body, Method, Request,
+};
+
+use crate::http::{self, HttpClient};
+
+/// Writes metrics as InfluxDB line protocol to an HTTP endpoint, the category being the
+/// measurement and the metric name the field key. Timestamps are sent in nanoseconds.
+///
+/// The URL is used as is, so it must carry the database or bucket, as in
+/// `http://localhost:8086/write?db=eagle` or `http://localhost:8086/api/v2/write?org=o&bucket=b`.
+pub struct Influx {
+    url: String,
+    token: Option<String>,
+    batch_size: usize,
+    period: Duration,
+    gzip: bool,
+    retries: usize,
+}
+
+impl Influx {
+    pub fn new(url: impl AsRef<str>) -> Self {
+        Self {
+            url: url.as_ref().to_string(),
+            token: None,
+            batch_size: 1_000,
+            period: Duration::from_secs(10),
+            gzip: true,
+            retries: 3,
+        }
+    }
+
+    /// Sent as `Authorization: Token <token>`.
+    pub fn token(self, token: impl AsRef<str>) -> Self {
+        Self {
+            token: Some(token.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    /// A batch is written as soon as it holds that many metrics.
+    pub fn batch_size(self, batch_size: usize) -> Self {
+        Self { batch_size, ..self }
+    }
+
+    /// Pending metrics are written at least that often.
+    pub fn period(self, period: Duration) -> Self {
+        Self { period, ..self }
+    }
+
+    pub fn gzip(self, gzip: bool) -> Self {
+        Self { gzip, ..self }
+    }
+
+    pub fn retries(self, retries: usize) -> Self {
+        Self { retries, ..self }
+    }
+
+    fn request(&self, body: &[u8]) -> eyre::Result<Request<Body>> {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.as_str())
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8");
+
+        if let Some(token) = self.token.as_ref() {
+            request = request.header(AUTHORIZATION, format!("Token {}", token));
+        }
+
+        if self.gzip {
+            request = request.header(CONTENT_ENCODING, "gzip");
+        }
+
+        Ok(request.body(Body::from(body.to_vec()))?)
+    }
+
+    async fn write(&self, origin: &Origin, client: &HttpClient, lines: &str) {
+        let body = if self.gzip {
+            match http::gzip(lines.as_bytes()) {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::error!(target = origin.instance_id(), "{:?}", e);
+                    return;
+                }
+            }
+        } else {
+            lines.as_bytes().to_vec()
+        };
+
+        match http::send_with_retries(client, || self.request(&body), self.retries).await {
+            Ok((status, _)) if status.is_success() => {
+                metrics::counter!("influx.sink.successes", 1);
+            }
+
+            Ok((status, body)) => {
+                tracing::error!(
+                    target = origin.instance_id(),
+                    "InfluxDB rejected the write with status {}: {}",
+                    status,
+                    String::from_utf8_lossy(&body)
+                );
+
+                metrics::counter!("influx.sink.failures", 1);
+            }
+
+            Err(e) => {
+                tracing::error!(
+                    target = origin.instance_id(),
+                    "Error when writing to InfluxDB: {:?}",
+                    e
+                );
+
+                metrics::counter!("influx.sink.failures", 1);
+            }
+        }
+    }
+}
+
+/// Escapes what would otherwise be taken as a separator, and backslashes so that they aren't
+/// taken as escaping what follows.
+fn escape(output: &mut String, input: &str, special: &[char]) {
+    for c in input.chars() {
+        if c == '\\' || special.contains(&c) {
+            output.push('\\');
+        }
+
+        output.push(c);
+    }
+}
+
+fn write_line(output: &mut String, metric: &Metric) {
+    // Line protocol has no representation for those.
+    if !metric.value.is_finite() {
+        return;
+    }
+
+    escape(output, metric.category.as_str(), &[',', ' ']);
+
+    for (name, value) in metric.tags.iter() {
+        // Empty tag values are rejected.
+        if value.is_empty() {
+            continue;
+        }
+
+        output.push(',');
+        escape(output, name, &[',', '=', ' ']);
+        output.push('=');
+        escape(output, value, &[',', '=', ' ']);
+    }
+
+    output.push(' ');
+    escape(output, metric.name.as_str(), &[',', '=', ' ']);
+
+    let _ = writeln!(
+        output,
+        "={:?} {}",
+        metric.value,
+        metric.timestamp.timestamp_nanos()
+    );
+}
+
+#[async_trait::async_trait]
+impl MetricSink for Influx {
+    async fn process(
+        &mut self,
+        origin: Arc<Origin>,
+        mut stream: EagleStream<MetricEvent>,
+    ) -> eyre::Result<()> {
+        let client = http::client();
+        let mut clock = Instant::now();
+        let mut lines = String::new();
+        let mut buffered = 0usize;
+
+        while let Recv::Available(msg) = stream.recv().await {
+            let shutdown = match msg {
+                EagleMsg::Tick => {
+                    if buffered == 0 || clock.elapsed() < self.period {
+                        continue;
+                    }
+
+                    false
+                }
+                EagleMsg::Msg(event) => {
+                    write_line(&mut lines, &event.metric);
+                    buffered += 1;
+
+                    if buffered < self.batch_size {
+                        continue;
+                    }
+
+                    false
+                }
+                EagleMsg::Shutdown => true,
+            };
+
+            if !lines.is_empty() {
+                self.write(&origin, &client, lines.as_str()).await;
+            }
+
+            lines.clear();
+            buffered = 0;
+            clock = Instant::now();
+
+            if shutdown {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::influx::{parse_line, Precision};
+    use chrono::{TimeZone, Utc};
+    use eagle_core::MetricBuilder;
+
+    #[test]
+    fn round_trips_lines() {
+        let names = [
+            ("cpu", "usage", "host", "web-1"),
+            ("disk io", "read bytes", "mount point", "/var/lib data"),
+            ("a,b", "c,d", "e,f", "g,h"),
+            ("a=b", "c=d", "e=f", "g=h"),
+            ("C:\\", "a\\b", "path\\", "C:\\Program Files\\"),
+            ("a\\ b", "c\\,d", "e\\=f", "\\,=\\ \\"),
+        ];
+
+        for (category, name, tag, value) in names {
+            let metric = MetricBuilder::gauge(category, name, 1.5)
+                .add_tag(tag, value)
+                .add_tag("other", "x")
+                .timestamp(Utc.timestamp_millis(1_700_000_000_123))
+                .build();
+
+            let mut line = String::new();
+            write_line(&mut line, &metric);
+
+            let parsed = parse_line(line.as_str(), Precision::Nanoseconds).unwrap();
+
+            assert_eq!(parsed.len(), 1, "{}", line);
+            assert_eq!(parsed[0].category, metric.category, "{}", line);
+            assert_eq!(parsed[0].name, metric.name, "{}", line);
+            assert_eq!(parsed[0].value, metric.value, "{}", line);
+            assert_eq!(parsed[0].tags, metric.tags, "{}", line);
+            assert_eq!(parsed[0].timestamp, metric.timestamp, "{}", line);
+        }
+    }
+
+    #[test]
+    fn skips_unrepresentable_values() {
+        let mut output = String::new();
+
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            write_line(
+                &mut output,
+                &MetricBuilder::gauge("cpu", "usage", value).build(),
+            );
+        }
+
+        write_line(
+            &mut output,
+            &MetricBuilder::gauge("cpu", "usage", 1.0)
+                .add_tag("empty", "")
+                .timestamp(Utc.timestamp_millis(1_000))
+                .build(),
+        );
+
+        assert_eq!(output, "cpu usage=1.0 1000000000\n");
+    }
+}
@@ -0,0 +1,880 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use eagle_core::{
+    EagleMsg, EagleStream, Log, LogEvent, LogSink, Metric, MetricEvent, MetricSink, Origin, Recv,
+};
+use eyre::{bail, eyre};
+use futures::{channel::oneshot::Canceled, stream::FuturesUnordered, FutureExt, StreamExt};
+use rdkafka::{
+    config::ClientConfig,
+    error::{KafkaError, RDKafkaErrorCode},
+    producer::{
+        future_producer::OwnedDeliveryResult, DeliveryFuture, FutureProducer, FutureRecord,
+    },
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    formats::kafka::{self, Encoding},
+    sinks::log_timestamp,
+};
+
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+/// How many replicas must have a record before the broker acknowledges it.
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Acks {
+    /// Records aren't acknowledged at all.
+    None,
+    Leader,
+    /// Every in-sync replica.
+    #[default]
+    All,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TopicPart {
+    Literal(String),
+    /// Replaced by a metric or log field, as in `{category}`.
+    Field(String),
+}
+
+struct Record {
+    topic: String,
+    key: Option<String>,
+    payload: Vec<u8>,
+}
+
+/// Produces metrics and logs to Kafka, one record each.
+///
+/// The topic is given by a template where `{source}` is replaced by the name of the source a
+/// metric or a log comes from. For metrics, `{category}` and `{name}` are replaced by the
+/// metric category and name, and any other `{field}` by a tag. For logs, `{field}` is replaced
+/// by a metadata field, or a log field when the metadata doesn't have it. Missing fields are
+/// replaced by `unknown`.
+///
+/// Records are keyed by the values of the configured key tags, or metadata fields for logs, so
+/// records sharing them land on the same partition. Without key tags, or when a metric or a log
+/// has none of them, records are spread over partitions.
+#[derive(Clone)]
+pub struct Kafka {
+    brokers: String,
+    topic: Vec<TopicPart>,
+    key_tags: Vec<String>,
+    encoding: Encoding,
+    compression: Compression,
+    acks: Acks,
+    timeout: Duration,
+    options: BTreeMap<String, String>,
+    producer: Option<Arc<FutureProducer>>,
+}
+
+impl Kafka {
+    /// `brokers` is a comma separated list of `host:port` addresses.
+    pub fn new(brokers: impl AsRef<str>) -> Self {
+        Self {
+            brokers: brokers.as_ref().to_string(),
+            topic: vec![TopicPart::Literal("eagle".to_string())],
+            key_tags: Vec::new(),
+            encoding: Encoding::default(),
+            compression: Compression::default(),
+            acks: Acks::default(),
+            timeout: Duration::from_secs(30),
+            options: BTreeMap::new(),
+            producer: None,
+        }
+    }
+
+    /// Topic template, like `eagle.{category}`. Defaults to `eagle`.
+    pub fn topic(self, template: impl AsRef<str>) -> eyre::Result<Self> {
+        Ok(Self {
+            topic: parse_topic(template.as_ref())?,
+            ..self
+        })
+    }
+
+    pub fn key_tags(self, key_tags: Vec<String>) -> Self {
+        Self { key_tags, ..self }
+    }
+
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    pub fn compression(self, compression: Compression) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    pub fn acks(self, acks: Acks) -> Self {
+        Self { acks, ..self }
+    }
+
+    /// How long a record can wait to be delivered, retries included, before being dropped.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Extra librdkafka producer settings, like `security.protocol` or `sasl.mechanisms`. They
+    /// take precedence over the ones the sink sets.
+    pub fn options(self, options: BTreeMap<String, String>) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Creates the producer now, so that clones of the sink, like the metric and log sinks of
+    /// the same configuration, share it. Otherwise each sink creates its own when it starts.
+    /// Settings changed afterwards don't apply to the producer.
+    pub fn build_producer(self) -> eyre::Result<Self> {
+        Ok(Self {
+            producer: Some(Arc::new(self.producer()?)),
+            ..self
+        })
+    }
+
+    fn producer(&self) -> eyre::Result<FutureProducer> {
+        let compression = match self.compression {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Snappy => "snappy",
+            Compression::Lz4 => "lz4",
+            Compression::Zstd => "zstd",
+        };
+
+        let acks = match self.acks {
+            Acks::None => "0",
+            Acks::Leader => "1",
+            Acks::All => "all",
+        };
+
+        let mut config = ClientConfig::new();
+
+        config
+            .set("bootstrap.servers", self.brokers.as_str())
+            .set("compression.type", compression)
+            .set("acks", acks)
+            .set("message.timeout.ms", self.timeout.as_millis().to_string());
+
+        for (key, value) in self.options.iter() {
+            config.set(key.as_str(), value.as_str());
+        }
+
+        config
+            .create()
+            .map_err(|e| eyre!("Error when creating the Kafka producer: {}", e))
+    }
+
+    fn metric_record(&self, origin: &Origin, metric: &Metric) -> Record {
+        let field = |name: &str| match name {
+            "source" => Some(origin.name.as_str()),
+            "category" => Some(metric.category.as_str()),
+            "name" => Some(metric.name.as_str()),
+            tag => metric.tags.get(tag).map(String::as_str),
+        };
+
+        Record {
+            topic: self.topic_name(field),
+            key: self.key(|tag| metric.tags.get(tag).cloned()),
+            payload: kafka::encode_metric(self.encoding, origin.name.as_str(), metric),
+        }
+    }
+
+    fn log_record(&self, origin: &Origin, log: &Log) -> Record {
+        let field = |name: &str| {
+            log.metadata
+                .get(name)
+                .or_else(|| log.inner.get(name))
+                .and_then(field_value)
+        };
+
+        let topic = self.topic_name(|name| match name {
+            "source" => Some(origin.name.clone()),
+            name => field(name),
+        });
+
+        Record {
+            topic,
+            key: self.key(field),
+            payload: kafka::encode_log(
+                self.encoding,
+                origin.name.as_str(),
+                log_timestamp(log),
+                log,
+            ),
+        }
+    }
+
+    fn topic_name<F, S>(&self, field: F) -> String
+    where
+        F: Fn(&str) -> Option<S>,
+        S: AsRef<str>,
+    {
+        let mut name = String::new();
+
+        for part in self.topic.iter() {
+            match part {
+                TopicPart::Literal(literal) => name.push_str(literal),
+                TopicPart::Field(key) => match field(key) {
+                    Some(value) => name.push_str(value.as_ref()),
+                    None => name.push_str("unknown"),
+                },
+            }
+        }
+
+        sanitize_topic(name.as_str())
+    }
+
+    /// Joins the key tag values with commas, missing ones being left empty.
+    fn key<F>(&self, field: F) -> Option<String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let values = self
+            .key_tags
+            .iter()
+            .map(|tag| field(tag.as_str()))
+            .collect::<Vec<_>>();
+
+        if values.iter().all(Option::is_none) {
+            return None;
+        }
+
+        Some(
+            values
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect::<Vec<_>>()
+                .join(","),
+        )
+    }
+
+    /// Produces the records the stream turns into, until the stream is shut down. Deliveries are
+    /// checked on every tick, and all awaited before returning.
+    async fn run<A, F>(
+        &self,
+        origin: &Origin,
+        mut stream: EagleStream<A>,
+        record: F,
+    ) -> eyre::Result<()>
+    where
+        A: Clone + Send,
+        F: Fn(A) -> Record,
+    {
+        let producer = match self.producer.clone() {
+            Some(producer) => producer,
+            None => Arc::new(self.producer()?),
+        };
+
+        let mut deliveries = Deliveries::default();
+
+        while let Recv::Available(msg) = stream.recv().await {
+            let record = match msg {
+                EagleMsg::Tick => {
+                    deliveries.collect();
+                    deliveries.report(origin);
+                    continue;
+                }
+                EagleMsg::Msg(event) => record(event),
+                EagleMsg::Shutdown => break,
+            };
+
+            loop {
+                let mut future_record =
+                    FutureRecord::<str, [u8]>::to(record.topic.as_str()).payload(&record.payload);
+
+                if let Some(key) = record.key.as_deref() {
+                    future_record = future_record.key(key);
+                }
+
+                match producer.send_result(future_record) {
+                    Ok(delivery) => {
+                        deliveries.push(delivery);
+                        break;
+                    }
+
+                    // The producer queue is full, so we wait for some records to be delivered.
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                        if !deliveries.wait().await {
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                        }
+                    }
+
+                    Err((e, _)) => {
+                        tracing::error!(
+                            target = origin.instance_id(),
+                            "Error when producing to topic '{}': {}",
+                            record.topic,
+                            e
+                        );
+
+                        metrics::counter!("kafka.sink.failures", 1);
+                        break;
+                    }
+                }
+            }
+        }
+
+        while deliveries.wait().await {}
+
+        deliveries.report(origin);
+
+        Ok(())
+    }
+}
+
+/// Records waiting to be acknowledged. Failed deliveries are reported together, so an
+/// unreachable cluster doesn't produce an error per record.
+#[derive(Default)]
+struct Deliveries {
+    pending: FuturesUnordered<DeliveryFuture>,
+    failures: usize,
+    last_error: Option<String>,
+}
+
+impl Deliveries {
+    fn push(&mut self, delivery: DeliveryFuture) {
+        self.pending.push(delivery);
+    }
+
+    /// Takes the outcome of the deliveries already done.
+    fn collect(&mut self) {
+        while let Some(Some(outcome)) = self.pending.next().now_or_never() {
+            self.outcome(outcome);
+        }
+    }
+
+    /// Waits for a delivery to be done. Returns `false` when none are pending.
+    async fn wait(&mut self) -> bool {
+        match self.pending.next().await {
+            Some(outcome) => {
+                self.outcome(outcome);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn outcome(&mut self, outcome: Result<OwnedDeliveryResult, Canceled>) {
+        let error = match outcome {
+            Ok(Ok(_)) => {
+                metrics::counter!("kafka.sink.successes", 1);
+                return;
+            }
+            Ok(Err((e, _))) => e.to_string(),
+            Err(_) => "the producer went away".to_string(),
+        };
+
+        metrics::counter!("kafka.sink.failures", 1);
+        self.failures += 1;
+        self.last_error = Some(error);
+    }
+
+    fn report(&mut self, origin: &Origin) {
+        if let Some(error) = self.last_error.take() {
+            tracing::error!(
+                target = origin.instance_id(),
+                "{} records not delivered to Kafka, last error: {}",
+                self.failures,
+                error
+            );
+
+            self.failures = 0;
+        }
+    }
+}
+
+fn field_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_topic(template: &str) -> eyre::Result<Vec<TopicPart>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+
+    while !rest.is_empty() {
+        let (literal, field) = match rest.split_once('{') {
+            Some((literal, remaining)) => {
+                let (field, remaining) = remaining
+                    .split_once('}')
+                    .ok_or_else(|| eyre!("Unclosed '{{' in topic '{}'", template))?;
+
+                rest = remaining;
+                (literal, Some(field))
+            }
+            None => (std::mem::take(&mut rest), None),
+        };
+
+        if !literal.is_empty() {
+            parts.push(TopicPart::Literal(literal.to_string()));
+        }
+
+        if let Some(field) = field {
+            if field.is_empty() {
+                bail!("Empty field name in topic '{}'", template);
+            }
+
+            parts.push(TopicPart::Field(field.to_string()));
+        }
+    }
+
+    if parts.is_empty() {
+        bail!("Topic can't be empty");
+    }
+
+    Ok(parts)
+}
+
+/// Topic names can only contain ASCII alphanumerics, `.`, `_` and `-`, and are at most 249
+/// characters long.
+fn sanitize_topic(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(249)
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl MetricSink for Kafka {
+    async fn process(
+        &mut self,
+        origin: Arc<Origin>,
+        stream: EagleStream<MetricEvent>,
+    ) -> eyre::Result<()> {
+        self.run(origin.as_ref(), stream, |event| {
+            self.metric_record(event.origin.as_ref(), event.metric.as_ref())
+        })
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl LogSink for Kafka {
+    async fn process(
+        &mut self,
+        origin: Arc<Origin>,
+        stream: EagleStream<LogEvent>,
+    ) -> eyre::Result<()> {
+        self.run(origin.as_ref(), stream, |event| {
+            self.log_record(event.origin.as_ref(), event.log.as_ref())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::sources::{
+        kafka::{OffsetReset, Payload},
+        KafkaConsumer,
+    };
+    use chrono::{DateTime, TimeZone, Utc};
+    use eagle_core::{
+        eagle_channel, poll::Schedule, EagleClient, EagleEndpoint, EagleEvent, Event,
+        MetricBuilder, MetricType, Source,
+    };
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn parses_topics() {
+        let literal = |literal: &str| TopicPart::Literal(literal.to_string());
+        let field = |name: &str| TopicPart::Field(name.to_string());
+
+        let cases = vec![
+            ("eagle", vec![literal("eagle")]),
+            (
+                "eagle.{category}",
+                vec![literal("eagle."), field("category")],
+            ),
+            (
+                "{source}-{host.name}.logs",
+                vec![
+                    field("source"),
+                    literal("-"),
+                    field("host.name"),
+                    literal(".logs"),
+                ],
+            ),
+        ];
+
+        for (template, expected) in cases {
+            assert_eq!(
+                parse_topic(template).unwrap(),
+                expected,
+                "parsing {}",
+                template
+            );
+        }
+
+        for template in ["", "eagle.{category", "eagle.{}"] {
+            assert!(parse_topic(template).is_err(), "rejecting {}", template);
+        }
+    }
+
+    #[test]
+    fn sanitizes_topics() {
+        let long = "a".repeat(300);
+        let cases = vec![
+            ("eagle.cpu_usage-1", "eagle.cpu_usage-1".to_string()),
+            ("eagle/web app:80", "eagle_web_app_80".to_string()),
+            ("été", "_t_".to_string()),
+            (long.as_str(), "a".repeat(249)),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(sanitize_topic(name), expected, "sanitizing {}", name);
+        }
+    }
+
+    #[test]
+    fn keys_records() {
+        let fields = HashMap::from([("host", "web-1"), ("region", "eu")]);
+        let field = |name: &str| fields.get(name).map(|value| value.to_string());
+
+        let cases = vec![
+            (vec![], None),
+            (vec!["host"], Some("web-1")),
+            (vec!["host", "region"], Some("web-1,eu")),
+            (vec!["missing", "region"], Some(",eu")),
+            (vec!["missing"], None),
+        ];
+
+        for (key_tags, expected) in cases {
+            let sink = Kafka::new("localhost:9092")
+                .key_tags(key_tags.iter().map(|tag| tag.to_string()).collect());
+
+            assert_eq!(
+                sink.key(field).as_deref(),
+                expected,
+                "keying with {:?}",
+                key_tags
+            );
+        }
+    }
+
+    #[test]
+    fn names_topics() {
+        let origin = Origin::new("app");
+        let metric = MetricBuilder::gauge("cpu", "usage", 1f64)
+            .add_tag("host", "Web 1")
+            .build();
+
+        let log = Log {
+            inner: Arc::new(json!({ "level": "info", "tag": "ignored" })),
+            metadata: json!({ "tag": "nginx.access", "code": 200 }),
+        };
+
+        let cases = vec![
+            ("eagle", "eagle", "eagle"),
+            ("{source}.{category}", "app.cpu", "app.unknown"),
+            (
+                "eagle.{name}.{host}",
+                "eagle.usage.Web_1",
+                "eagle.unknown.unknown",
+            ),
+            (
+                "logs.{tag}.{level}.{code}",
+                "logs.unknown.unknown.unknown",
+                "logs.nginx.access.info.200",
+            ),
+        ];
+
+        for (template, metric_topic, log_topic) in cases {
+            let sink = Kafka::new("localhost:9092").topic(template).unwrap();
+
+            assert_eq!(
+                sink.metric_record(&origin, &metric).topic,
+                metric_topic,
+                "naming metric topics with {}",
+                template
+            );
+            assert_eq!(
+                sink.log_record(&origin, &log).topic,
+                log_topic,
+                "naming log topics with {}",
+                template
+            );
+        }
+    }
+
+    /// Brokers of the cluster the integration tests run against, which must create topics
+    /// automatically, as in `KAFKA_BROKERS=localhost:9092 cargo test -- --ignored`.
+    fn brokers() -> Option<String> {
+        std::env::var("KAFKA_BROKERS").ok()
+    }
+
+    fn topic() -> String {
+        format!("eagle-test-{}", uuid::Uuid::new_v4())
+    }
+
+    async fn produce_metrics(sink: Kafka, metrics: Vec<Metric>) {
+        let mut sink = sink.build_producer().unwrap();
+        let (sender, stream) = eagle_channel(16);
+        let origin = Arc::new(Origin::new("app"));
+        let task = tokio::spawn(async move {
+            MetricSink::process(&mut sink, Arc::new(Origin::new("kafka")), stream).await
+        });
+
+        for metric in metrics {
+            sender
+                .send_msg(MetricEvent {
+                    origin: origin.clone(),
+                    metric: Arc::new(metric),
+                })
+                .await;
+        }
+
+        sender.shutdown().await;
+        task.await.unwrap().unwrap();
+    }
+
+    async fn produce_logs(sink: Kafka, logs: Vec<(Value, Value)>) {
+        let mut sink = sink.build_producer().unwrap();
+        let (sender, stream) = eagle_channel(16);
+        let origin = Arc::new(Origin::new("app"));
+        let task = tokio::spawn(async move {
+            LogSink::process(&mut sink, Arc::new(Origin::new("kafka")), stream).await
+        });
+
+        for (inner, metadata) in logs {
+            let log = Log {
+                inner: Arc::new(inner),
+                metadata,
+            };
+
+            sender
+                .send_msg(LogEvent {
+                    origin: origin.clone(),
+                    log: Arc::new(log),
+                })
+                .await;
+        }
+
+        sender.shutdown().await;
+        task.await.unwrap().unwrap();
+    }
+
+    /// Consumes until `count` events were received, then makes sure no more arrive and stops
+    /// the consumer, once its offsets had time to be committed.
+    async fn consume(consumer: KafkaConsumer, count: usize) -> Vec<Event> {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<EagleEvent>();
+        let client = EagleClient {
+            origin: Arc::new(Origin::new("kafka")),
+            endpoint: EagleEndpoint::new(sender),
+            schedule: Schedule::default(),
+        };
+
+        let mut consumer = consumer
+            .offset_reset(OffsetReset::Earliest)
+            .options(BTreeMap::from([(
+                "auto.commit.interval.ms".to_string(),
+                "100".to_string(),
+            )]));
+
+        let task = tokio::spawn(async move { consumer.produce(client).await });
+        let mut events = Vec::new();
+
+        while events.len() < count {
+            let event = tokio::time::timeout(Duration::from_secs(60), receiver.recv())
+                .await
+                .expect("Timed out waiting for records")
+                .unwrap();
+
+            events.push(event.event);
+        }
+
+        let extra = tokio::time::timeout(Duration::from_secs(2), receiver.recv()).await;
+
+        assert!(extra.is_err(), "Consumed more than {} records", count);
+
+        task.abort();
+        let _ = task.await;
+
+        events
+    }
+
+    /// Category, name, value, type, tags and timestamp.
+    type Fields = (
+        String,
+        String,
+        f64,
+        MetricType,
+        BTreeMap<String, String>,
+        DateTime<Utc>,
+    );
+
+    fn fields(metric: &Metric) -> Fields {
+        (
+            metric.category.clone(),
+            metric.name.clone(),
+            metric.value,
+            metric.r#type,
+            metric.tags.clone(),
+            metric.timestamp,
+        )
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn produces_and_consumes_metrics() {
+        let brokers = match brokers() {
+            Some(brokers) => brokers,
+            None => return,
+        };
+
+        let timestamp = Utc.timestamp_millis(1_700_000_000_123);
+        let metrics = || {
+            vec![
+                MetricBuilder::gauge("cpu", "usage", 12.5)
+                    .add_tag("host", "web-1")
+                    .timestamp(timestamp)
+                    .build(),
+                MetricBuilder::counter("http", "requests", 42f64)
+                    .add_tag("host", "web-2")
+                    .timestamp(timestamp)
+                    .build(),
+                MetricBuilder::delta("http", "errors", 3f64)
+                    .timestamp(timestamp)
+                    .build(),
+            ]
+        };
+
+        for encoding in [Encoding::Json, Encoding::Protobuf] {
+            let topic = topic();
+            let sink = Kafka::new(brokers.as_str())
+                .topic(topic.as_str())
+                .unwrap()
+                .encoding(encoding)
+                .key_tags(vec!["host".to_string()]);
+
+            produce_metrics(sink, metrics()).await;
+
+            let consumer =
+                KafkaConsumer::new(brokers.as_str(), topic.as_str(), vec![topic.clone()])
+                    .payload(Payload::Metrics)
+                    .encoding(encoding);
+
+            let mut consumed = consume(consumer, 3)
+                .await
+                .iter()
+                .map(|event| match event {
+                    Event::Metric(metric) => fields(metric),
+                    other => panic!("Unexpected event {:?}", other),
+                })
+                .collect::<Vec<_>>();
+
+            let mut expected = metrics().iter().map(fields).collect::<Vec<_>>();
+
+            // Records with different keys may land on different partitions.
+            consumed.sort_by(|a, b| a.1.cmp(&b.1));
+            expected.sort_by(|a, b| a.1.cmp(&b.1));
+
+            assert_eq!(consumed, expected);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn produces_and_consumes_logs() {
+        let brokers = match brokers() {
+            Some(brokers) => brokers,
+            None => return,
+        };
+
+        let logs = vec![
+            (
+                json!({ "message": "first", "count": 1 }),
+                json!({ "tag": "app", "timestamp": "2023-11-14T22:13:20+00:00" }),
+            ),
+            (
+                json!("second"),
+                json!({ "tag": "app", "timestamp": "2023-11-14T22:13:21+00:00" }),
+            ),
+        ];
+
+        for encoding in [Encoding::Json, Encoding::Protobuf] {
+            let topic = topic();
+            let sink = Kafka::new(brokers.as_str())
+                .topic(topic.as_str())
+                .unwrap()
+                .encoding(encoding)
+                .key_tags(vec!["tag".to_string()]);
+
+            produce_logs(sink, logs.clone()).await;
+
+            let consumer =
+                KafkaConsumer::new(brokers.as_str(), topic.as_str(), vec![topic.clone()])
+                    .encoding(encoding);
+
+            let consumed = consume(consumer, 2).await;
+
+            for (event, (inner, metadata)) in consumed.iter().zip(logs.iter()) {
+                let log = match event {
+                    Event::Log(log) => log,
+                    other => panic!("Unexpected event {:?}", other),
+                };
+
+                assert_eq!(log.inner.as_ref(), inner);
+                assert_eq!(log.metadata["tag"], metadata["tag"]);
+                assert_eq!(log.metadata["timestamp"], metadata["timestamp"]);
+                assert_eq!(log.metadata["topic"], json!(topic));
+                assert_eq!(log.metadata["key"], json!("app"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn resumes_from_committed_offsets() {
+        let brokers = match brokers() {
+            Some(brokers) => brokers,
+            None => return,
+        };
+
+        let topic = topic();
+        let sink = Kafka::new(brokers.as_str()).topic(topic.as_str()).unwrap();
+        let consumer = || KafkaConsumer::new(brokers.as_str(), topic.as_str(), vec![topic.clone()]);
+        let lines = |events: Vec<Event>| {
+            events
+                .into_iter()
+                .map(|event| match event {
+                    Event::Log(log) => log.inner.as_ref().clone(),
+                    other => panic!("Unexpected event {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        produce_logs(sink.clone(), vec![(json!("first"), json!({}))]).await;
+
+        assert_eq!(lines(consume(consumer(), 1).await), vec![json!("first")]);
+
+        produce_logs(sink, vec![(json!("second"), json!({}))]).await;
+
+        assert_eq!(lines(consume(consumer(), 1).await), vec![json!("second")]);
+    }
+}
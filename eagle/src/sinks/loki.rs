@@ -0,0 +1,771 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use eagle_core::{EagleMsg, EagleStream, LogEvent, LogSink, Origin, Recv};
+use hyper::{header::CONTENT_TYPE, Body, Method, Request};
+use prost::Message;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    http::{self, HttpClient},
+    sinks::log_timestamp,
+};
+
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// Snappy compressed protobuf, what Promtail sends.
+    #[default]
+    Protobuf,
+    Json,
+}
+
+/// What to do with an entry older than the last one sent for its stream, which Loki rejects
+/// unless it accepts unordered writes.
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutOfOrder {
+    /// Sends it as is, for Loki 2.4 and later which accept unordered writes by default.
+    Accept,
+    Drop,
+    /// Sends it with the timestamp of the last entry sent for its stream.
+    #[default]
+    RewriteTimestamp,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PushRequest {
+    #[prost(message, repeated, tag = "1")]
+    streams: Vec<StreamAdapter>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StreamAdapter {
+    #[prost(string, tag = "1")]
+    labels: String,
+    #[prost(message, repeated, tag = "2")]
+    entries: Vec<EntryAdapter>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EntryAdapter {
+    #[prost(message, optional, tag = "1")]
+    timestamp: Option<prost_types::Timestamp>,
+    #[prost(string, tag = "2")]
+    line: String,
+}
+
+type Labels = BTreeMap<String, String>;
+
+struct Entry {
+    timestamp: DateTime<Utc>,
+    line: String,
+}
+
+/// Pushes logs to Loki. Every stream has the `source` label, set to the name of the source the
+/// logs come from, along with the configured static labels and the configured metadata fields.
+///
+/// Entries are timestamped with their `timestamp` metadata field when it's an RFC 3339 date,
+/// and with the time the sink received them otherwise. Logs that are JSON strings are sent as
+/// is, other values as JSON.
+pub struct Loki {
+    url: String,
+    tenant_id: Option<String>,
+    encoding: Encoding,
+    labels: Labels,
+    label_fields: Vec<String>,
+    max_label_values: usize,
+    out_of_order: OutOfOrder,
+    batch_size: usize,
+    period: Duration,
+    retries: usize,
+}
+
+impl Loki {
+    /// `url` is the push endpoint, like `http://localhost:3100/loki/api/v1/push`.
+    pub fn new(url: impl AsRef<str>) -> Self {
+        Self {
+            url: url.as_ref().to_string(),
+            tenant_id: None,
+            encoding: Encoding::default(),
+            labels: Labels::new(),
+            label_fields: Vec::new(),
+            max_label_values: 100,
+            out_of_order: OutOfOrder::default(),
+            batch_size: 1_000,
+            period: Duration::from_secs(1),
+            retries: 3,
+        }
+    }
+
+    /// Sent as `X-Scope-OrgID`, for multi-tenant setups.
+    pub fn tenant_id(self, tenant_id: impl AsRef<str>) -> Self {
+        Self {
+            tenant_id: Some(tenant_id.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Labels added to every stream.
+    pub fn labels(self, labels: Labels) -> Self {
+        Self { labels, ..self }
+    }
+
+    /// Metadata fields turned into labels, when they are strings, numbers or booleans. Label
+    /// names are the field names with invalid characters replaced by `_`.
+    pub fn label_fields(self, label_fields: Vec<String>) -> Self {
+        Self {
+            label_fields,
+            ..self
+        }
+    }
+
+    /// Once a label taken from metadata has had that many values, logs with a new value are
+    /// sent without it, so a field with unbounded values can't create countless streams.
+    pub fn max_label_values(self, max_label_values: usize) -> Self {
+        Self {
+            max_label_values,
+            ..self
+        }
+    }
+
+    pub fn out_of_order(self, out_of_order: OutOfOrder) -> Self {
+        Self {
+            out_of_order,
+            ..self
+        }
+    }
+
+    /// A batch is pushed as soon as it holds that many entries.
+    pub fn batch_size(self, batch_size: usize) -> Self {
+        Self { batch_size, ..self }
+    }
+
+    /// Pending entries are pushed at least that often.
+    pub fn period(self, period: Duration) -> Self {
+        Self { period, ..self }
+    }
+
+    pub fn retries(self, retries: usize) -> Self {
+        Self { retries, ..self }
+    }
+
+    fn request(&self, body: &[u8]) -> eyre::Result<Request<Body>> {
+        let content_type = match self.encoding {
+            Encoding::Protobuf => "application/x-protobuf",
+            Encoding::Json => "application/json",
+        };
+
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.as_str())
+            .header(CONTENT_TYPE, content_type);
+
+        if let Some(tenant_id) = self.tenant_id.as_ref() {
+            request = request.header("X-Scope-OrgID", tenant_id.as_str());
+        }
+
+        Ok(request.body(Body::from(body.to_vec()))?)
+    }
+
+    async fn push(&self, origin: &Origin, client: &HttpClient, streams: &[(Labels, Vec<Entry>)]) {
+        let body = match self.encoding {
+            Encoding::Protobuf => encode_protobuf(streams),
+            Encoding::Json => Ok(encode_json(streams)),
+        };
+
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!(target = origin.instance_id(), "{:?}", e);
+                return;
+            }
+        };
+
+        match http::send_with_retries(client, || self.request(&body), self.retries).await {
+            Ok((status, _)) if status.is_success() => {
+                metrics::counter!("loki.sink.successes", 1);
+            }
+
+            Ok((status, body)) => {
+                tracing::error!(
+                    target = origin.instance_id(),
+                    "Loki rejected the push with status {}: {}",
+                    status,
+                    String::from_utf8_lossy(&body)
+                );
+
+                metrics::counter!("loki.sink.failures", 1);
+            }
+
+            Err(e) => {
+                tracing::error!(
+                    target = origin.instance_id(),
+                    "Error when pushing to Loki: {:?}",
+                    e
+                );
+
+                metrics::counter!("loki.sink.failures", 1);
+            }
+        }
+    }
+}
+
+/// Remembers the values seen for each label taken from metadata.
+struct CardinalityGuard {
+    max_values: usize,
+    values: HashMap<String, HashSet<String>>,
+    /// Labels that reached the limit, so it's only reported once.
+    full: HashSet<String>,
+}
+
+impl CardinalityGuard {
+    fn admit(&mut self, origin: &Origin, label: &str, value: &str) -> bool {
+        let values = self.values.entry(label.to_string()).or_default();
+
+        if values.contains(value) {
+            return true;
+        }
+
+        if values.len() < self.max_values {
+            values.insert(value.to_string());
+            return true;
+        }
+
+        if self.full.insert(label.to_string()) {
+            tracing::warn!(
+                target = origin.instance_id(),
+                "Label '{}' reached {} values, logs with new values are sent without it",
+                label,
+                self.max_values
+            );
+        }
+
+        metrics::counter!("loki.sink.labels_dropped", 1);
+
+        false
+    }
+}
+
+fn sanitize_label_name(name: &str) -> String {
+    let mut sanitized = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+
+    sanitized
+}
+
+/// Formats labels the way Loki and Prometheus expect them, as in `{job="eagle"}`.
+fn format_labels(labels: &Labels) -> String {
+    let labels = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect::<Vec<_>>();
+
+    format!("{{{}}}", labels.join(", "))
+}
+
+fn encode_protobuf(streams: &[(Labels, Vec<Entry>)]) -> eyre::Result<Vec<u8>> {
+    let request = PushRequest {
+        streams: streams
+            .iter()
+            .map(|(labels, entries)| StreamAdapter {
+                labels: format_labels(labels),
+                entries: entries
+                    .iter()
+                    .map(|entry| EntryAdapter {
+                        timestamp: Some(prost_types::Timestamp {
+                            seconds: entry.timestamp.timestamp(),
+                            nanos: entry.timestamp.timestamp_subsec_nanos() as i32,
+                        }),
+                        line: entry.line.clone(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    Ok(snap::raw::Encoder::new().compress_vec(request.encode_to_vec().as_slice())?)
+}
+
+fn encode_json(streams: &[(Labels, Vec<Entry>)]) -> Vec<u8> {
+    let streams = streams
+        .iter()
+        .map(|(labels, entries)| {
+            let values = entries
+                .iter()
+                .map(|entry| json!([entry.timestamp.timestamp_nanos().to_string(), entry.line]))
+                .collect::<Vec<_>>();
+
+            json!({ "stream": labels, "values": values })
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "streams": streams }).to_string().into_bytes()
+}
+
+#[async_trait::async_trait]
+impl LogSink for Loki {
+    async fn process(
+        &mut self,
+        origin: Arc<Origin>,
+        mut stream: EagleStream<LogEvent>,
+    ) -> eyre::Result<()> {
+        let client = http::client();
+        let mut guard = CardinalityGuard {
+            max_values: self.max_label_values,
+            values: HashMap::new(),
+            full: HashSet::new(),
+        };
+
+        let mut clock = Instant::now();
+        let mut pending = BTreeMap::<Labels, Vec<Entry>>::new();
+        let mut buffered = 0usize;
+        let mut last_timestamps = HashMap::<Labels, DateTime<Utc>>::new();
+
+        while let Recv::Available(msg) = stream.recv().await {
+            let shutdown = match msg {
+                EagleMsg::Tick => {
+                    if buffered == 0 || clock.elapsed() < self.period {
+                        continue;
+                    }
+
+                    false
+                }
+                EagleMsg::Msg(event) => {
+                    let mut labels = self.labels.clone();
+
+                    labels
+                        .entry("source".to_string())
+                        .or_insert_with(|| event.origin.name.clone());
+
+                    for field in self.label_fields.iter() {
+                        let value = match event.log.metadata.get(field.as_str()) {
+                            Some(Value::String(value)) => value.clone(),
+                            Some(value @ Value::Number(_)) | Some(value @ Value::Bool(_)) => {
+                                value.to_string()
+                            }
+                            _ => continue,
+                        };
+
+                        let name = sanitize_label_name(field);
+
+                        if !value.is_empty() && guard.admit(origin.as_ref(), &name, &value) {
+                            labels.insert(name, value);
+                        }
+                    }
+
+                    let timestamp = log_timestamp(event.log.as_ref());
+
+                    let line = match event.log.inner.as_ref() {
+                        Value::String(line) => line.clone(),
+                        value => value.to_string(),
+                    };
+
+                    pending
+                        .entry(labels)
+                        .or_default()
+                        .push(Entry { timestamp, line });
+
+                    buffered += 1;
+
+                    if buffered < self.batch_size {
+                        continue;
+                    }
+
+                    false
+                }
+                EagleMsg::Shutdown => true,
+            };
+
+            let mut streams = Vec::with_capacity(pending.len());
+
+            for (labels, mut entries) in std::mem::take(&mut pending) {
+                entries.sort_by_key(|entry| entry.timestamp);
+
+                let last = last_timestamps.get(&labels).copied();
+
+                if let Some(last) = last {
+                    match self.out_of_order {
+                        OutOfOrder::Accept => {}
+                        OutOfOrder::Drop => {
+                            let count = entries.len();
+
+                            entries.retain(|entry| entry.timestamp >= last);
+                            metrics::counter!(
+                                "loki.sink.out_of_order_dropped",
+                                (count - entries.len()) as u64
+                            );
+                        }
+                        OutOfOrder::RewriteTimestamp => {
+                            for entry in entries.iter_mut() {
+                                entry.timestamp = entry.timestamp.max(last);
+                            }
+                        }
+                    }
+                }
+
+                if let Some(newest) = entries.last() {
+                    let newest = last.map_or(newest.timestamp, |last| last.max(newest.timestamp));
+
+                    last_timestamps.insert(labels.clone(), newest);
+                    streams.push((labels, entries));
+                }
+            }
+
+            if !streams.is_empty() {
+                self.push(&origin, &client, streams.as_slice()).await;
+            }
+
+            buffered = 0;
+            clock = Instant::now();
+
+            if shutdown {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use eagle_core::{eagle_channel, Log};
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server,
+    };
+    use std::convert::Infallible;
+    use tokio::sync::mpsc;
+
+    struct Push {
+        content_type: String,
+        tenant_id: Option<String>,
+        body: Vec<u8>,
+    }
+
+    type Streams = Vec<(String, Vec<(i64, String)>)>;
+
+    /// Runs the sink against a stand-in Loki, flushing after each group of `(log, metadata)`
+    /// pairs, and returns the pushes it received.
+    async fn push(
+        configure: impl FnOnce(Loki) -> Loki,
+        flushes: Vec<Vec<(Value, Value)>>,
+    ) -> Vec<Push> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let make_service = make_service_fn(move |_| {
+            let sender = sender.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let sender = sender.clone();
+
+                    async move {
+                        let header = |name: &str| {
+                            request
+                                .headers()
+                                .get(name)
+                                .and_then(|value| value.to_str().ok())
+                                .map(str::to_string)
+                        };
+
+                        let content_type = header("content-type").unwrap_or_default();
+                        let tenant_id = header("x-scope-orgid");
+                        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+
+                        let _ = sender.send(Push {
+                            content_type,
+                            tenant_id,
+                            body: body.to_vec(),
+                        });
+
+                        Ok::<_, Infallible>(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/loki/api/v1/push", server.local_addr());
+
+        tokio::spawn(server);
+
+        let mut loki = configure(Loki::new(url).period(Duration::ZERO));
+        let (sink, stream) = eagle_channel(16);
+        let task =
+            tokio::spawn(async move { loki.process(Arc::new(Origin::new("loki")), stream).await });
+        let origin = Arc::new(Origin::new("app"));
+
+        for logs in flushes {
+            for (log, metadata) in logs {
+                let log = Log {
+                    inner: Arc::new(log),
+                    metadata,
+                };
+
+                sink.send_msg(LogEvent {
+                    origin: origin.clone(),
+                    log: Arc::new(log),
+                })
+                .await;
+            }
+
+            sink.send_tick().await;
+        }
+
+        sink.shutdown().await;
+        task.await.unwrap().unwrap();
+
+        let mut pushes = Vec::new();
+
+        while let Ok(push) = receiver.try_recv() {
+            pushes.push(push);
+        }
+
+        pushes
+    }
+
+    /// Decodes either encoding into the same shape, with formatted labels and timestamps in
+    /// nanoseconds.
+    fn decode(push: &Push) -> Streams {
+        match push.content_type.as_str() {
+            "application/x-protobuf" => {
+                let body = snap::raw::Decoder::new()
+                    .decompress_vec(push.body.as_slice())
+                    .unwrap();
+
+                PushRequest::decode(body.as_slice())
+                    .unwrap()
+                    .streams
+                    .into_iter()
+                    .map(|stream| {
+                        let entries = stream
+                            .entries
+                            .into_iter()
+                            .map(|entry| {
+                                let timestamp = entry.timestamp.unwrap();
+
+                                (
+                                    timestamp.seconds * 1_000_000_000 + timestamp.nanos as i64,
+                                    entry.line,
+                                )
+                            })
+                            .collect();
+
+                        (stream.labels, entries)
+                    })
+                    .collect()
+            }
+
+            "application/json" => {
+                let body: Value = serde_json::from_slice(push.body.as_slice()).unwrap();
+
+                body["streams"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|stream| {
+                        let labels: Labels =
+                            serde_json::from_value(stream["stream"].clone()).unwrap();
+                        let entries = stream["values"]
+                            .as_array()
+                            .unwrap()
+                            .iter()
+                            .map(|value| {
+                                (
+                                    value[0].as_str().unwrap().parse().unwrap(),
+                                    value[1].as_str().unwrap().to_string(),
+                                )
+                            })
+                            .collect();
+
+                        (format_labels(&labels), entries)
+                    })
+                    .collect()
+            }
+
+            other => panic!("Unexpected content type '{}'", other),
+        }
+    }
+
+    /// Nanoseconds `seconds` after 2024-01-01.
+    fn nanos(seconds: i64) -> i64 {
+        (1_704_067_200 + seconds) * 1_000_000_000
+    }
+
+    fn timestamp(seconds: i64) -> String {
+        Utc.timestamp_nanos(nanos(seconds)).to_rfc3339()
+    }
+
+    #[test]
+    fn sanitizes_label_names() {
+        let cases = vec![
+            ("level", "level"),
+            ("host.name", "host_name"),
+            ("k8s-pod/name", "k8s_pod_name"),
+            ("1st", "_1st"),
+            ("été", "_t_"),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(sanitize_label_name(name), expected, "sanitizing {}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn pushes_labeled_streams() {
+        let logs = vec![
+            (
+                json!({ "msg": "first" }),
+                json!({ "timestamp": timestamp(10), "host.name": "web-1", "level": "info" }),
+            ),
+            (
+                json!("second"),
+                json!({ "timestamp": timestamp(11), "host.name": "web \"2\"", "level": 3 }),
+            ),
+            // Both fields reached their 2 values, so the entry only gets the other labels.
+            (
+                json!("third"),
+                json!({ "timestamp": timestamp(12), "host.name": "web-3", "level": true }),
+            ),
+            (
+                json!("fourth"),
+                json!({ "timestamp": timestamp(9), "level": { "nested": 1 } }),
+            ),
+        ];
+
+        let expected = vec![
+            (
+                r#"{host_name="web \"2\"", job="eagle", level="3", source="app"}"#.to_string(),
+                vec![(nanos(11), "second".to_string())],
+            ),
+            (
+                r#"{host_name="web-1", job="eagle", level="info", source="app"}"#.to_string(),
+                vec![(nanos(10), r#"{"msg":"first"}"#.to_string())],
+            ),
+            (
+                r#"{job="eagle", source="app"}"#.to_string(),
+                vec![
+                    (nanos(9), "fourth".to_string()),
+                    (nanos(12), "third".to_string()),
+                ],
+            ),
+        ];
+
+        for (encoding, content_type) in [
+            (Encoding::Protobuf, "application/x-protobuf"),
+            (Encoding::Json, "application/json"),
+        ] {
+            let pushes = push(
+                |loki| {
+                    loki.encoding(encoding)
+                        .tenant_id("team")
+                        .labels(Labels::from([("job".to_string(), "eagle".to_string())]))
+                        .label_fields(vec!["host.name".to_string(), "level".to_string()])
+                        .max_label_values(2)
+                },
+                vec![logs.clone()],
+            )
+            .await;
+
+            assert_eq!(pushes.len(), 1, "pushing {}", content_type);
+            assert_eq!(pushes[0].content_type, content_type);
+            assert_eq!(pushes[0].tenant_id.as_deref(), Some("team"));
+            assert_eq!(decode(&pushes[0]), expected, "decoding {}", content_type);
+        }
+    }
+
+    #[tokio::test]
+    async fn pushes_json_bodies() {
+        let pushes = push(
+            |loki| loki.encoding(Encoding::Json),
+            vec![vec![(json!("line"), json!({ "timestamp": timestamp(1) }))]],
+        )
+        .await;
+
+        let body: Value = serde_json::from_slice(pushes[0].body.as_slice()).unwrap();
+
+        assert_eq!(pushes[0].tenant_id, None);
+        assert_eq!(
+            body,
+            json!({
+                "streams": [{
+                    "stream": { "source": "app" },
+                    "values": [[nanos(1).to_string(), "line"]],
+                }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn handles_out_of_order_entries() {
+        let flushes = vec![
+            vec![(json!("first"), json!({ "timestamp": timestamp(10) }))],
+            vec![
+                (json!("newer"), json!({ "timestamp": timestamp(20) })),
+                (json!("older"), json!({ "timestamp": timestamp(5) })),
+            ],
+        ];
+
+        let cases = [
+            (
+                OutOfOrder::Accept,
+                vec![(nanos(5), "older"), (nanos(20), "newer")],
+            ),
+            (OutOfOrder::Drop, vec![(nanos(20), "newer")]),
+            (
+                OutOfOrder::RewriteTimestamp,
+                vec![(nanos(10), "older"), (nanos(20), "newer")],
+            ),
+        ];
+
+        for encoding in [Encoding::Protobuf, Encoding::Json] {
+            for (out_of_order, expected) in cases.iter() {
+                let pushes = push(
+                    |loki| loki.encoding(encoding).out_of_order(*out_of_order),
+                    flushes.clone(),
+                )
+                .await;
+
+                let streams = pushes.iter().map(decode).collect::<Vec<_>>();
+                let labels = r#"{source="app"}"#.to_string();
+                let expected = expected
+                    .iter()
+                    .map(|(timestamp, line)| (*timestamp, line.to_string()))
+                    .collect();
+
+                assert_eq!(
+                    streams,
+                    vec![
+                        vec![(labels.clone(), vec![(nanos(10), "first".to_string())])],
+                        vec![(labels, expected)],
+                    ]
+                );
+            }
+        }
+    }
+}
@@ -1,5 +1,27 @@
+pub mod cgroup;
+pub mod exec;
 pub mod file;
+pub mod fluent;
+pub mod graphite;
 pub mod host;
+pub mod http;
+pub mod influx;
+pub mod journal;
+pub mod kafka;
+pub mod kmsg;
+pub mod process;
+pub mod syslog;
 
+pub use cgroup::Cgroups;
+pub use exec::{Exec, OutputFormat};
 pub use file::{Codec, File};
-pub use host::{Disks, Load, Memory};
+pub use fluent::FluentForward;
+pub use graphite::GraphiteReceiver;
+pub use host::{Cpu, Disks, Filesystems, Load, Memory, Network, Pressure, Sockets, VmStat};
+pub use http::HttpReceiver;
+pub use influx::InfluxReceiver;
+pub use journal::Journal;
+pub use kafka::KafkaConsumer;
+pub use kmsg::Kmsg;
+pub use process::{ProcessMatcher, Processes};
+pub use syslog::{Syslog, Transport};
@@ -0,0 +1,249 @@
+use std::path::{Path, PathBuf};
+
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+use eyre::WrapErr;
+use regex::Regex;
+
+pub const DEFAULT_CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Memory events reported by `memory.events`.
+const MEMORY_EVENTS: &[&str] = &["low", "high", "max", "oom", "oom_kill"];
+
+/// Reads cgroup v2 controller files for every cgroup under the root directory.
+#[derive(Clone)]
+pub struct Cgroups {
+    root: PathBuf,
+    max_depth: usize,
+    includes: Vec<Regex>,
+    container_id: Regex,
+}
+
+impl Default for Cgroups {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from(DEFAULT_CGROUP_ROOT),
+            max_depth: 3,
+            includes: Vec::new(),
+            // Covers docker, containerd, cri-o and podman naming schemes
+            // (`/docker/<id>`, `docker-<id>.scope`, `cri-containerd-<id>.scope`,
+            // `crio-<id>.scope`, `libpod-<id>.scope`...).
+            container_id: Regex::new(r"(?:^|[/-])([0-9a-f]{64})(?:\.scope)?$").unwrap(),
+        }
+    }
+}
+
+impl Cgroups {
+    pub fn root(self, root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            ..self
+        }
+    }
+
+    /// How deep the cgroup hierarchy is walked, the root cgroup being at depth 0.
+    pub fn max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Only reports cgroups whose path matches at least one of those regexes. Every cgroup is
+    /// reported if empty.
+    pub fn includes(self, includes: Vec<String>) -> eyre::Result<Self> {
+        let includes = includes
+            .iter()
+            .map(|include| {
+                Regex::new(include)
+                    .wrap_err_with(|| format!("Invalid cgroup include regex '{}'", include))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(Self { includes, ..self })
+    }
+
+    fn is_included(&self, cgroup: &str) -> bool {
+        self.includes.is_empty() || self.includes.iter().any(|r| r.is_match(cgroup))
+    }
+
+    fn collect(&self) -> eyre::Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+        let mut stack = vec![(self.root.clone(), 0usize)];
+
+        // Makes sure we are not walking a cgroup v1 hierarchy.
+        std::fs::metadata(self.root.join("cgroup.controllers")).wrap_err_with(|| {
+            format!(
+                "'{}' is not a cgroup v2 hierarchy",
+                self.root.to_string_lossy()
+            )
+        })?;
+
+        while let Some((dir, depth)) = stack.pop() {
+            let cgroup = match dir.strip_prefix(&self.root) {
+                Ok(relative) => format!("/{}", relative.to_string_lossy()),
+                Err(_) => continue,
+            };
+
+            if self.is_included(cgroup.as_str()) {
+                let container_id = self
+                    .container_id
+                    .captures(cgroup.as_str())
+                    .map(|caps| caps[1].to_string());
+
+                read_cgroup(&dir, &cgroup, container_id.as_deref(), &mut metrics);
+            }
+
+            if depth >= self.max_depth {
+                continue;
+            }
+
+            // Cgroups can be removed while we are walking the hierarchy.
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                for entry in entries.flatten() {
+                    if entry.file_type().map(|t| t.is_dir()).unwrap_or_default() {
+                        stack.push((entry.path(), depth + 1));
+                    }
+                }
+            }
+        }
+
+        Ok(metrics)
+    }
+}
+
+fn read_file(dir: &Path, name: &str) -> Option<String> {
+    std::fs::read_to_string(dir.join(name)).ok()
+}
+
+/// Parses flat keyed files like `cpu.stat` or `memory.events`.
+fn read_keyed(dir: &Path, name: &str) -> Vec<(String, f64)> {
+    read_file(dir, name)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+
+            Some((key.to_string(), value.trim().parse::<f64>().ok()?))
+        })
+        .collect()
+}
+
+/// Reads single value files. `max` means there is no limit, in which case nothing is returned.
+fn read_value(dir: &Path, name: &str) -> Option<f64> {
+    read_file(dir, name)?.trim().parse::<f64>().ok()
+}
+
+fn read_cgroup(dir: &Path, cgroup: &str, container_id: Option<&str>, metrics: &mut Vec<Metric>) {
+    let tag = |metric: MetricBuilder| {
+        let metric = metric.add_tag("cgroup", cgroup);
+
+        match container_id {
+            Some(id) => metric.add_tag("container_id", id),
+            None => metric,
+        }
+    };
+
+    for (key, value) in read_keyed(dir, "cpu.stat") {
+        let (name, value) = match key.as_str() {
+            "usage_usec" => ("cgroup_cpu_usage_seconds_total", value / 1_000_000f64),
+            "user_usec" => ("cgroup_cpu_user_seconds_total", value / 1_000_000f64),
+            "system_usec" => ("cgroup_cpu_system_seconds_total", value / 1_000_000f64),
+            "nr_periods" => ("cgroup_cpu_periods_total", value),
+            "nr_throttled" => ("cgroup_cpu_throttled_periods_total", value),
+            "throttled_usec" => ("cgroup_cpu_throttled_seconds_total", value / 1_000_000f64),
+            _ => continue,
+        };
+
+        metrics.push(tag(MetricBuilder::counter("cgroup", name, value)).build());
+    }
+
+    if let Some(current) = read_value(dir, "memory.current") {
+        metrics.push(
+            tag(MetricBuilder::gauge(
+                "cgroup",
+                "cgroup_memory_current_bytes",
+                current,
+            ))
+            .build(),
+        );
+    }
+
+    if let Some(max) = read_value(dir, "memory.max") {
+        metrics.push(
+            tag(MetricBuilder::gauge(
+                "cgroup",
+                "cgroup_memory_max_bytes",
+                max,
+            ))
+            .build(),
+        );
+    }
+
+    for (event, value) in read_keyed(dir, "memory.events") {
+        if !MEMORY_EVENTS.contains(&event.as_str()) {
+            continue;
+        }
+
+        metrics.push(
+            tag(MetricBuilder::counter(
+                "cgroup",
+                "cgroup_memory_events_total",
+                value,
+            ))
+            .add_tag("event", event)
+            .build(),
+        );
+    }
+
+    // Each line looks like `8:0 rbytes=1459200 wbytes=314773504 rios=192 wios=353 dbytes=0 dios=0`.
+    for line in read_file(dir, "io.stat").unwrap_or_default().lines() {
+        let mut fields = line.split_whitespace();
+        let device = match fields.next() {
+            Some(device) => device,
+            None => continue,
+        };
+
+        for field in fields {
+            let (key, value) = match field.split_once('=') {
+                Some((key, value)) => (key, value.parse::<f64>().unwrap_or_default()),
+                None => continue,
+            };
+
+            let name = match key {
+                "rbytes" => "cgroup_io_read_bytes_total",
+                "wbytes" => "cgroup_io_written_bytes_total",
+                "rios" => "cgroup_io_reads_total",
+                "wios" => "cgroup_io_writes_total",
+                _ => continue,
+            };
+
+            metrics.push(
+                tag(MetricBuilder::counter("cgroup", name, value))
+                    .add_tag("device", device)
+                    .build(),
+            );
+        }
+    }
+
+    if let Some(current) = read_value(dir, "pids.current") {
+        metrics.push(
+            tag(MetricBuilder::gauge(
+                "cgroup",
+                "cgroup_pids_current",
+                current,
+            ))
+            .build(),
+        );
+    }
+
+    if let Some(max) = read_value(dir, "pids.max") {
+        metrics.push(tag(MetricBuilder::gauge("cgroup", "cgroup_pids_max", max)).build());
+    }
+}
+
+#[async_trait::async_trait]
+impl PollingSource for Cgroups {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let cgroups = self.clone();
+        tokio::task::spawn_blocking(move || cgroups.collect())
+            .await
+            .wrap_err("Cgroup scanning task panicked")?
+    }
+}
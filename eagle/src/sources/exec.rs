@@ -0,0 +1,164 @@
+use std::{collections::HashMap, path::PathBuf, process::Stdio, time::Instant};
+
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+use serde::Deserialize;
+use tokio::{process::Command, time::Duration};
+
+use crate::formats::{influx, json, nagios, prometheus};
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Prometheus,
+    Influx,
+    Json,
+    Nagios,
+}
+
+/// Runs a command on every tick and parses its standard output into metrics.
+pub struct Exec {
+    command: String,
+    args: Vec<String>,
+    env: HashMap<String, String>,
+    working_dir: Option<PathBuf>,
+    timeout: Duration,
+    format: OutputFormat,
+    category: String,
+}
+
+impl Exec {
+    pub fn new(command: impl AsRef<str>, format: OutputFormat) -> Self {
+        Self {
+            command: command.as_ref().to_string(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            working_dir: None,
+            timeout: Duration::from_secs(10),
+            format,
+            category: "exec".to_string(),
+        }
+    }
+
+    pub fn args(self, args: Vec<String>) -> Self {
+        Self { args, ..self }
+    }
+
+    pub fn env(self, env: HashMap<String, String>) -> Self {
+        Self { env, ..self }
+    }
+
+    pub fn working_dir(self, working_dir: Option<PathBuf>) -> Self {
+        Self {
+            working_dir,
+            ..self
+        }
+    }
+
+    /// The command is killed when it runs longer than that.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Category of the exec metrics and of parsed metrics that don't carry one. InfluxDB line
+    /// protocol measurements are always used as category.
+    pub fn category(self, category: impl AsRef<str>) -> Self {
+        Self {
+            category: category.as_ref().to_string(),
+            ..self
+        }
+    }
+
+    fn parse_output(&self, output: &str) -> eyre::Result<Vec<Metric>> {
+        let category = self.category.as_str();
+
+        match self.format {
+            OutputFormat::Prometheus => prometheus::parse(category, output),
+            OutputFormat::Influx => influx::parse(output, influx::Precision::Nanoseconds),
+            OutputFormat::Json => json::parse(category, output),
+            OutputFormat::Nagios => nagios::parse(category, output),
+        }
+    }
+
+    fn exec_metric(&self, name: &str, value: f64) -> Metric {
+        MetricBuilder::gauge(self.category.as_str(), name, value)
+            .add_tag("command", self.command.as_str())
+            .build()
+    }
+}
+
+#[async_trait::async_trait]
+impl PollingSource for Exec {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let mut command = Command::new(self.command.as_str());
+
+        command
+            .args(self.args.iter())
+            .envs(self.env.iter())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        if let Some(working_dir) = self.working_dir.as_ref() {
+            command.current_dir(working_dir);
+        }
+
+        let started = Instant::now();
+
+        // A missing or broken command is reported but doesn't stop the source, it might be
+        // fixed by the next tick.
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                tracing::warn!("Error when running command '{}': {}", self.command, e);
+                return Ok(Vec::new());
+            }
+        };
+
+        // On timeout, the child is dropped and therefore killed.
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output()).await;
+        let runtime = started.elapsed().as_secs_f64();
+        let mut metrics = vec![self.exec_metric("exec_runtime_seconds", runtime)];
+
+        let output = match output {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => {
+                tracing::warn!("Error when waiting for command '{}': {}", self.command, e);
+                return Ok(metrics);
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "Command '{}' timed out after {:?}",
+                    self.command,
+                    self.timeout
+                );
+
+                metrics.push(self.exec_metric("exec_timed_out", 1f64));
+                return Ok(metrics);
+            }
+        };
+
+        metrics.push(self.exec_metric("exec_timed_out", 0f64));
+
+        // There is no exit code when the command is killed by a signal.
+        if let Some(code) = output.status.code() {
+            metrics.push(self.exec_metric("exec_exit_code", code as f64));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        match self.parse_output(stdout.as_ref()) {
+            Ok(parsed) => metrics.extend(parsed),
+            Err(e) => {
+                tracing::warn!(
+                    "Error when parsing the output of command '{}': {:?}. stderr: {}",
+                    self.command,
+                    e,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        }
+
+        Ok(metrics)
+    }
+}
@@ -0,0 +1,788 @@
+use std::{io::Read, net::SocketAddr, path::Path};
+
+use chrono::{DateTime, TimeZone, Utc};
+use eagle_core::{EagleClient, Source};
+use eyre::{bail, eyre, WrapErr};
+use flate2::read::MultiGzDecoder;
+use rmpv::Value as MsgPack;
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+};
+use tokio_rustls::TlsAcceptor;
+
+#[derive(Serialize)]
+struct Metadata<'a> {
+    tag: &'a str,
+    timestamp: String,
+    peer: &'a str,
+}
+
+/// Receives logs sent by Fluentd or Fluent Bit `forward` outputs, using the Forward protocol
+/// over TCP. Message, Forward, PackedForward and CompressedPackedForward modes are supported,
+/// and chunks are acknowledged once their records are handed to the engine when the sender
+/// asks for it (`require_ack_response`).
+///
+/// Each record is the log value, while its tag and time end up in the log metadata. The shared
+/// key handshake isn't supported, use TLS to restrict who can send logs.
+pub struct FluentForward {
+    address: String,
+    tls: Option<TlsAcceptor>,
+    max_message_size: usize,
+}
+
+impl FluentForward {
+    pub fn new(address: impl AsRef<str>) -> Self {
+        Self {
+            address: address.as_ref().to_string(),
+            tls: None,
+            max_message_size: 16 * 1_024 * 1_024,
+        }
+    }
+
+    pub fn tls(
+        self,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> eyre::Result<Self> {
+        Ok(Self {
+            tls: Some(crate::tls::acceptor(cert_path, key_path)?),
+            ..self
+        })
+    }
+
+    /// Applies to messages both as sent and once decompressed. Connections sending larger
+    /// ones are closed.
+    pub fn max_message_size(self, max_message_size: usize) -> Self {
+        Self {
+            max_message_size,
+            ..self
+        }
+    }
+}
+
+async fn handle_connection<S>(
+    mut stream: S,
+    peer: SocketAddr,
+    client: &EagleClient,
+    max_message_size: usize,
+) -> eyre::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let peer = peer.to_string();
+    let mut buffer = Vec::new();
+    let mut chunk = vec![0u8; 64 * 1_024];
+    let mut scanner = MessageScanner::new();
+
+    loop {
+        while let Some(size) = scanner.message_size(&buffer, max_message_size)? {
+            let message = rmpv::decode::read_value(&mut &buffer[..size])
+                .map_err(|e| eyre!("Invalid MessagePack: {}", e))?;
+
+            buffer.drain(..size);
+
+            if let Some(ack) =
+                handle_message(client, peer.as_str(), message, max_message_size).await?
+            {
+                stream.write_all(ack.as_slice()).await?;
+                stream.flush().await?;
+            }
+        }
+
+        let size = stream.read(&mut chunk).await?;
+
+        if size == 0 {
+            if !buffer.is_empty() {
+                bail!("Connection closed in the middle of a message");
+            }
+
+            return Ok(());
+        }
+
+        buffer.extend_from_slice(&chunk[..size]);
+    }
+}
+
+/// Sends the records of a message, returning the acknowledgement to send back if the sender
+/// asked for one.
+async fn handle_message(
+    client: &EagleClient,
+    peer: &str,
+    message: MsgPack,
+    max_message_size: usize,
+) -> eyre::Result<Option<Vec<u8>>> {
+    let mut items = match message {
+        MsgPack::Array(items) if items.len() >= 2 => items.into_iter(),
+        _ => bail!("Expected an array holding a tag and records"),
+    };
+
+    let tag = match items.next() {
+        Some(MsgPack::String(tag)) => tag.into_str().ok_or_else(|| eyre!("Invalid tag"))?,
+        _ => bail!("Expected a tag"),
+    };
+
+    let entries = items.next().unwrap();
+    let mut records = Vec::new();
+
+    let option = match entries {
+        // Forward mode.
+        MsgPack::Array(entries) => {
+            for entry in entries {
+                records.push(parse_entry(entry)?);
+            }
+
+            items.next()
+        }
+
+        // PackedForward and CompressedPackedForward modes, where entries are concatenated.
+        MsgPack::Binary(_) | MsgPack::String(_) => {
+            let option = items.next();
+            let compressed = option
+                .as_ref()
+                .and_then(|option| option_value(option, "compressed"))
+                .and_then(MsgPack::as_str);
+
+            let packed = match entries {
+                MsgPack::Binary(bytes) => bytes,
+                MsgPack::String(string) => string.into_bytes(),
+                _ => unreachable!(),
+            };
+
+            let packed = match compressed {
+                None | Some("text") => packed,
+                Some("gzip") => {
+                    let mut decompressed = Vec::new();
+
+                    MultiGzDecoder::new(packed.as_slice())
+                        .take(max_message_size as u64 + 1)
+                        .read_to_end(&mut decompressed)
+                        .wrap_err("Invalid gzip entries")?;
+
+                    if decompressed.len() > max_message_size {
+                        bail!("Decompressed entries exceed {} bytes", max_message_size);
+                    }
+
+                    decompressed
+                }
+                Some(other) => bail!("Unsupported compression '{}'", other),
+            };
+
+            let mut input = packed.as_slice();
+
+            while !input.is_empty() {
+                let entry = rmpv::decode::read_value(&mut input)
+                    .map_err(|e| eyre!("Invalid packed entry: {}", e))?;
+
+                records.push(parse_entry(entry)?);
+            }
+
+            option
+        }
+
+        // Message mode.
+        time => {
+            let record = items.next().ok_or_else(|| eyre!("Missing record"))?;
+
+            records.push((parse_time(&time)?, to_json(record)));
+            items.next()
+        }
+    };
+
+    for (time, record) in records {
+        let metadata = Metadata {
+            tag: tag.as_str(),
+            timestamp: time.to_rfc3339(),
+            peer,
+        };
+
+        client.send_log_with_metadata(record, metadata).await?;
+    }
+
+    let chunk = option
+        .as_ref()
+        .and_then(|option| option_value(option, "chunk"))
+        .cloned();
+
+    let ack = match chunk {
+        Some(chunk) => {
+            let mut ack = Vec::new();
+
+            rmpv::encode::write_value(
+                &mut ack,
+                &MsgPack::Map(vec![(MsgPack::from("ack"), chunk)]),
+            )?;
+
+            Some(ack)
+        }
+
+        None => None,
+    };
+
+    Ok(ack)
+}
+
+fn option_value<'a>(option: &'a MsgPack, key: &str) -> Option<&'a MsgPack> {
+    option
+        .as_map()?
+        .iter()
+        .find(|(name, _)| name.as_str() == Some(key))
+        .map(|(_, value)| value)
+}
+
+/// Parses a `[time, record]` entry.
+fn parse_entry(entry: MsgPack) -> eyre::Result<(DateTime<Utc>, Value)> {
+    match entry {
+        MsgPack::Array(entry) if entry.len() == 2 => {
+            let mut entry = entry.into_iter();
+            let time = parse_time(&entry.next().unwrap())?;
+
+            Ok((time, to_json(entry.next().unwrap())))
+        }
+
+        _ => bail!("Expected a [time, record] entry"),
+    }
+}
+
+/// Times are either seconds or an `EventTime`, i.e. the extension type 0 holding big-endian
+/// seconds and nanoseconds. Fluent Bit 2.1 and later may wrap them as `[time, metadata]`.
+fn parse_time(time: &MsgPack) -> eyre::Result<DateTime<Utc>> {
+    match time {
+        MsgPack::Array(items) if !items.is_empty() => parse_time(&items[0]),
+        MsgPack::Integer(seconds) => seconds
+            .as_i64()
+            .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+            .ok_or_else(|| eyre!("Invalid time {}", seconds)),
+        MsgPack::F64(seconds) => Ok(Utc.timestamp_nanos((seconds * 1e9) as i64)),
+        MsgPack::Ext(0, bytes) if bytes.len() == 8 => {
+            let seconds = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            let nanos = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+            Utc.timestamp_opt(seconds as i64, nanos)
+                .single()
+                .ok_or_else(|| eyre!("Invalid event time"))
+        }
+        other => bail!("Invalid time {}", other),
+    }
+}
+
+fn to_json(value: MsgPack) -> Value {
+    match value {
+        MsgPack::Nil => Value::Null,
+        MsgPack::Boolean(value) => Value::Bool(value),
+        MsgPack::Integer(value) => value
+            .as_i64()
+            .map(Number::from)
+            .or_else(|| value.as_u64().map(Number::from))
+            .map_or(Value::Null, Value::Number),
+        MsgPack::F32(value) => Number::from_f64(value as f64).map_or(Value::Null, Value::Number),
+        MsgPack::F64(value) => Number::from_f64(value).map_or(Value::Null, Value::Number),
+        MsgPack::String(value) => match value.into_str() {
+            Some(value) => Value::String(value),
+            None => Value::Null,
+        },
+        // Fluentd sends strings as binary when using its older `raw` format.
+        MsgPack::Binary(bytes) => match String::from_utf8(bytes) {
+            Ok(value) => Value::String(value),
+            Err(e) => Value::Array(e.into_bytes().into_iter().map(Value::from).collect()),
+        },
+        MsgPack::Array(values) => Value::Array(values.into_iter().map(to_json).collect()),
+        MsgPack::Map(entries) => {
+            let mut map = Map::new();
+
+            for (key, value) in entries {
+                let key = match key {
+                    MsgPack::String(key) => key.into_str().unwrap_or_default(),
+                    MsgPack::Binary(key) => String::from_utf8_lossy(&key).into_owned(),
+                    key => key.to_string(),
+                };
+
+                map.insert(key, to_json(value));
+            }
+
+            Value::Object(map)
+        }
+        MsgPack::Ext(_, bytes) => Value::Array(bytes.into_iter().map(Value::from).collect()),
+    }
+}
+
+/// Finds where the MessagePack value at the start of a growing buffer ends. The position and
+/// count of values left to read are kept between calls, so large messages spanning many reads
+/// don't get scanned again from the start each time.
+struct MessageScanner {
+    position: usize,
+    pending: u64,
+}
+
+impl MessageScanner {
+    fn new() -> Self {
+        Self {
+            position: 0,
+            pending: 1,
+        }
+    }
+
+    /// Size of the value at the start of `input`, or `None` if it isn't complete yet. Only
+    /// headers are looked at, so nothing gets decoded until the whole value was received.
+    /// `input` may only grow between calls, until a size is returned and the value removed.
+    fn message_size(
+        &mut self,
+        input: &[u8],
+        max_message_size: usize,
+    ) -> eyre::Result<Option<usize>> {
+        while self.pending > 0 {
+            let (size, children) = match value_header(&input[self.position.min(input.len())..])? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+
+            self.position += size;
+            self.pending = self.pending - 1 + children;
+
+            if self.position > max_message_size {
+                bail!("Message exceeds {} bytes", max_message_size);
+            }
+        }
+
+        if self.position > input.len() {
+            return Ok(None);
+        }
+
+        let size = self.position;
+        *self = Self::new();
+
+        Ok(Some(size))
+    }
+}
+
+/// Size of the value at the start of `input` without its children, if it's an array or a map,
+/// and how many children it has.
+fn value_header(input: &[u8]) -> eyre::Result<Option<(usize, u64)>> {
+    let marker = match input.first() {
+        Some(marker) => *marker,
+        None => return Ok(None),
+    };
+
+    let length = |width: usize| {
+        input.get(1..1 + width).map(|bytes| {
+            bytes
+                .iter()
+                .fold(0usize, |acc, byte| (acc << 8) | *byte as usize)
+        })
+    };
+
+    let header = match marker {
+        0x00..=0x7f | 0xe0..=0xff | 0xc0 | 0xc2 | 0xc3 => Some((1, 0)),
+        0x80..=0x8f => Some((1, 2 * (marker & 0x0f) as u64)),
+        0x90..=0x9f => Some((1, (marker & 0x0f) as u64)),
+        0xa0..=0xbf => Some((1 + (marker & 0x1f) as usize, 0)),
+        0xcc | 0xd0 => Some((2, 0)),
+        0xcd | 0xd1 => Some((3, 0)),
+        0xca | 0xce | 0xd2 => Some((5, 0)),
+        0xcb | 0xcf | 0xd3 => Some((9, 0)),
+        0xd4 => Some((3, 0)),
+        0xd5 => Some((4, 0)),
+        0xd6 => Some((6, 0)),
+        0xd7 => Some((10, 0)),
+        0xd8 => Some((18, 0)),
+        // Strings and binaries.
+        0xc4 | 0xd9 => length(1).map(|length| (2 + length, 0)),
+        0xc5 | 0xda => length(2).map(|length| (3 + length, 0)),
+        0xc6 | 0xdb => length(4).map(|length| (5 + length, 0)),
+        // Extensions, which also have a type byte.
+        0xc7 => length(1).map(|length| (3 + length, 0)),
+        0xc8 => length(2).map(|length| (4 + length, 0)),
+        0xc9 => length(4).map(|length| (6 + length, 0)),
+        // Arrays and maps.
+        0xdc => length(2).map(|count| (3, count as u64)),
+        0xdd => length(4).map(|count| (5, count as u64)),
+        0xde => length(2).map(|count| (3, 2 * count as u64)),
+        0xdf => length(4).map(|count| (5, 2 * count as u64)),
+        _ => bail!("Invalid MessagePack marker {:#x}", marker),
+    };
+
+    Ok(header)
+}
+
+#[async_trait::async_trait]
+impl Source for FluentForward {
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        let listener = TcpListener::bind(self.address.as_str())
+            .await
+            .wrap_err_with(|| format!("Error when binding TCP listener on {}", self.address))?;
+
+        loop {
+            let (stream, peer) = listener
+                .accept()
+                .await
+                .wrap_err("Error when accepting TCP connection")?;
+
+            let client = client.clone();
+            let tls = self.tls.clone();
+            let max_message_size = self.max_message_size;
+
+            tokio::spawn(async move {
+                let result = match tls {
+                    None => handle_connection(stream, peer, &client, max_message_size).await,
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => {
+                            handle_connection(stream, peer, &client, max_message_size).await
+                        }
+                        Err(e) => Err(e).wrap_err("TLS handshake failed"),
+                    },
+                };
+
+                if let Err(e) = result {
+                    tracing::warn!(
+                        target = client.origin().instance_id(),
+                        "Forward connection from {} closed: {:?}",
+                        peer,
+                        e
+                    );
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eagle_core::{poll::Schedule, EagleEndpoint, EagleEvent, Event, Origin};
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::json;
+    use std::{io::Write, sync::Arc};
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    fn client() -> (EagleClient, UnboundedReceiver<EagleEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let client = EagleClient {
+            origin: Arc::new(Origin::new("fluent")),
+            endpoint: EagleEndpoint::new(sender),
+            schedule: Schedule::default(),
+        };
+
+        (client, receiver)
+    }
+
+    fn logs(receiver: &mut UnboundedReceiver<EagleEvent>) -> Vec<(Value, Value)> {
+        let mut logs = Vec::new();
+
+        while let Ok(event) = receiver.try_recv() {
+            match event.event {
+                Event::Log(log) => logs.push((log.inner.as_ref().clone(), log.metadata)),
+                other => panic!("Unexpected event {:?}", other),
+            }
+        }
+
+        logs
+    }
+
+    fn encode(value: &MsgPack) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        rmpv::encode::write_value(&mut bytes, value).unwrap();
+        bytes
+    }
+
+    fn map(entries: Vec<(&str, MsgPack)>) -> MsgPack {
+        MsgPack::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (MsgPack::from(key), value))
+                .collect(),
+        )
+    }
+
+    fn event_time(seconds: u32, nanos: u32) -> MsgPack {
+        let mut bytes = seconds.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&nanos.to_be_bytes());
+        MsgPack::Ext(0, bytes)
+    }
+
+    fn entry(time: MsgPack, message: &str) -> MsgPack {
+        MsgPack::Array(vec![time, map(vec![("message", MsgPack::from(message))])])
+    }
+
+    fn metadata(tag: &str, timestamp: &str) -> Value {
+        json!({ "tag": tag, "timestamp": timestamp, "peer": "127.0.0.1:24224" })
+    }
+
+    #[test]
+    fn parses_times() {
+        let cases = vec![
+            (MsgPack::from(1_700_000_000), "2023-11-14T22:13:20+00:00"),
+            (
+                MsgPack::F64(1_700_000_000.5),
+                "2023-11-14T22:13:20.500+00:00",
+            ),
+            (
+                event_time(1_700_000_000, 123_456_789),
+                "2023-11-14T22:13:20.123456789+00:00",
+            ),
+            (
+                MsgPack::Array(vec![event_time(1_700_000_000, 1_000), map(vec![])]),
+                "2023-11-14T22:13:20.000001+00:00",
+            ),
+        ];
+
+        for (time, expected) in cases {
+            assert_eq!(
+                parse_time(&time).unwrap().to_rfc3339(),
+                expected,
+                "parsing {}",
+                time
+            );
+        }
+
+        assert!(parse_time(&MsgPack::from("now")).is_err());
+        assert!(parse_time(&MsgPack::Ext(0, vec![0; 4])).is_err());
+        assert!(parse_time(&MsgPack::Ext(1, vec![0; 8])).is_err());
+    }
+
+    #[tokio::test]
+    async fn decodes_every_mode() {
+        let entries = vec![
+            entry(MsgPack::from(1_700_000_000), "first"),
+            entry(event_time(1_700_000_001, 500_000_000), "second"),
+        ];
+        let packed: Vec<u8> = entries.iter().flat_map(encode).collect();
+        let mut compressed = GzEncoder::new(Vec::new(), Compression::default());
+        compressed.write_all(&packed).unwrap();
+        let compressed = compressed.finish().unwrap();
+
+        let expected = [
+            (
+                json!({ "message": "first" }),
+                metadata("app", "2023-11-14T22:13:20+00:00"),
+            ),
+            (
+                json!({ "message": "second" }),
+                metadata("app", "2023-11-14T22:13:21.500+00:00"),
+            ),
+        ];
+
+        let cases = vec![
+            (
+                "message",
+                MsgPack::Array(vec![
+                    MsgPack::from("app"),
+                    MsgPack::from(1_700_000_000),
+                    map(vec![("message", MsgPack::from("first"))]),
+                ]),
+                &expected[..1],
+            ),
+            (
+                "forward",
+                MsgPack::Array(vec![MsgPack::from("app"), MsgPack::Array(entries.clone())]),
+                &expected[..],
+            ),
+            (
+                "packed forward",
+                MsgPack::Array(vec![MsgPack::from("app"), MsgPack::Binary(packed.clone())]),
+                &expected[..],
+            ),
+            (
+                "compressed packed forward",
+                MsgPack::Array(vec![
+                    MsgPack::from("app"),
+                    MsgPack::Binary(compressed),
+                    map(vec![("compressed", MsgPack::from("gzip"))]),
+                ]),
+                &expected[..],
+            ),
+        ];
+
+        for (mode, message, expected) in cases {
+            let (client, mut receiver) = client();
+            let ack = handle_message(&client, "127.0.0.1:24224", message, 1_024)
+                .await
+                .unwrap();
+
+            assert_eq!(ack, None, "acknowledging {}", mode);
+            assert_eq!(logs(&mut receiver), expected, "decoding {}", mode);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_messages() {
+        let (client, _receiver) = client();
+        let bomb = {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&[0xc0; 2_048]).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let cases = vec![
+            MsgPack::from("app"),
+            MsgPack::Array(vec![MsgPack::from(1), MsgPack::Array(vec![])]),
+            MsgPack::Array(vec![MsgPack::from("app"), MsgPack::from(1_700_000_000)]),
+            MsgPack::Array(vec![
+                MsgPack::from("app"),
+                MsgPack::Array(vec![MsgPack::from(1_700_000_000)]),
+            ]),
+            MsgPack::Array(vec![
+                MsgPack::from("app"),
+                MsgPack::Binary(vec![0xc1]),
+                map(vec![]),
+            ]),
+            MsgPack::Array(vec![
+                MsgPack::from("app"),
+                MsgPack::Binary(vec![]),
+                map(vec![("compressed", MsgPack::from("zstd"))]),
+            ]),
+            MsgPack::Array(vec![
+                MsgPack::from("app"),
+                MsgPack::Binary(bomb),
+                map(vec![("compressed", MsgPack::from("gzip"))]),
+            ]),
+        ];
+
+        for message in cases {
+            let description = message.to_string();
+
+            assert!(
+                handle_message(&client, "127.0.0.1:24224", message, 1_024)
+                    .await
+                    .is_err(),
+                "rejecting {}",
+                description
+            );
+        }
+    }
+
+    #[test]
+    fn scans_messages_incrementally() {
+        let messages = vec![
+            MsgPack::from(1),
+            MsgPack::from("a string spanning reads"),
+            MsgPack::Array(vec![
+                MsgPack::from("app"),
+                MsgPack::Array(vec![entry(event_time(1, 2), "message"); 20]),
+                map(vec![("chunk", MsgPack::from("p8n9gmxTQVC8/nh2wlKKeQ=="))]),
+            ]),
+            MsgPack::Binary(vec![7; 300]),
+            map(vec![(
+                "nested",
+                map(vec![("array", MsgPack::Array(vec![]))]),
+            )]),
+        ];
+
+        for message in messages {
+            let bytes = encode(&message);
+
+            for step in [1, 3, bytes.len()] {
+                let mut scanner = MessageScanner::new();
+                let mut buffer = Vec::new();
+                let mut sizes = Vec::new();
+
+                // Two copies, so the scanner has to reset once the first one is complete.
+                for byte in bytes.iter().chain(&bytes).collect::<Vec<_>>().chunks(step) {
+                    buffer.extend(byte.iter().copied());
+
+                    while let Some(size) = scanner.message_size(&buffer, 1_024).unwrap() {
+                        sizes.push(size);
+                        buffer.drain(..size);
+                    }
+                }
+
+                assert_eq!(
+                    sizes,
+                    vec![bytes.len(); 2],
+                    "scanning {} by {}",
+                    message,
+                    step
+                );
+                assert!(buffer.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_messages() {
+        let mut scanner = MessageScanner::new();
+        let bytes = encode(&MsgPack::Binary(vec![0; 2_048]));
+
+        assert!(scanner.message_size(&bytes[..3], 1_024).is_err());
+
+        let mut scanner = MessageScanner::new();
+        let bytes = encode(&MsgPack::Array(vec![MsgPack::from(1); 2_048]));
+
+        assert!(scanner.message_size(&bytes, 1_024).is_err());
+        assert!(MessageScanner::new().message_size(&[0xc1], 1_024).is_err());
+    }
+
+    #[tokio::test]
+    async fn acknowledges_chunks() {
+        let (client, mut receiver) = client();
+        let (mut sender, server) = tokio::io::duplex(64);
+        let peer: SocketAddr = "127.0.0.1:24224".parse().unwrap();
+
+        let connection =
+            tokio::spawn(async move { handle_connection(server, peer, &client, 1_024).await });
+
+        let acked = MsgPack::Array(vec![
+            MsgPack::from("app"),
+            MsgPack::Array(vec![entry(MsgPack::from(1_700_000_000), "first")]),
+            map(vec![("chunk", MsgPack::from("p8n9gmxTQVC8/nh2wlKKeQ=="))]),
+        ]);
+        let unacked = MsgPack::Array(vec![
+            MsgPack::from("app"),
+            MsgPack::Array(vec![entry(MsgPack::from(1_700_000_001), "second")]),
+        ]);
+
+        sender.write_all(&encode(&unacked)).await.unwrap();
+        sender.write_all(&encode(&acked)).await.unwrap();
+
+        let ack = rmpv::decode::read_value(&mut {
+            let mut bytes = vec![0; 64];
+            let size = sender.read(&mut bytes).await.unwrap();
+            bytes.truncate(size);
+            std::io::Cursor::new(bytes)
+        })
+        .unwrap();
+
+        assert_eq!(
+            ack,
+            map(vec![("ack", MsgPack::from("p8n9gmxTQVC8/nh2wlKKeQ=="))])
+        );
+
+        drop(sender);
+        connection.await.unwrap().unwrap();
+
+        let messages: Vec<_> = logs(&mut receiver)
+            .into_iter()
+            .map(|(log, metadata)| (log["message"].clone(), metadata["timestamp"].clone()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                (json!("second"), json!("2023-11-14T22:13:21+00:00")),
+                (json!("first"), json!("2023-11-14T22:13:20+00:00")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_truncated_connections() {
+        let (client, _receiver) = client();
+        let (mut sender, server) = tokio::io::duplex(64);
+        let peer: SocketAddr = "127.0.0.1:24224".parse().unwrap();
+
+        let connection =
+            tokio::spawn(async move { handle_connection(server, peer, &client, 1_024).await });
+
+        let bytes = encode(&MsgPack::Array(vec![
+            MsgPack::from("app"),
+            MsgPack::from(1_700_000_000),
+            map(vec![]),
+        ]));
+
+        sender.write_all(&bytes[..bytes.len() - 1]).await.unwrap();
+        drop(sender);
+
+        assert!(connection.await.unwrap().is_err());
+    }
+}
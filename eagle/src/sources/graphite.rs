@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use eagle_core::{EagleClient, Metric, Source};
+
+use crate::{formats::graphite::Templates, lines, sources::Transport};
+
+/// Receives metrics in the Graphite plaintext protocol, i.e. `path value timestamp` lines, over
+/// UDP or TCP. Paths are turned into a category, a name and tags by the first matching template,
+/// see `formats::graphite::Template`.
+pub struct GraphiteReceiver {
+    address: String,
+    transport: Transport,
+    templates: Arc<Templates>,
+    max_message_size: usize,
+}
+
+impl GraphiteReceiver {
+    pub fn new(address: impl AsRef<str>, transport: Transport) -> Self {
+        Self {
+            address: address.as_ref().to_string(),
+            transport,
+            templates: Arc::new(Templates::default()),
+            max_message_size: 65_536,
+        }
+    }
+
+    pub fn templates(self, templates: Templates) -> Self {
+        Self {
+            templates: Arc::new(templates),
+            ..self
+        }
+    }
+
+    /// Largest UDP datagram or TCP line accepted.
+    pub fn max_message_size(self, max_message_size: usize) -> Self {
+        Self {
+            max_message_size,
+            ..self
+        }
+    }
+
+    async fn listen_udp(&self, client: EagleClient) -> eyre::Result<()> {
+        let templates = self.templates.clone();
+
+        lines::listen_udp(&self.address, &client, self.max_message_size, move |line| {
+            parse_line(&templates, line)
+        })
+        .await
+    }
+
+    async fn listen_tcp(&self, client: EagleClient) -> eyre::Result<()> {
+        let templates = self.templates.clone();
+
+        lines::listen_tcp(&self.address, &client, self.max_message_size, move |line| {
+            parse_line(&templates, line)
+        })
+        .await
+    }
+}
+
+fn parse_line(templates: &Templates, line: &str) -> eyre::Result<Vec<Metric>> {
+    Ok(templates.parse_line(line)?.into_iter().collect())
+}
+
+#[async_trait::async_trait]
+impl Source for GraphiteReceiver {
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        match self.transport {
+            Transport::Udp => self.listen_udp(client).await,
+            Transport::Tcp => self.listen_tcp(client).await,
+        }
+    }
+}
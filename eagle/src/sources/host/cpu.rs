@@ -0,0 +1,117 @@
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+use eyre::WrapErr;
+use futures::StreamExt;
+use heim::units::time::second;
+use heim_cpu::CpuTime;
+
+/// Time spent in each CPU state, in seconds.
+struct Times {
+    states: Vec<(&'static str, f64)>,
+}
+
+impl Times {
+    fn new(time: &CpuTime) -> Self {
+        let mut states = vec![
+            ("user", time.user().get::<second>()),
+            ("system", time.system().get::<second>()),
+            ("idle", time.idle().get::<second>()),
+        ];
+
+        #[cfg(target_os = "linux")]
+        {
+            use heim_cpu::os::linux::CpuTimeExt;
+
+            states.push(("nice", time.nice().get::<second>()));
+            states.push(("iowait", time.io_wait().get::<second>()));
+            states.push(("irq", time.irq().get::<second>()));
+            states.push(("softirq", time.soft_irq().get::<second>()));
+            states.push(("steal", time.steal().get::<second>()));
+        }
+
+        Self { states }
+    }
+
+    fn total(&self) -> f64 {
+        self.states.iter().map(|(_, value)| value).sum()
+    }
+
+    fn utilization(&self, previous: &Times, cpu: &str) -> Vec<Metric> {
+        let elapsed = self.total() - previous.total();
+
+        if elapsed <= 0f64 {
+            return Vec::new();
+        }
+
+        self.states
+            .iter()
+            .zip(previous.states.iter())
+            .map(|((state, current), (_, previous))| {
+                let percent = ((current - previous) / elapsed * 100f64).clamp(0f64, 100f64);
+
+                MetricBuilder::gauge("host", format!("cpu_{}_percent", state), percent)
+                    .add_tag("cpu", cpu)
+                    .build()
+            })
+            .collect()
+    }
+}
+
+pub struct Cpu {
+    per_core: bool,
+    previous_total: Option<Times>,
+    previous_cores: Vec<Times>,
+}
+
+impl Cpu {
+    pub fn new(per_core: bool) -> Self {
+        Self {
+            per_core,
+            previous_total: None,
+            previous_cores: Vec::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PollingSource for Cpu {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+        let total = Times::new(
+            &heim_cpu::time()
+                .await
+                .wrap_err("Failed to load CPU times")?,
+        );
+
+        if let Some(previous) = self.previous_total.as_ref() {
+            metrics.extend(total.utilization(previous, "total"));
+        }
+
+        self.previous_total = Some(total);
+
+        if self.per_core {
+            let times = heim_cpu::times()
+                .await
+                .wrap_err("Failed to load per-core CPU times")?;
+
+            let mut times = Box::pin(times);
+            let mut cores = Vec::new();
+
+            while let Some(item) = times.next().await {
+                cores.push(Times::new(&item.wrap_err("Failed to load CPU core times")?));
+            }
+
+            // Cores can go offline between ticks, in which case we just start over.
+            if cores.len() == self.previous_cores.len() {
+                for (index, (current, previous)) in
+                    cores.iter().zip(self.previous_cores.iter()).enumerate()
+                {
+                    metrics.extend(current.utilization(previous, index.to_string().as_str()));
+                }
+            }
+
+            self.previous_cores = cores;
+        }
+
+        Ok(metrics)
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap, ffi::CString, os::unix::ffi::OsStrExt, path::PathBuf, time::Duration,
+};
+
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+use eyre::{bail, WrapErr};
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+
+/// Pseudo filesystems that don't hold data worth monitoring.
+pub const DEFAULT_IGNORED_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "overlay",
+    "proc",
+    "pstore",
+    "ramfs",
+    "rpc_pipefs",
+    "securityfs",
+    "squashfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+struct Mount {
+    mount_point: PathBuf,
+    device: String,
+    fs_type: String,
+}
+
+struct Stats {
+    total: f64,
+    free: f64,
+    available: f64,
+    inodes_total: f64,
+    inodes_free: f64,
+}
+
+// Why there are `u64::from()` conversions: depending on the architecture,
+// `statvfs` fields are either `u32` or `u64`.
+#[allow(clippy::useless_conversion)]
+fn statvfs(mount_point: PathBuf) -> eyre::Result<Stats> {
+    let path = CString::new(mount_point.as_os_str().as_bytes())
+        .wrap_err_with(|| format!("Invalid mount point {:?}", mount_point))?;
+
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        bail!(
+            "statvfs failed on {:?}: {}",
+            mount_point,
+            std::io::Error::last_os_error()
+        );
+    }
+
+    let stats = unsafe { stats.assume_init() };
+    let fragment = u64::from(stats.f_frsize) as f64;
+
+    Ok(Stats {
+        total: u64::from(stats.f_blocks) as f64 * fragment,
+        free: u64::from(stats.f_bfree) as f64 * fragment,
+        available: u64::from(stats.f_bavail) as f64 * fragment,
+        inodes_total: u64::from(stats.f_files) as f64,
+        inodes_free: u64::from(stats.f_ffree) as f64,
+    })
+}
+
+pub struct Filesystems {
+    fs_types: Vec<String>,
+    ignored_fs_types: Vec<String>,
+    ignored_mount_points: Vec<String>,
+    statvfs_timeout: Duration,
+    // statvfs calls that timed out but are still running, a blocking call can't be cancelled.
+    pending: HashMap<PathBuf, JoinHandle<eyre::Result<Stats>>>,
+}
+
+impl Default for Filesystems {
+    fn default() -> Self {
+        Self {
+            fs_types: Vec::new(),
+            ignored_fs_types: DEFAULT_IGNORED_FS_TYPES
+                .iter()
+                .map(|t| t.to_string())
+                .collect(),
+            ignored_mount_points: Vec::new(),
+            statvfs_timeout: Duration::from_secs(5),
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl Filesystems {
+    /// Only reports those filesystem types. When empty, every type not ignored is reported.
+    pub fn fs_types(self, fs_types: Vec<String>) -> Self {
+        Self { fs_types, ..self }
+    }
+
+    pub fn ignored_fs_types(self, ignored_fs_types: Vec<String>) -> Self {
+        Self {
+            ignored_fs_types,
+            ..self
+        }
+    }
+
+    /// Mount points starting with any of those prefixes are ignored.
+    pub fn ignored_mount_points(self, ignored_mount_points: Vec<String>) -> Self {
+        Self {
+            ignored_mount_points,
+            ..self
+        }
+    }
+
+    /// How long to wait for the usage of a filesystem before skipping it. Defaults to 5 seconds.
+    pub fn statvfs_timeout(self, statvfs_timeout: Duration) -> Self {
+        Self {
+            statvfs_timeout,
+            ..self
+        }
+    }
+
+    fn is_handled(&self, mount: &Mount) -> bool {
+        if self.ignored_fs_types.contains(&mount.fs_type) {
+            return false;
+        }
+
+        if self
+            .ignored_mount_points
+            .iter()
+            .any(|prefix| mount.mount_point.starts_with(prefix))
+        {
+            return false;
+        }
+
+        self.fs_types.is_empty() || self.fs_types.contains(&mount.fs_type)
+    }
+
+    async fn mounts(&self) -> eyre::Result<Vec<Mount>> {
+        let partitions = heim_disk::partitions()
+            .await
+            .wrap_err("Unexpected error when loading mounted filesystems")?;
+
+        let mut partitions = Box::pin(partitions);
+        let mut mounts = Vec::new();
+
+        while let Some(item) = partitions.next().await {
+            if let Ok(partition) = item {
+                let mount = Mount {
+                    mount_point: partition.mount_point().to_path_buf(),
+                    device: partition
+                        .device()
+                        .map(|d| d.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    fs_type: partition.file_system().as_str().to_string(),
+                };
+
+                if self.is_handled(&mount) {
+                    mounts.push(mount);
+                }
+            }
+        }
+
+        Ok(mounts)
+    }
+}
+
+#[async_trait::async_trait]
+impl PollingSource for Filesystems {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+
+        self.pending.retain(|_, handle| !handle.is_finished());
+
+        for mount in self.mounts().await? {
+            if self.pending.contains_key(&mount.mount_point) {
+                tracing::debug!(
+                    "Skipping {:?}, the previous statvfs call is still running",
+                    mount.mount_point
+                );
+                continue;
+            }
+
+            let mount_point = mount.mount_point.clone();
+
+            // statvfs can hang on unresponsive network filesystems.
+            let mut handle = tokio::task::spawn_blocking(move || statvfs(mount_point));
+
+            let stats = match tokio::time::timeout(self.statvfs_timeout, &mut handle).await {
+                Ok(result) => result.wrap_err("statvfs task panicked")?,
+                Err(_) => {
+                    tracing::warn!(
+                        "statvfs on {:?} didn't complete within {:?}",
+                        mount.mount_point,
+                        self.statvfs_timeout
+                    );
+
+                    self.pending.insert(mount.mount_point.clone(), handle);
+                    continue;
+                }
+            };
+
+            let stats = match stats {
+                Ok(stats) => stats,
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    continue;
+                }
+            };
+
+            let mount_point = mount.mount_point.to_string_lossy();
+
+            metrics.extend(
+                vec![
+                    MetricBuilder::gauge("host", "filesystem_total_bytes", stats.total),
+                    MetricBuilder::gauge("host", "filesystem_used_bytes", stats.total - stats.free),
+                    MetricBuilder::gauge("host", "filesystem_free_bytes", stats.free),
+                    MetricBuilder::gauge("host", "filesystem_available_bytes", stats.available),
+                    MetricBuilder::gauge("host", "filesystem_inodes_total", stats.inodes_total),
+                    MetricBuilder::gauge(
+                        "host",
+                        "filesystem_inodes_used",
+                        stats.inodes_total - stats.inodes_free,
+                    ),
+                    MetricBuilder::gauge("host", "filesystem_inodes_free", stats.inodes_free),
+                ]
+                .into_iter()
+                .map(|m| {
+                    m.add_tag("mountpoint", mount_point.as_ref())
+                        .add_tag("device", mount.device.as_str())
+                        .add_tag("fs_type", mount.fs_type.as_str())
+                        .build()
+                }),
+            );
+        }
+
+        Ok(metrics)
+    }
+}
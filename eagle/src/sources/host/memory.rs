@@ -1,50 +1,142 @@
-use eagle_core::{EagleClient, MetricBuilder, Source};
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
 use eyre::WrapErr;
 use heim::units::information::byte;
-use tokio::time::Duration;
 
 pub struct Memory;
 
+#[cfg(target_os = "linux")]
+fn used_bytes(memory: &heim::memory::Memory) -> f64 {
+    use heim::memory::os::linux::MemoryExt;
+
+    memory.used().get::<byte>() as f64
+}
+
+#[cfg(not(target_os = "linux"))]
+fn used_bytes(memory: &heim::memory::Memory) -> f64 {
+    (memory.total() - memory.available()).get::<byte>() as f64
+}
+
+/// heim doesn't expose slab memory, so we read it from `/proc/meminfo` directly.
+#[cfg(target_os = "linux")]
+async fn slab_bytes() -> Option<f64> {
+    let meminfo = tokio::fs::read_to_string("/proc/meminfo").await.ok()?;
+    let kilobytes = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("Slab:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<f64>()
+        .ok()?;
+
+    Some(kilobytes * 1_024f64)
+}
+
+#[cfg(target_os = "linux")]
+async fn linux_metrics(memory: &heim::memory::Memory) -> Vec<Metric> {
+    use heim::memory::os::linux::MemoryExt;
+
+    let mut metrics = vec![
+        MetricBuilder::gauge(
+            "host",
+            "memory_buffers_bytes",
+            memory.buffers().get::<byte>() as f64,
+        )
+        .build(),
+        MetricBuilder::gauge(
+            "host",
+            "memory_cached_bytes",
+            memory.cached().get::<byte>() as f64,
+        )
+        .build(),
+        MetricBuilder::gauge(
+            "host",
+            "memory_shared_bytes",
+            memory.shared().get::<byte>() as f64,
+        )
+        .build(),
+    ];
+
+    if let Some(slab) = slab_bytes().await {
+        metrics.push(MetricBuilder::gauge("host", "memory_slab_bytes", slab).build());
+    }
+
+    metrics
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn linux_metrics(_memory: &heim::memory::Memory) -> Vec<Metric> {
+    Vec::new()
+}
+
 #[async_trait::async_trait]
-impl Source for Memory {
-    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
-        let mut clock = tokio::time::interval(Duration::from_secs(3));
-
-        loop {
-            let memory = heim::memory::memory()
-                .await
-                .wrap_err("Failed to load memory info")?;
-
-            client
-                .send_metrics(vec![
-                    MetricBuilder::gauge(
-                        "host",
-                        "memory_total_bytes",
-                        memory.total().get::<byte>() as f64,
-                    )
-                    .build(),
-                    MetricBuilder::gauge(
-                        "host",
-                        "memory_free_bytes",
-                        memory.total().get::<byte>() as f64,
-                    )
-                    .build(),
-                    MetricBuilder::gauge(
-                        "host",
-                        "memory_available_bytes",
-                        memory.total().get::<byte>() as f64,
-                    )
-                    .build(),
-                    MetricBuilder::gauge(
+impl PollingSource for Memory {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let memory = heim::memory::memory()
+            .await
+            .wrap_err("Failed to load memory info")?;
+
+        let swap = heim::memory::swap()
+            .await
+            .wrap_err("Failed to load swap info")?;
+
+        let total = memory.total().get::<byte>() as f64;
+        let used = used_bytes(&memory);
+        let used_ratio = if total > 0f64 { used / total } else { 0f64 };
+
+        let mut metrics = vec![
+            MetricBuilder::gauge("host", "memory_total_bytes", total).build(),
+            MetricBuilder::gauge(
+                "host",
+                "memory_free_bytes",
+                memory.free().get::<byte>() as f64,
+            )
+            .build(),
+            MetricBuilder::gauge(
+                "host",
+                "memory_available_bytes",
+                memory.available().get::<byte>() as f64,
+            )
+            .build(),
+            MetricBuilder::gauge("host", "memory_used_bytes", used).build(),
+            MetricBuilder::gauge("host", "memory_used_ratio", used_ratio).build(),
+            MetricBuilder::gauge(
+                "host",
+                "swap_total_bytes",
+                swap.total().get::<byte>() as f64,
+            )
+            .build(),
+            MetricBuilder::gauge("host", "swap_used_bytes", swap.used().get::<byte>() as f64)
+                .build(),
+            MetricBuilder::gauge("host", "swap_free_bytes", swap.free().get::<byte>() as f64)
+                .build(),
+        ];
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            use heim::memory::os::SwapExt;
+
+            if let Some(sin) = swap.sin() {
+                metrics.push(
+                    MetricBuilder::counter("host", "swap_in_bytes_total", sin.get::<byte>() as f64)
+                        .build(),
+                );
+            }
+
+            if let Some(sout) = swap.sout() {
+                metrics.push(
+                    MetricBuilder::counter(
                         "host",
-                        "memory_used_bytes",
-                        memory.total().get::<byte>() as f64,
+                        "swap_out_bytes_total",
+                        sout.get::<byte>() as f64,
                     )
                     .build(),
-                ])
-                .await?;
-
-            clock.tick().await;
+                );
+            }
         }
+
+        metrics.extend(linux_metrics(&memory).await);
+
+        Ok(metrics)
     }
 }
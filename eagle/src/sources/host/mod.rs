@@ -1,7 +1,19 @@
+mod cpu;
 mod disks;
+mod filesystems;
 mod load;
 mod memory;
+mod network;
+mod pressure;
+mod sockets;
+mod vmstat;
 
+pub use cpu::Cpu;
 pub use disks::Disks;
+pub use filesystems::Filesystems;
 pub use load::Load;
 pub use memory::Memory;
+pub use network::Network;
+pub use pressure::Pressure;
+pub use sockets::Sockets;
+pub use vmstat::VmStat;
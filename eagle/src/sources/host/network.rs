@@ -0,0 +1,155 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+use eyre::WrapErr;
+use futures::{FutureExt, StreamExt};
+use heim::units::information::byte;
+
+/// How long the list of loopback interfaces is reused before being loaded again.
+const LOOPBACK_TTL: Duration = Duration::from_secs(300);
+
+pub struct Network {
+    includes: Vec<String>,
+    excludes: Vec<String>,
+    include_loopback: bool,
+    loopbacks: Option<(Instant, HashSet<String>)>,
+}
+
+impl Network {
+    /// When `includes` is empty, every interface not excluded is reported.
+    pub fn new(includes: Vec<String>, excludes: Vec<String>) -> Self {
+        Self {
+            includes,
+            excludes,
+            include_loopback: false,
+            loopbacks: None,
+        }
+    }
+
+    pub fn include_loopback(self, include_loopback: bool) -> Self {
+        Self {
+            include_loopback,
+            ..self
+        }
+    }
+
+    fn is_handled(&self, interface: &str, loopbacks: &HashSet<String>) -> bool {
+        if !self.include_loopback && loopbacks.contains(interface) {
+            return false;
+        }
+
+        if self.excludes.iter().any(|i| i.as_str() == interface) {
+            return false;
+        }
+
+        self.includes.is_empty() || self.includes.iter().any(|i| i.as_str() == interface)
+    }
+}
+
+async fn list_loopback_interfaces() -> eyre::Result<HashSet<String>> {
+    let nics = heim_net::nic()
+        .await
+        .wrap_err("Unexpected error when loading network interfaces")?;
+
+    // heim's interface stream isn't `Send`, so it can't be held across an await point of a
+    // source future. It's an iterator over `getifaddrs` though, so it's always ready.
+    let mut nics = Box::pin(nics);
+    let mut loopbacks = HashSet::new();
+
+    while let Some(Some(item)) = nics.next().now_or_never() {
+        if let Ok(nic) = item {
+            if nic.is_loopback() {
+                loopbacks.insert(nic.name().to_string());
+            }
+        }
+    }
+
+    Ok(loopbacks)
+}
+
+#[async_trait::async_trait]
+impl PollingSource for Network {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let (loaded, loopbacks) = match self.loopbacks.take() {
+            Some((loaded, loopbacks)) if loaded.elapsed() < LOOPBACK_TTL => (loaded, loopbacks),
+            _ => (Instant::now(), list_loopback_interfaces().await?),
+        };
+        let counters = heim_net::io_counters()
+            .await
+            .wrap_err("Unexpected error when loading network info")?;
+
+        let mut counters = Box::pin(counters);
+        let mut result = Vec::new();
+
+        while let Some(item) = counters.next().await {
+            if let Ok(counter) = item {
+                if !self.is_handled(counter.interface(), &loopbacks) {
+                    continue;
+                }
+
+                let interface = counter.interface();
+                let mut metrics = vec![
+                    MetricBuilder::counter(
+                        "host",
+                        "network_sent_bytes_total",
+                        counter.bytes_sent().get::<byte>() as f64,
+                    ),
+                    MetricBuilder::counter(
+                        "host",
+                        "network_received_bytes_total",
+                        counter.bytes_recv().get::<byte>() as f64,
+                    ),
+                    MetricBuilder::counter(
+                        "host",
+                        "network_sent_packets_total",
+                        counter.packets_sent() as f64,
+                    ),
+                    MetricBuilder::counter(
+                        "host",
+                        "network_received_packets_total",
+                        counter.packets_recv() as f64,
+                    ),
+                    MetricBuilder::counter(
+                        "host",
+                        "network_sent_errors_total",
+                        counter.errors_sent() as f64,
+                    ),
+                    MetricBuilder::counter(
+                        "host",
+                        "network_received_errors_total",
+                        counter.errors_recv() as f64,
+                    ),
+                    MetricBuilder::counter(
+                        "host",
+                        "network_received_drops_total",
+                        counter.drop_recv() as f64,
+                    ),
+                ];
+
+                #[cfg(target_os = "linux")]
+                {
+                    use heim_net::os::linux::IoCountersExt;
+
+                    metrics.push(MetricBuilder::counter(
+                        "host",
+                        "network_sent_drops_total",
+                        counter.drop_sent() as f64,
+                    ));
+                }
+
+                result.extend(
+                    metrics
+                        .into_iter()
+                        .map(|m| m.add_tag("interface", interface).build()),
+                );
+            }
+        }
+
+        self.loopbacks = Some((loaded, loopbacks));
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,66 @@
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+
+const RESOURCES: &[&str] = &["cpu", "memory", "io"];
+
+/// Pressure stall information, only available on Linux 4.20+ kernels.
+pub struct Pressure;
+
+/// Each line looks like `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`.
+fn parse_pressure(resource: &str, content: &str) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let kind = match fields.next() {
+            Some(kind @ ("some" | "full")) => kind,
+            _ => continue,
+        };
+
+        for field in fields {
+            let (key, value) = match field.split_once('=') {
+                Some((key, value)) => match value.parse::<f64>() {
+                    Ok(value) => (key, value),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+
+            let metric = match key {
+                "avg10" | "avg60" | "avg300" => {
+                    MetricBuilder::gauge("host", format!("pressure_{}_{}", kind, key), value)
+                }
+
+                // Total stall time is reported in microseconds.
+                "total" => MetricBuilder::counter(
+                    "host",
+                    format!("pressure_{}_stall_seconds_total", kind),
+                    value / 1_000_000f64,
+                ),
+
+                _ => continue,
+            };
+
+            metrics.push(metric.add_tag("resource", resource).build());
+        }
+    }
+
+    metrics
+}
+
+#[async_trait::async_trait]
+impl PollingSource for Pressure {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+
+        for resource in RESOURCES {
+            // A resource can be missing, e.g. when PSI is disabled at boot time.
+            if let Ok(content) =
+                tokio::fs::read_to_string(format!("/proc/pressure/{}", resource)).await
+            {
+                metrics.extend(parse_pressure(resource, content.as_str()));
+            }
+        }
+
+        Ok(metrics)
+    }
+}
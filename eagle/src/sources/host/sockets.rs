@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, HashSet};
+
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+use eyre::WrapErr;
+
+/// Socket tables, the protocol name being used as the `protocol` tag value.
+const SOCKET_TABLES: &[(&str, &str)] = &[
+    ("tcp", "/proc/net/tcp"),
+    ("tcp6", "/proc/net/tcp6"),
+    ("udp", "/proc/net/udp"),
+    ("udp6", "/proc/net/udp6"),
+];
+
+/// Connection states as encoded by the kernel in `include/net/tcp_states.h`.
+const TCP_STATES: &[(u8, &str)] = &[
+    (0x01, "ESTABLISHED"),
+    (0x02, "SYN_SENT"),
+    (0x03, "SYN_RECV"),
+    (0x04, "FIN_WAIT1"),
+    (0x05, "FIN_WAIT2"),
+    (0x06, "TIME_WAIT"),
+    (0x07, "CLOSE"),
+    (0x08, "CLOSE_WAIT"),
+    (0x09, "LAST_ACK"),
+    (0x0A, "LISTEN"),
+    (0x0B, "CLOSING"),
+    (0x0C, "NEW_SYN_RECV"),
+];
+
+/// Selected `/proc/net/snmp` and `/proc/net/netstat` counters, as (section, field, metric name).
+const PROTOCOL_COUNTERS: &[(&str, &str, &str)] = &[
+    ("Tcp", "ActiveOpens", "tcp_active_opens_total"),
+    ("Tcp", "PassiveOpens", "tcp_passive_opens_total"),
+    ("Tcp", "AttemptFails", "tcp_attempt_fails_total"),
+    ("Tcp", "EstabResets", "tcp_established_resets_total"),
+    ("Tcp", "RetransSegs", "tcp_retransmitted_segments_total"),
+    ("Tcp", "InErrs", "tcp_received_errors_total"),
+    ("Tcp", "OutRsts", "tcp_sent_resets_total"),
+    ("TcpExt", "ListenOverflows", "tcp_listen_overflows_total"),
+    ("TcpExt", "ListenDrops", "tcp_listen_drops_total"),
+    ("TcpExt", "TCPTimeouts", "tcp_timeouts_total"),
+    ("TcpExt", "SyncookiesSent", "tcp_syncookies_sent_total"),
+    ("Udp", "InErrors", "udp_received_errors_total"),
+    ("Udp", "NoPorts", "udp_no_ports_total"),
+    ("Udp", "RcvbufErrors", "udp_receive_buffer_errors_total"),
+    ("Udp", "SndbufErrors", "udp_send_buffer_errors_total"),
+];
+
+/// Reports socket counts per state and TCP/UDP protocol counters.
+#[derive(Default)]
+pub struct Sockets {
+    per_port: bool,
+    ports: HashSet<u16>,
+}
+
+impl Sockets {
+    /// Also reports socket counts per local port.
+    pub fn per_port(self, per_port: bool) -> Self {
+        Self { per_port, ..self }
+    }
+
+    /// Restricts the per port breakdown to those local ports. Every port is reported if empty.
+    pub fn ports(self, ports: Vec<u16>) -> Self {
+        Self {
+            ports: ports.into_iter().collect(),
+            ..self
+        }
+    }
+
+    fn is_port_reported(&self, port: u16) -> bool {
+        self.per_port && (self.ports.is_empty() || self.ports.contains(&port))
+    }
+}
+
+fn state_name(state: u8) -> &'static str {
+    TCP_STATES
+        .iter()
+        .find(|(code, _)| *code == state)
+        .map(|(_, name)| *name)
+        .unwrap_or("UNKNOWN")
+}
+
+/// Returns the (local port, state) of every socket of a `/proc/net/{tcp,udp}*` table.
+///
+/// Each line looks like `0: 0100007F:BC8F 00000000:0000 0A ...`.
+fn parse_socket_table(content: &str) -> Vec<(u16, u8)> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let local_address = fields.next()?;
+            let state = fields.nth(1)?;
+            let (_, port) = local_address.rsplit_once(':')?;
+
+            Some((
+                u16::from_str_radix(port, 16).ok()?,
+                u8::from_str_radix(state, 16).ok()?,
+            ))
+        })
+        .collect()
+}
+
+/// Parses `/proc/net/snmp` like files, where each section is a header line followed by a
+/// values line, both prefixed by the section name.
+fn parse_protocol_counters(content: &str) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    let mut lines = content.lines();
+
+    while let (Some(header), Some(values)) = (lines.next(), lines.next()) {
+        let (section, header) = match header.split_once(':') {
+            Some(split) => split,
+            None => continue,
+        };
+
+        let values = values.split_once(':').map(|(_, v)| v).unwrap_or_default();
+
+        for (field, value) in header.split_whitespace().zip(values.split_whitespace()) {
+            let name = PROTOCOL_COUNTERS
+                .iter()
+                .find(|(s, f, _)| *s == section && *f == field)
+                .map(|(_, _, name)| *name);
+
+            if let (Some(name), Ok(value)) = (name, value.parse::<f64>()) {
+                metrics.push(MetricBuilder::counter("host", name, value).build());
+            }
+        }
+    }
+
+    metrics
+}
+
+#[async_trait::async_trait]
+impl PollingSource for Sockets {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+
+        for (protocol, path) in SOCKET_TABLES {
+            // IPv6 tables are missing when IPv6 is disabled.
+            let content = match tokio::fs::read_to_string(path).await {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            let mut states = BTreeMap::<&str, u64>::new();
+            let mut ports = BTreeMap::<(u16, &str), u64>::new();
+
+            if protocol.starts_with("tcp") {
+                // Reports every state so each series is always present.
+                for (_, name) in TCP_STATES {
+                    states.insert(name, 0);
+                }
+            }
+
+            for (port, state) in parse_socket_table(content.as_str()) {
+                let state = state_name(state);
+
+                *states.entry(state).or_default() += 1;
+
+                if self.is_port_reported(port) {
+                    *ports.entry((port, state)).or_default() += 1;
+                }
+            }
+
+            for (state, count) in states {
+                metrics.push(
+                    MetricBuilder::gauge("host", "socket_connections", count as f64)
+                        .add_tag("protocol", *protocol)
+                        .add_tag("state", state)
+                        .build(),
+                );
+            }
+
+            for ((port, state), count) in ports {
+                metrics.push(
+                    MetricBuilder::gauge("host", "socket_port_connections", count as f64)
+                        .add_tag("protocol", *protocol)
+                        .add_tag("state", state)
+                        .add_tag("local_port", port.to_string())
+                        .build(),
+                );
+            }
+        }
+
+        let snmp = tokio::fs::read_to_string("/proc/net/snmp")
+            .await
+            .wrap_err("Failed to read /proc/net/snmp")?;
+
+        metrics.extend(parse_protocol_counters(snmp.as_str()));
+
+        if let Ok(netstat) = tokio::fs::read_to_string("/proc/net/netstat").await {
+            metrics.extend(parse_protocol_counters(netstat.as_str()));
+        }
+
+        Ok(metrics)
+    }
+}
@@ -0,0 +1,70 @@
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+use eyre::WrapErr;
+
+/// Selected `/proc/vmstat` counters.
+const VMSTAT_COUNTERS: &[(&str, &str)] = &[
+    ("pgfault", "page_faults_total"),
+    ("pgmajfault", "page_major_faults_total"),
+    ("pgpgin", "paged_in_kilobytes_total"),
+    ("pgpgout", "paged_out_kilobytes_total"),
+    ("pswpin", "swapped_in_pages_total"),
+    ("pswpout", "swapped_out_pages_total"),
+    ("oom_kill", "oom_kills_total"),
+];
+
+/// Kernel activity counters from `/proc/vmstat` and `/proc/stat`.
+pub struct VmStat;
+
+fn parse_vmstat(content: &str) -> Vec<Metric> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            let (_, name) = VMSTAT_COUNTERS.iter().find(|(k, _)| *k == key)?;
+            let value = value.trim().parse::<f64>().ok()?;
+
+            Some(MetricBuilder::counter("host", *name, value).build())
+        })
+        .collect()
+}
+
+fn parse_stat(content: &str) -> Vec<Metric> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let key = fields.next()?;
+            // For `intr`, the first value is the total of all interrupts.
+            let value = fields.next()?.parse::<f64>().ok()?;
+
+            let metric = match key {
+                "ctxt" => MetricBuilder::counter("host", "context_switches_total", value),
+                "intr" => MetricBuilder::counter("host", "interrupts_total", value),
+                "processes" => MetricBuilder::counter("host", "forks_total", value),
+                "procs_running" => MetricBuilder::gauge("host", "procs_running", value),
+                "procs_blocked" => MetricBuilder::gauge("host", "procs_blocked", value),
+                _ => return None,
+            };
+
+            Some(metric.build())
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl PollingSource for VmStat {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let vmstat = tokio::fs::read_to_string("/proc/vmstat")
+            .await
+            .wrap_err("Failed to read /proc/vmstat")?;
+
+        let stat = tokio::fs::read_to_string("/proc/stat")
+            .await
+            .wrap_err("Failed to read /proc/stat")?;
+
+        let mut metrics = parse_vmstat(vmstat.as_str());
+        metrics.extend(parse_stat(stat.as_str()));
+
+        Ok(metrics)
+    }
+}
@@ -0,0 +1,443 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use eagle_core::{EagleClient, Source};
+use eyre::{eyre, WrapErr};
+use hyper::{
+    header::CONTENT_TYPE,
+    server::conn::AddrStream,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    formats::json,
+    http::{is_authorized, read_body, response},
+};
+
+struct Settings {
+    token: Option<String>,
+    max_body_size: usize,
+    category: String,
+}
+
+/// HTTP server receiving pushed JSON logs on `POST /logs` and JSON metrics on `POST /metrics`.
+///
+/// Bodies can hold a single JSON value, an array of them, or one per line (NDJSON). Metrics
+/// follow the `formats::json` schema.
+pub struct HttpReceiver {
+    address: String,
+    token: Option<String>,
+    max_body_size: usize,
+    category: String,
+}
+
+impl HttpReceiver {
+    pub fn new(address: impl AsRef<str>) -> Self {
+        Self {
+            address: address.as_ref().to_string(),
+            token: None,
+            max_body_size: 10 * 1_024 * 1_024,
+            category: "http".to_string(),
+        }
+    }
+
+    /// Requests must then carry `Authorization: Bearer <token>`.
+    pub fn bearer_token(self, token: impl AsRef<str>) -> Self {
+        Self {
+            token: Some(token.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    /// Applies to the body both as sent and once decompressed.
+    pub fn max_body_size(self, max_body_size: usize) -> Self {
+        Self {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Category of pushed metrics that don't carry one.
+    pub fn category(self, category: impl AsRef<str>) -> Self {
+        Self {
+            category: category.as_ref().to_string(),
+            ..self
+        }
+    }
+}
+
+/// Parses a body holding either a JSON value, with arrays being flattened, or NDJSON.
+fn parse_values(body: &[u8], is_ndjson: bool) -> eyre::Result<Vec<Value>> {
+    if !is_ndjson {
+        if let Ok(value) = serde_json::from_slice::<Value>(body) {
+            return Ok(match value {
+                Value::Array(values) => values,
+                value => vec![value],
+            });
+        }
+    }
+
+    let body = std::str::from_utf8(body).wrap_err("Body is not valid UTF-8")?;
+    let mut values = Vec::new();
+
+    for (index, line) in body.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value = serde_json::from_str::<Value>(line)
+            .wrap_err_with(|| format!("Invalid JSON at line {}", index + 1))?;
+
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+async fn handle(
+    request: Request<Body>,
+    peer: SocketAddr,
+    settings: &Settings,
+    client: &EagleClient,
+) -> eyre::Result<Response<Body>> {
+    if !is_authorized(&request, settings.token.as_deref()) {
+        return Ok(response(StatusCode::UNAUTHORIZED, "Invalid bearer token"));
+    }
+
+    let is_logs = match (request.method(), request.uri().path()) {
+        (&Method::POST, "/logs") => true,
+        (&Method::POST, "/metrics") => false,
+        (_, "/logs") | (_, "/metrics") => {
+            return Ok(response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Only POST is allowed",
+            ))
+        }
+        _ => return Ok(response(StatusCode::NOT_FOUND, "Unknown endpoint")),
+    };
+
+    let is_ndjson = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.starts_with("application/x-ndjson") || value.starts_with("application/jsonl")
+        });
+
+    let body = match read_body(request, settings.max_body_size).await {
+        Ok(body) => body,
+        Err(response) => return Ok(response),
+    };
+
+    let values = match parse_values(&body, is_ndjson) {
+        Ok(values) => values,
+        Err(e) => return Ok(response(StatusCode::BAD_REQUEST, format!("{:#}", e))),
+    };
+
+    if is_logs {
+        let peer = peer.to_string();
+
+        for value in values {
+            client
+                .send_log_with_metadata(value, json!({ "peer": peer }))
+                .await?;
+        }
+
+        return Ok(response(StatusCode::NO_CONTENT, ""));
+    }
+
+    let mut metrics = Vec::new();
+
+    // Metrics are only sent once the whole body is known to be valid.
+    for value in values {
+        match json::parse_value(settings.category.as_str(), value) {
+            Ok(parsed) => metrics.extend(parsed),
+            Err(e) => return Ok(response(StatusCode::BAD_REQUEST, format!("{:#}", e))),
+        }
+    }
+
+    if !metrics.is_empty() {
+        client.send_metrics(metrics).await?;
+    }
+
+    Ok(response(StatusCode::NO_CONTENT, ""))
+}
+
+#[async_trait::async_trait]
+impl Source for HttpReceiver {
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        let address = self
+            .address
+            .parse::<SocketAddr>()
+            .wrap_err_with(|| format!("Invalid address '{}'", self.address))?;
+
+        let settings = Arc::new(Settings {
+            token: self.token.clone(),
+            max_body_size: self.max_body_size,
+            category: self.category.clone(),
+        });
+
+        let make_service = make_service_fn(move |connection: &AddrStream| {
+            let peer = connection.remote_addr();
+            let settings = settings.clone();
+            let client = client.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let settings = settings.clone();
+                    let client = client.clone();
+
+                    async move {
+                        let result = handle(request, peer, &settings, &client).await;
+
+                        Ok::<_, Infallible>(result.unwrap_or_else(|e| {
+                            response(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))
+                        }))
+                    }
+                }))
+            }
+        });
+
+        Server::try_bind(&address)
+            .map_err(|e| eyre!("Error when binding HTTP server on {}: {}", address, e))?
+            .serve(make_service)
+            .await
+            .wrap_err("HTTP server failed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eagle_core::{poll::Schedule, EagleEndpoint, EagleEvent, Event, Origin};
+    use flate2::{write::GzEncoder, Compression};
+    use hyper::header::{AUTHORIZATION, CONTENT_ENCODING};
+    use std::io::Write;
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    fn client() -> (EagleClient, UnboundedReceiver<EagleEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let client = EagleClient {
+            origin: Arc::new(Origin::new("http")),
+            endpoint: EagleEndpoint::new(sender),
+            schedule: Schedule::default(),
+        };
+
+        (client, receiver)
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            token: Some("secret".to_string()),
+            max_body_size: 64,
+            category: "http".to_string(),
+        }
+    }
+
+    fn request(method: Method, path: &str, body: impl Into<Body>) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header(AUTHORIZATION, "Bearer secret")
+            .body(body.into())
+            .unwrap()
+    }
+
+    fn gzip(input: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(input).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn status(request: Request<Body>, client: &EagleClient) -> StatusCode {
+        let peer = SocketAddr::from(([127, 0, 0, 1], 4000));
+
+        handle(request, peer, &settings(), client)
+            .await
+            .unwrap()
+            .status()
+    }
+
+    fn events(receiver: &mut UnboundedReceiver<EagleEvent>) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event.event);
+        }
+
+        events
+    }
+
+    #[test]
+    fn parses_values() {
+        let cases: &[(&str, bool, Vec<Value>)] = &[
+            (r#"{"a": 1}"#, false, vec![json!({"a": 1})]),
+            (
+                r#"[{"a": 1}, {"a": 2}]"#,
+                false,
+                vec![json!({"a": 1}), json!({"a": 2})],
+            ),
+            (
+                "{\"a\": 1}\n\n{\"a\": 2}\n  \n",
+                false,
+                vec![json!({"a": 1}), json!({"a": 2})],
+            ),
+            (
+                "{\"a\": 1}\n\n{\"a\": 2}\n",
+                true,
+                vec![json!({"a": 1}), json!({"a": 2})],
+            ),
+            ("", true, vec![]),
+        ];
+
+        for (body, is_ndjson, expected) in cases {
+            let values = parse_values(body.as_bytes(), *is_ndjson).unwrap();
+
+            assert_eq!(&values, expected, "{:?}", body);
+        }
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        let error = parse_values(b"{\"a\": 1}\n\n{\"a\": \n", true).unwrap_err();
+
+        assert!(
+            format!("{:#}", error).starts_with("Invalid JSON at line 3"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_bearer_tokens() {
+        let (client, mut receiver) = client();
+
+        let mut anonymous = request(Method::POST, "/logs", r#"{"a": 1}"#);
+        anonymous.headers_mut().remove(AUTHORIZATION);
+
+        let mut wrong = request(Method::POST, "/logs", r#"{"a": 1}"#);
+        wrong
+            .headers_mut()
+            .insert(AUTHORIZATION, "Bearer other".parse().unwrap());
+
+        assert_eq!(status(anonymous, &client).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(wrong, &client).await, StatusCode::UNAUTHORIZED);
+        assert!(events(&mut receiver).is_empty());
+    }
+
+    #[tokio::test]
+    async fn routes_requests() {
+        let (client, _receiver) = client();
+        let cases = [
+            (Method::POST, "/other", StatusCode::NOT_FOUND),
+            (Method::GET, "/logs", StatusCode::METHOD_NOT_ALLOWED),
+            (Method::PUT, "/metrics", StatusCode::METHOD_NOT_ALLOWED),
+            (Method::POST, "/logs", StatusCode::NO_CONTENT),
+        ];
+
+        for (method, path, expected) in cases {
+            let request = request(method.clone(), path, "{}");
+
+            assert_eq!(
+                status(request, &client).await,
+                expected,
+                "{} {}",
+                method,
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn receives_logs() {
+        let (client, mut receiver) = client();
+        let mut request = request(Method::POST, "/logs", gzip(br#"[{"a": 1}, {"a": 2}]"#));
+        request
+            .headers_mut()
+            .insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+
+        assert_eq!(status(request, &client).await, StatusCode::NO_CONTENT);
+
+        let logs = events(&mut receiver)
+            .into_iter()
+            .map(|event| match event {
+                Event::Log(log) => (log.inner.as_ref().clone(), log.metadata),
+                other => panic!("Unexpected event {:?}", other),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            logs,
+            vec![
+                (json!({"a": 1}), json!({"peer": "127.0.0.1:4000"})),
+                (json!({"a": 2}), json!({"peer": "127.0.0.1:4000"})),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_large_bodies() {
+        let (client, mut receiver) = client();
+        let padding = " ".repeat(64);
+
+        let raw = request(Method::POST, "/logs", format!("{{}}{}", padding));
+
+        // Small once compressed, but too large once decompressed.
+        let mut decompressed = request(
+            Method::POST,
+            "/logs",
+            gzip(format!("{{}}{}", padding).as_bytes()),
+        );
+        decompressed
+            .headers_mut()
+            .insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+
+        assert_eq!(status(raw, &client).await, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            status(decompressed, &client).await,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert!(events(&mut receiver).is_empty());
+    }
+
+    #[tokio::test]
+    async fn receives_metrics() {
+        let (client, mut receiver) = client();
+        let body = "{\"name\": \"up\", \"value\": 1}\n{\"name\": \"hits\", \"value\": 2}\n";
+        let mut request = request(Method::POST, "/metrics", body);
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, "application/x-ndjson".parse().unwrap());
+
+        assert_eq!(status(request, &client).await, StatusCode::NO_CONTENT);
+
+        let metrics = events(&mut receiver)
+            .into_iter()
+            .map(|event| match event {
+                Event::Metric(metric) => (metric.category, metric.name, metric.value),
+                other => panic!("Unexpected event {:?}", other),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            metrics,
+            vec![
+                ("http".to_string(), "up".to_string(), 1f64),
+                ("http".to_string(), "hits".to_string(), 2f64),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_batches_with_an_invalid_metric() {
+        let (client, mut receiver) = client();
+        let body = r#"[{"name": "up", "value": 1}, {"name": "hits"}]"#;
+
+        assert_eq!(
+            status(request(Method::POST, "/metrics", body), &client).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert!(events(&mut receiver).is_empty());
+    }
+}
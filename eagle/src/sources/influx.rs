@@ -0,0 +1,283 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use eagle_core::{EagleClient, Source};
+use eyre::{eyre, WrapErr};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    formats::influx::{self, Precision},
+    http::{is_authorized, read_body, response},
+    lines,
+};
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Udp,
+    Tcp,
+    Http,
+}
+
+struct Settings {
+    precision: Precision,
+    token: Option<String>,
+    max_body_size: usize,
+}
+
+/// Receives InfluxDB line protocol over UDP, TCP or HTTP. Each field becomes a gauge named after
+/// it, within the measurement as category.
+///
+/// The HTTP server mimics InfluxDB with `/write`, `/api/v2/write` and `/ping`, so the precision
+/// is taken from the `precision` query parameter there.
+pub struct InfluxReceiver {
+    address: String,
+    protocol: Protocol,
+    precision: Precision,
+    token: Option<String>,
+    max_body_size: usize,
+}
+
+impl InfluxReceiver {
+    pub fn new(address: impl AsRef<str>, protocol: Protocol) -> Self {
+        Self {
+            address: address.as_ref().to_string(),
+            protocol,
+            precision: Precision::default(),
+            token: None,
+            max_body_size: 10 * 1_024 * 1_024,
+        }
+    }
+
+    /// Timestamp precision of UDP and TCP lines, and of HTTP writes not specifying one.
+    pub fn precision(self, precision: Precision) -> Self {
+        Self { precision, ..self }
+    }
+
+    /// Only applies to HTTP, where requests must then carry `Authorization: Token <token>`.
+    pub fn token(self, token: impl AsRef<str>) -> Self {
+        Self {
+            token: Some(token.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    /// Largest HTTP body, UDP datagram or TCP line accepted.
+    pub fn max_body_size(self, max_body_size: usize) -> Self {
+        Self {
+            max_body_size,
+            ..self
+        }
+    }
+
+    fn settings(&self) -> Settings {
+        Settings {
+            precision: self.precision,
+            token: self.token.clone(),
+            max_body_size: self.max_body_size,
+        }
+    }
+
+    async fn listen_udp(&self, client: EagleClient) -> eyre::Result<()> {
+        let precision = self.precision;
+
+        lines::listen_udp(&self.address, &client, self.max_body_size, move |line| {
+            influx::parse_line(line, precision)
+        })
+        .await
+    }
+
+    async fn listen_tcp(&self, client: EagleClient) -> eyre::Result<()> {
+        let precision = self.precision;
+
+        lines::listen_tcp(&self.address, &client, self.max_body_size, move |line| {
+            influx::parse_line(line, precision)
+        })
+        .await
+    }
+
+    async fn listen_http(&self, client: EagleClient) -> eyre::Result<()> {
+        let address = self
+            .address
+            .parse::<SocketAddr>()
+            .wrap_err_with(|| format!("Invalid address '{}'", self.address))?;
+
+        let settings = Arc::new(self.settings());
+        let make_service = make_service_fn(move |_| {
+            let settings = settings.clone();
+            let client = client.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let settings = settings.clone();
+                    let client = client.clone();
+
+                    async move {
+                        let result = handle_request(request, &settings, &client).await;
+
+                        Ok::<_, Infallible>(result.unwrap_or_else(|e| {
+                            response(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e))
+                        }))
+                    }
+                }))
+            }
+        });
+
+        Server::try_bind(&address)
+            .map_err(|e| eyre!("Error when binding HTTP server on {}: {}", address, e))?
+            .serve(make_service)
+            .await
+            .wrap_err("HTTP server failed")
+    }
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    settings: &Settings,
+    client: &EagleClient,
+) -> eyre::Result<Response<Body>> {
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/ping") | (&Method::HEAD, "/ping") => {
+            return Ok(response(StatusCode::NO_CONTENT, ""))
+        }
+        (&Method::POST, "/write") | (&Method::POST, "/api/v2/write") => {}
+        _ => return Ok(response(StatusCode::NOT_FOUND, "Unknown endpoint")),
+    }
+
+    if !is_authorized(&request, settings.token.as_deref()) {
+        return Ok(response(StatusCode::UNAUTHORIZED, "Invalid token"));
+    }
+
+    let precision = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|param| param.strip_prefix("precision="));
+
+    let precision = match precision {
+        Some(precision) => match Precision::parse(precision) {
+            Ok(precision) => precision,
+            Err(e) => return Ok(response(StatusCode::BAD_REQUEST, format!("{}", e))),
+        },
+        None => settings.precision,
+    };
+
+    let body = match read_body(request, settings.max_body_size).await {
+        Ok(body) => body,
+        Err(response) => return Ok(response),
+    };
+
+    // Like InfluxDB, the whole write is rejected when a line is invalid.
+    let metrics = match influx::parse(String::from_utf8_lossy(&body).as_ref(), precision) {
+        Ok(metrics) => metrics,
+        Err(e) => return Ok(response(StatusCode::BAD_REQUEST, format!("{:#}", e))),
+    };
+
+    if !metrics.is_empty() {
+        client.send_metrics(metrics).await?;
+    }
+
+    Ok(response(StatusCode::NO_CONTENT, ""))
+}
+
+#[async_trait::async_trait]
+impl Source for InfluxReceiver {
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        match self.protocol {
+            Protocol::Udp => self.listen_udp(client).await,
+            Protocol::Tcp => self.listen_tcp(client).await,
+            Protocol::Http => self.listen_http(client).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eagle_core::{poll::Schedule, EagleEndpoint, EagleEvent, Event, Origin};
+    use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+    fn client() -> (EagleClient, UnboundedReceiver<EagleEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let client = EagleClient {
+            origin: Arc::new(Origin::new("influx")),
+            endpoint: EagleEndpoint::new(sender),
+            schedule: Schedule::default(),
+        };
+
+        (client, receiver)
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            precision: Precision::Seconds,
+            token: None,
+            max_body_size: 1_024,
+        }
+    }
+
+    fn write(uri: &str, body: &'static str) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn timestamps(receiver: &mut UnboundedReceiver<EagleEvent>) -> Vec<i64> {
+        let mut timestamps = Vec::new();
+
+        while let Ok(event) = receiver.try_recv() {
+            match event.event {
+                Event::Metric(metric) => timestamps.push(metric.timestamp.timestamp_millis()),
+                other => panic!("Unexpected event {:?}", other),
+            }
+        }
+
+        timestamps
+    }
+
+    #[tokio::test]
+    async fn applies_write_precision() {
+        let cases = [
+            ("/write?db=eagle&precision=ms", "cpu usage=1 1700000000123"),
+            (
+                "/api/v2/write?precision=ns",
+                "cpu usage=1 1700000000123000000",
+            ),
+            ("/write?precision=s&db=eagle", "cpu usage=1 1700000000"),
+            // Falls back to the configured precision.
+            ("/write?db=eagle", "cpu usage=1 1700000000"),
+        ];
+        let expected = [
+            1_700_000_000_123,
+            1_700_000_000_123,
+            1_700_000_000_000,
+            1_700_000_000_000,
+        ];
+
+        for ((uri, body), expected) in cases.into_iter().zip(expected) {
+            let (client, mut receiver) = client();
+            let response = handle_request(write(uri, body), &settings(), &client)
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::NO_CONTENT, "{}", uri);
+            assert_eq!(timestamps(&mut receiver), vec![expected], "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_precisions() {
+        let (client, mut receiver) = client();
+        let request = write("/write?db=eagle&precision=h", "cpu usage=1 1");
+        let response = handle_request(request, &settings(), &client).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(timestamps(&mut receiver).is_empty());
+    }
+}
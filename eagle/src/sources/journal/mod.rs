@@ -0,0 +1,583 @@
+mod reader;
+
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use eagle_core::{EagleClient, Source};
+use eyre::{bail, eyre, WrapErr};
+use serde_json::{Map, Value};
+
+use self::reader::{format_id, parse_id, Entry, EntryHeader, Id, JournalFile};
+use crate::sources::syslog::SEVERITIES;
+
+pub const DEFAULT_JOURNAL_DIRECTORY: &str = "/var/log/journal";
+
+/// Position of the last entry we went through. Sequence numbers are used when they come from
+/// the same sequence, realtime timestamps otherwise.
+#[derive(Clone)]
+struct Cursor {
+    seqnum_id: Id,
+    seqnum: u64,
+    realtime: u64,
+}
+
+impl Cursor {
+    /// Parses a journal cursor, as in `s=...;i=...;b=...;m=...;t=...;x=...`. Only the sequence
+    /// and the realtime timestamp are needed.
+    fn parse(input: &str) -> eyre::Result<Self> {
+        let mut seqnum_id = None;
+        let mut seqnum = None;
+        let mut realtime = None;
+
+        for part in input.trim().split(';') {
+            match part.split_once('=') {
+                Some(("s", value)) => seqnum_id = parse_id(value),
+                Some(("i", value)) => seqnum = u64::from_str_radix(value, 16).ok(),
+                Some(("t", value)) => realtime = u64::from_str_radix(value, 16).ok(),
+                _ => {}
+            }
+        }
+
+        match (seqnum_id, seqnum, realtime) {
+            (Some(seqnum_id), Some(seqnum), Some(realtime)) => Ok(Self {
+                seqnum_id,
+                seqnum,
+                realtime,
+            }),
+            _ => bail!("Invalid journal cursor '{}'", input.trim()),
+        }
+    }
+
+    fn is_before(&self, seqnum_id: &Id, seqnum: u64, realtime: u64) -> bool {
+        if &self.seqnum_id == seqnum_id {
+            seqnum > self.seqnum
+        } else {
+            realtime > self.realtime
+        }
+    }
+}
+
+/// Same format as journalctl, so it can be used with `journalctl --after-cursor`.
+fn format_cursor(seqnum_id: &Id, header: &EntryHeader) -> String {
+    format!(
+        "s={};i={:x};b={};m={:x};t={:x};x={:x}",
+        format_id(seqnum_id),
+        header.seqnum,
+        format_id(&header.boot_id),
+        header.monotonic,
+        header.realtime,
+        header.xor_hash
+    )
+}
+
+/// Reads journal files directly, without going through journald. Each entry is sent as a log
+/// made of its message, with all its fields as metadata, the way `journalctl -o json` shows
+/// them.
+///
+/// Without a saved cursor, only entries written after the source started are read.
+pub struct Journal {
+    directory: PathBuf,
+    units: Vec<String>,
+    max_priority: Option<usize>,
+    matches: HashMap<String, String>,
+    cursor_path: Option<PathBuf>,
+    cursor: Option<Cursor>,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from(DEFAULT_JOURNAL_DIRECTORY),
+            units: Vec::new(),
+            max_priority: None,
+            matches: HashMap::new(),
+            cursor_path: None,
+            cursor: None,
+        }
+    }
+}
+
+impl Journal {
+    /// Journal files are looked for in that directory and its direct subdirectories, which is
+    /// where journald puts them, one subdirectory per machine id.
+    pub fn directory(self, directory: impl AsRef<Path>) -> Self {
+        Self {
+            directory: directory.as_ref().to_path_buf(),
+            ..self
+        }
+    }
+
+    /// Only keeps entries from those systemd units. Like journalctl, `.service` is implied when
+    /// a unit has no suffix.
+    pub fn units(self, units: Vec<String>) -> Self {
+        let units = units
+            .into_iter()
+            .map(|unit| {
+                if unit.contains('.') {
+                    unit
+                } else {
+                    format!("{}.service", unit)
+                }
+            })
+            .collect();
+
+        Self { units, ..self }
+    }
+
+    /// Only keeps entries of that priority or more important, given either as a syslog severity
+    /// name or as a number between 0 (emerg) and 7 (debug).
+    pub fn max_priority(self, priority: impl AsRef<str>) -> eyre::Result<Self> {
+        let priority = priority.as_ref();
+        let max_priority = match priority.parse::<usize>() {
+            Ok(value) if value < SEVERITIES.len() => value,
+            Ok(_) => bail!("Priority '{}' is out of range", priority),
+            Err(_) => SEVERITIES
+                .iter()
+                .position(|name| *name == priority)
+                .ok_or_else(|| eyre!("Unknown priority '{}'", priority))?,
+        };
+
+        Ok(Self {
+            max_priority: Some(max_priority),
+            ..self
+        })
+    }
+
+    /// Only keeps entries having all those field values, as in `FIELD=value` journalctl matches.
+    pub fn matches(self, matches: HashMap<String, String>) -> Self {
+        Self { matches, ..self }
+    }
+
+    /// Where the cursor of the last read entry is saved, so a restarted source resumes from it.
+    pub fn cursor_path(self, cursor_path: impl AsRef<Path>) -> Self {
+        Self {
+            cursor_path: Some(cursor_path.as_ref().to_path_buf()),
+            ..self
+        }
+    }
+
+    fn is_selected(&self, entry: &Entry) -> bool {
+        let has_value = |name: &str, predicate: &dyn Fn(&[u8]) -> bool| {
+            entry
+                .fields
+                .iter()
+                .any(|(field, value)| field == name && predicate(value))
+        };
+
+        if !self.units.is_empty()
+            && !has_value("_SYSTEMD_UNIT", &|value| {
+                self.units.iter().any(|unit| unit.as_bytes() == value)
+            })
+        {
+            return false;
+        }
+
+        if let Some(max_priority) = self.max_priority {
+            // Like journalctl, entries without a priority don't pass a priority filter.
+            let passes = has_value("PRIORITY", &|value| {
+                std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .is_some_and(|priority| priority <= max_priority)
+            });
+
+            if !passes {
+                return false;
+            }
+        }
+
+        self.matches
+            .iter()
+            .all(|(name, expected)| has_value(name, &|value| expected.as_bytes() == value))
+    }
+
+    async fn load_cursor(&mut self) -> eyre::Result<()> {
+        let path = match self.cursor_path.as_ref() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => {
+                let cursor = Cursor::parse(content.as_str())
+                    .wrap_err_with(|| format!("Invalid journal cursor file {:?}", path))?;
+
+                self.cursor = Some(cursor);
+                Ok(())
+            }
+
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => {
+                Err(e).wrap_err_with(|| format!("Error when reading journal cursor {:?}", path))
+            }
+        }
+    }
+
+    /// Writes to a temporary file first so a crash never leaves a truncated cursor behind.
+    async fn save_cursor(&self, cursor: &str) -> eyre::Result<()> {
+        let path = match self.cursor_path.as_ref() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let temporary = path.with_extension("tmp");
+
+        tokio::fs::write(temporary.as_path(), cursor)
+            .await
+            .wrap_err_with(|| format!("Error when writing journal cursor {:?}", temporary))?;
+
+        tokio::fs::rename(temporary.as_path(), path)
+            .await
+            .wrap_err_with(|| format!("Error when writing journal cursor {:?}", path))
+    }
+}
+
+fn journal_files(directory: &Path) -> eyre::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut directories = vec![directory.to_path_buf()];
+    let entries = std::fs::read_dir(directory)
+        .wrap_err_with(|| format!("Error when listing journal directory {:?}", directory))?;
+
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            directories.push(entry.path());
+        }
+    }
+
+    for directory in directories {
+        // Subdirectories can go away between listings, which is fine.
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        // Files ending with `.journal~` were found corrupted by journald and are skipped.
+        files.extend(
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "journal")),
+        );
+    }
+
+    Ok(files)
+}
+
+/// Where the journal currently ends, across all files.
+fn tail_cursor(directory: &Path) -> eyre::Result<Option<Cursor>> {
+    let mut cursor = None::<Cursor>;
+
+    for path in journal_files(directory)? {
+        let file = match JournalFile::open(path.as_path()) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+
+        if cursor
+            .as_ref()
+            .is_none_or(|cursor| file.tail_entry_realtime() > cursor.realtime)
+        {
+            cursor = Some(Cursor {
+                seqnum_id: file.seqnum_id(),
+                seqnum: file.tail_entry_seqnum(),
+                realtime: file.tail_entry_realtime(),
+            });
+        }
+    }
+
+    Ok(cursor)
+}
+
+/// Reads the entries coming after the cursor from every journal file, ordered by time.
+fn read_journal(directory: &Path, cursor: Option<&Cursor>) -> eyre::Result<Vec<(Id, Entry)>> {
+    let mut entries = Vec::new();
+
+    for path in journal_files(directory)? {
+        let result = JournalFile::open(path.as_path()).and_then(|mut file| {
+            let seqnum_id = file.seqnum_id();
+
+            // Archived files are left untouched once we're past them.
+            if let Some(cursor) = cursor {
+                if !cursor.is_before(
+                    &seqnum_id,
+                    file.tail_entry_seqnum(),
+                    file.tail_entry_realtime(),
+                ) {
+                    return Ok(());
+                }
+            }
+
+            let read = file.read_entries(|header| {
+                cursor.is_none_or(|cursor| {
+                    cursor.is_before(&seqnum_id, header.seqnum, header.realtime)
+                })
+            })?;
+
+            entries.extend(read.into_iter().map(|entry| (seqnum_id, entry)));
+
+            Ok(())
+        });
+
+        if let Err(e) = result {
+            tracing::warn!("Error when reading journal file {:?}: {:?}", path, e);
+        }
+    }
+
+    entries.sort_by_key(|(_, entry)| (entry.header.realtime, entry.header.seqnum));
+
+    Ok(entries)
+}
+
+fn field_value(value: Vec<u8>) -> Value {
+    match String::from_utf8(value) {
+        Ok(value) => Value::String(value),
+        // journalctl shows binary values as arrays of bytes.
+        Err(e) => Value::Array(e.into_bytes().into_iter().map(Value::from).collect()),
+    }
+}
+
+/// Builds the JSON object of an entry along with its message.
+fn entry_object(seqnum_id: &Id, entry: Entry) -> (String, Map<String, Value>) {
+    let header = entry.header;
+    let mut fields = HashMap::<String, Vec<Value>>::new();
+    let mut object = Map::new();
+
+    object.insert(
+        "__CURSOR".to_string(),
+        Value::String(format_cursor(seqnum_id, &header)),
+    );
+
+    object.insert(
+        "__REALTIME_TIMESTAMP".to_string(),
+        Value::String(header.realtime.to_string()),
+    );
+
+    object.insert(
+        "__MONOTONIC_TIMESTAMP".to_string(),
+        Value::String(header.monotonic.to_string()),
+    );
+
+    object.insert(
+        "_BOOT_ID".to_string(),
+        Value::String(format_id(&header.boot_id)),
+    );
+
+    for (name, value) in entry.fields {
+        fields.entry(name).or_default().push(field_value(value));
+    }
+
+    // Fields appearing more than once are kept as an array of values.
+    for (name, mut values) in fields {
+        let value = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            Value::Array(values)
+        };
+
+        object.insert(name, value);
+    }
+
+    let message = match object.get("MESSAGE") {
+        Some(Value::String(message)) => message.clone(),
+        _ => String::new(),
+    };
+
+    (message, object)
+}
+
+#[async_trait::async_trait]
+impl Source for Journal {
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        self.load_cursor().await?;
+
+        if self.cursor.is_none() {
+            let directory = self.directory.clone();
+
+            self.cursor = tokio::task::spawn_blocking(move || tail_cursor(&directory)).await??;
+        }
+
+        let mut ticker = client.schedule.ticker();
+
+        loop {
+            ticker.tick().await;
+
+            let directory = self.directory.clone();
+            let cursor = self.cursor.clone();
+            let entries =
+                tokio::task::spawn_blocking(move || read_journal(&directory, cursor.as_ref()))
+                    .await??;
+
+            let mut last = None;
+
+            for (seqnum_id, entry) in entries {
+                // Filtered out entries still move the cursor forward.
+                last = Some(format_cursor(&seqnum_id, &entry.header));
+
+                if !self.is_selected(&entry) {
+                    continue;
+                }
+
+                let (message, object) = entry_object(&seqnum_id, entry);
+
+                client.send_log_with_metadata(message, object).await?;
+            }
+
+            if let Some(cursor) = last {
+                self.save_cursor(cursor.as_str()).await?;
+                self.cursor = Some(Cursor::parse(cursor.as_str())?);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cursors of the entries of both test files, as `journalctl -D testdata` lists them.
+    const CURSORS: &[&str] = &[
+        "s=0000000000000000000000000000000a;i=1;b=8c0a44ba742347dfb188c7adb2e072df;m=4c4b40;t=60a24181e4000;x=79c79a93137a6417",
+        "s=0000000000000000000000000000000b;i=1;b=8c0a44ba742347dfb188c7adb2e072df;m=4c4b40;t=60a24182d8240;x=50d909c7b4648efc",
+        "s=0000000000000000000000000000000a;i=2;b=8c0a44ba742347dfb188c7adb2e072df;m=4c4b41;t=60a24183cc480;x=a6fbc6bb7ae7bfa6",
+        "s=0000000000000000000000000000000b;i=2;b=8c0a44ba742347dfb188c7adb2e072df;m=4c4b41;t=60a24184c06c0;x=e5f50590e3642df",
+        "s=0000000000000000000000000000000a;i=3;b=8c0a44ba742347dfb188c7adb2e072df;m=4c4b42;t=60a24185b4900;x=2081628e99c53e5a",
+        "s=0000000000000000000000000000000b;i=3;b=8c0a44ba742347dfb188c7adb2e072df;m=4c4b42;t=60a24186a8b40;x=c6268188d7e342d0",
+        "s=0000000000000000000000000000000a;i=4;b=8c0a44ba742347dfb188c7adb2e072df;m=4c4b43;t=60a241879cd80;x=f61d0cf1b47c62fd",
+        "s=0000000000000000000000000000000a;i=5;b=8c0a44ba742347dfb188c7adb2e072df;m=4c4b44;t=60a2418985200;x=a0062cad9c69e236",
+    ];
+
+    fn testdata() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src/sources/journal/testdata")
+    }
+
+    fn cursors(entries: &[(Id, Entry)]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|(seqnum_id, entry)| format_cursor(seqnum_id, &entry.header))
+            .collect()
+    }
+
+    #[test]
+    fn reads_every_file_in_order() {
+        let entries = read_journal(testdata().as_path(), None).unwrap();
+
+        assert_eq!(cursors(&entries), CURSORS);
+    }
+
+    #[test]
+    fn resumes_from_cursor() {
+        // Entries of the same file are compared by sequence number, entries of the other one
+        // by time.
+        for (index, cursor) in CURSORS.iter().enumerate() {
+            let cursor = Cursor::parse(cursor).unwrap();
+            let entries = read_journal(testdata().as_path(), Some(&cursor)).unwrap();
+
+            assert_eq!(cursors(&entries), &CURSORS[index + 1..], "after {}", index);
+        }
+    }
+
+    #[test]
+    fn starts_at_the_tail() {
+        let cursor = tail_cursor(testdata().as_path()).unwrap().unwrap();
+
+        assert_eq!(
+            format_id(&cursor.seqnum_id),
+            "0000000000000000000000000000000a"
+        );
+        assert_eq!(cursor.seqnum, 5);
+        assert_eq!(cursor.realtime, 1_700_000_008_000_000);
+        assert!(read_journal(testdata().as_path(), Some(&cursor))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_cursors() {
+        for input in [
+            "",
+            "s=0a;i=1;t=1",
+            "i=1;t=1",
+            CURSORS[0].replace("t=", "z=").as_str(),
+        ] {
+            assert!(Cursor::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn builds_entry_objects() {
+        let (seqnum_id, entry) = read_journal(testdata().as_path(), None)
+            .unwrap()
+            .into_iter()
+            .nth(2)
+            .unwrap();
+
+        let (message, object) = entry_object(&seqnum_id, entry);
+
+        assert_eq!(message, "x".repeat(600));
+        assert_eq!(object["__CURSOR"], CURSORS[2]);
+        assert_eq!(object["__REALTIME_TIMESTAMP"], "1700000002000000");
+        assert_eq!(object["__MONOTONIC_TIMESTAMP"], "5000001");
+        assert_eq!(object["_BOOT_ID"], "8c0a44ba742347dfb188c7adb2e072df");
+        assert_eq!(object["PRIORITY"], "3");
+        assert_eq!(object["BINARY"], serde_json::json!([0, 255]));
+        assert_eq!(object["TAG"], serde_json::json!(["a", "b"]));
+    }
+
+    #[test]
+    fn selects_entries() {
+        let entries = read_journal(testdata().as_path(), None).unwrap();
+        let selected = |journal: Journal| {
+            entries
+                .iter()
+                .filter(|(_, entry)| journal.is_selected(entry))
+                .count()
+        };
+
+        assert_eq!(selected(Journal::default()), 8);
+        assert_eq!(
+            selected(Journal::default().units(vec!["cron".to_string()])),
+            3
+        );
+        assert_eq!(
+            selected(Journal::default().units(vec!["sshd.service".to_string()])),
+            2
+        );
+        assert_eq!(
+            selected(Journal::default().max_priority("notice").unwrap()),
+            3
+        );
+        assert_eq!(selected(Journal::default().max_priority("3").unwrap()), 1);
+        assert_eq!(
+            selected(
+                Journal::default()
+                    .units(vec!["nginx".to_string()])
+                    .matches(HashMap::from([("PRIORITY".to_string(), "6".to_string())]))
+            ),
+            2
+        );
+
+        assert!(Journal::default().max_priority("8").is_err());
+        assert!(Journal::default().max_priority("loud").is_err());
+    }
+
+    #[tokio::test]
+    async fn saves_and_loads_cursors() {
+        let path = std::env::temp_dir().join(format!("eagle-journal-{}", uuid::Uuid::new_v4()));
+        let journal = Journal::default().cursor_path(path.as_path());
+
+        journal.save_cursor(CURSORS[3]).await.unwrap();
+
+        let mut restarted = Journal::default().cursor_path(path.as_path());
+
+        restarted.load_cursor().await.unwrap();
+        std::fs::remove_file(path.as_path()).unwrap();
+
+        let cursor = restarted.cursor.unwrap();
+        let entries = read_journal(testdata().as_path(), Some(&cursor)).unwrap();
+
+        assert_eq!(cursors(&entries), &CURSORS[4..]);
+    }
+}
@@ -0,0 +1,698 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use eyre::{bail, eyre, WrapErr};
+
+// See https://systemd.io/JOURNAL_FILE_FORMAT/ for the layout of everything below. All integers
+// are little-endian.
+
+const SIGNATURE: &[u8] = b"LPKSHHRH";
+
+/// Up to `tail_entry_monotonic`, the last header field we need. Every file written by systemd
+/// 187 or later has a header at least that large.
+const HEADER_READ_SIZE: u64 = 208;
+
+const INCOMPATIBLE_COMPRESSED_XZ: u32 = 1 << 0;
+const INCOMPATIBLE_COMPRESSED_LZ4: u32 = 1 << 1;
+const INCOMPATIBLE_KEYED_HASH: u32 = 1 << 2;
+const INCOMPATIBLE_COMPRESSED_ZSTD: u32 = 1 << 3;
+const INCOMPATIBLE_COMPACT: u32 = 1 << 4;
+const INCOMPATIBLE_SUPPORTED: u32 = INCOMPATIBLE_COMPRESSED_XZ
+    | INCOMPATIBLE_COMPRESSED_LZ4
+    | INCOMPATIBLE_KEYED_HASH
+    | INCOMPATIBLE_COMPRESSED_ZSTD
+    | INCOMPATIBLE_COMPACT;
+
+const OBJECT_DATA: u8 = 1;
+const OBJECT_ENTRY: u8 = 3;
+const OBJECT_ENTRY_ARRAY: u8 = 6;
+
+const OBJECT_COMPRESSED_XZ: u8 = 1 << 0;
+const OBJECT_COMPRESSED_LZ4: u8 = 1 << 1;
+const OBJECT_COMPRESSED_ZSTD: u8 = 1 << 2;
+
+const OBJECT_HEADER_SIZE: u64 = 16;
+const ENTRY_HEADER_SIZE: u64 = 64;
+const ENTRY_ARRAY_HEADER_SIZE: u64 = 24;
+
+/// Fields larger than that, compressed or not, are skipped. It mostly concerns core dumps.
+const MAX_DATA_SIZE: u64 = 16 * 1_024 * 1_024;
+
+/// Entry arrays and entries are much smaller than that in practice, this only guards against
+/// allocating whatever a corrupted size says.
+const MAX_OBJECT_SIZE: u64 = 64 * 1_024 * 1_024;
+
+pub type Id = [u8; 16];
+
+pub fn format_id(id: &Id) -> String {
+    id.iter()
+        .fold(String::with_capacity(32), |mut output, byte| {
+            let _ = write!(output, "{:02x}", byte);
+            output
+        })
+}
+
+pub fn parse_id(input: &str) -> Option<Id> {
+    if input.len() != 32 || !input.is_ascii() {
+        return None;
+    }
+
+    let mut id = [0u8; 16];
+
+    for (index, byte) in id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&input[index * 2..index * 2 + 2], 16).ok()?;
+    }
+
+    Some(id)
+}
+
+/// What identifies an entry, as found at the beginning of every entry object.
+pub struct EntryHeader {
+    pub seqnum: u64,
+    /// Microseconds since the Unix epoch.
+    pub realtime: u64,
+    /// Microseconds since boot.
+    pub monotonic: u64,
+    pub boot_id: Id,
+    pub xor_hash: u64,
+}
+
+pub struct Entry {
+    pub header: EntryHeader,
+    /// Fields in the order the entry references them. A field can appear more than once.
+    pub fields: Vec<(String, Vec<u8>)>,
+}
+
+/// A single journal file, read with plain file IO so files being written by journald can be
+/// read too.
+pub struct JournalFile {
+    file: File,
+    size: u64,
+    compact: bool,
+    seqnum_id: Id,
+    entry_array_offset: u64,
+    n_entries: u64,
+    tail_entry_seqnum: u64,
+    tail_entry_realtime: u64,
+}
+
+impl JournalFile {
+    pub fn open(path: &Path) -> eyre::Result<Self> {
+        let mut file = File::open(path).wrap_err("Error when opening journal file")?;
+        let file_size = file.metadata()?.len();
+        let mut header = [0u8; HEADER_READ_SIZE as usize];
+
+        if file_size < HEADER_READ_SIZE {
+            bail!("File too small to be a journal file");
+        }
+
+        file.read_exact(&mut header)?;
+
+        if &header[..8] != SIGNATURE {
+            bail!("Not a journal file");
+        }
+
+        let incompatible_flags = le_u32(&header, 12);
+
+        if incompatible_flags & !INCOMPATIBLE_SUPPORTED != 0 {
+            bail!("Unsupported journal features {:#x}", incompatible_flags);
+        }
+
+        let mut seqnum_id = [0u8; 16];
+
+        seqnum_id.copy_from_slice(&header[72..88]);
+
+        let header_size = le_u64(&header, 88);
+        let arena_size = le_u64(&header, 96);
+
+        Ok(Self {
+            file,
+            // The header can claim more than what's been flushed to disk so far.
+            size: header_size.saturating_add(arena_size).min(file_size),
+            compact: incompatible_flags & INCOMPATIBLE_COMPACT != 0,
+            seqnum_id,
+            n_entries: le_u64(&header, 152),
+            tail_entry_seqnum: le_u64(&header, 160),
+            entry_array_offset: le_u64(&header, 176),
+            tail_entry_realtime: le_u64(&header, 192),
+        })
+    }
+
+    /// Sequence numbers are only comparable between files sharing that id.
+    pub fn seqnum_id(&self) -> Id {
+        self.seqnum_id
+    }
+
+    pub fn tail_entry_seqnum(&self) -> u64 {
+        self.tail_entry_seqnum
+    }
+
+    pub fn tail_entry_realtime(&self) -> u64 {
+        self.tail_entry_realtime
+    }
+
+    /// Reads the entries `is_new` accepts, in the order they were written. It's expected to
+    /// accept every entry after the first one it accepts, which lets us skip whole entry arrays
+    /// by only looking at their last entry.
+    pub fn read_entries<F>(&mut self, is_new: F) -> eyre::Result<Vec<Entry>>
+    where
+        F: Fn(&EntryHeader) -> bool,
+    {
+        let mut entries = Vec::new();
+        let mut array_offset = self.entry_array_offset;
+        let mut remaining = self.n_entries;
+
+        while array_offset != 0 && remaining > 0 {
+            let array = self.read_object(array_offset, OBJECT_ENTRY_ARRAY)?;
+            let mut offsets = self.item_offsets(&array[ENTRY_ARRAY_HEADER_SIZE as usize..]);
+
+            // Unused slots at the end of the last array are zeroed.
+            offsets.retain(|offset| *offset != 0);
+            offsets.truncate(remaining as usize);
+            remaining -= offsets.len() as u64;
+            array_offset = le_u64(&array, 16);
+
+            if let Some(last) = offsets.last() {
+                if !is_new(&self.read_entry_header(*last)?) {
+                    continue;
+                }
+            }
+
+            for offset in offsets {
+                let header = self.read_entry_header(offset)?;
+
+                if is_new(&header) {
+                    entries.push(self.read_entry(offset, header)?);
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Entry arrays and entries reference objects with 32 bits offsets in compact files, and
+    /// with 64 bits offsets otherwise. Regular entry items also carry the data hash, which we
+    /// don't need.
+    fn item_offsets(&self, items: &[u8]) -> Vec<u64> {
+        if self.compact {
+            items
+                .chunks_exact(4)
+                .map(|item| le_u32(item, 0) as u64)
+                .collect()
+        } else {
+            items.chunks_exact(8).map(|item| le_u64(item, 0)).collect()
+        }
+    }
+
+    fn read_entry_header(&mut self, offset: u64) -> eyre::Result<EntryHeader> {
+        let entry = self.read_at(offset, ENTRY_HEADER_SIZE)?;
+
+        if entry[0] != OBJECT_ENTRY {
+            bail!("Expected an entry object at offset {}", offset);
+        }
+
+        Ok(parse_entry_header(&entry))
+    }
+
+    fn read_entry(&mut self, offset: u64, header: EntryHeader) -> eyre::Result<Entry> {
+        let entry = self.read_object(offset, OBJECT_ENTRY)?;
+        let items = &entry[ENTRY_HEADER_SIZE as usize..];
+        let offsets = if self.compact {
+            self.item_offsets(items)
+        } else {
+            items.chunks_exact(16).map(|item| le_u64(item, 0)).collect()
+        };
+
+        let mut fields = Vec::with_capacity(offsets.len());
+
+        for offset in offsets {
+            let payload = match self.read_data(offset)? {
+                Some(payload) => payload,
+                None => continue,
+            };
+
+            // Payloads are `FIELD=value`, where the value can be binary.
+            if let Some(separator) = payload.iter().position(|byte| *byte == b'=') {
+                let name = String::from_utf8_lossy(&payload[..separator]).into_owned();
+
+                fields.push((name, payload[separator + 1..].to_vec()));
+            }
+        }
+
+        Ok(Entry { header, fields })
+    }
+
+    fn read_data(&mut self, offset: u64) -> eyre::Result<Option<Vec<u8>>> {
+        let object = self.read_at(offset, OBJECT_HEADER_SIZE)?;
+        let size = le_u64(&object, 8);
+
+        if object[0] != OBJECT_DATA {
+            bail!("Expected a data object at offset {}", offset);
+        }
+
+        if size > MAX_DATA_SIZE {
+            return Ok(None);
+        }
+
+        let flags = object[1];
+        let object = self.read_object(offset, OBJECT_DATA)?;
+        let payload_offset = if self.compact { 72 } else { 64 };
+        let payload = object
+            .get(payload_offset..)
+            .ok_or_else(|| eyre!("Truncated data object at offset {}", offset))?;
+
+        if flags & OBJECT_COMPRESSED_LZ4 != 0 {
+            decompress_lz4(payload)
+        } else if flags & OBJECT_COMPRESSED_ZSTD != 0 {
+            decompress_zstd(payload)
+        } else if flags & OBJECT_COMPRESSED_XZ != 0 {
+            decompress_xz(payload)
+        } else {
+            Ok(Some(payload.to_vec()))
+        }
+    }
+
+    fn read_object(&mut self, offset: u64, r#type: u8) -> eyre::Result<Vec<u8>> {
+        let header = self.read_at(offset, OBJECT_HEADER_SIZE)?;
+        let size = le_u64(&header, 8);
+
+        if header[0] != r#type {
+            bail!(
+                "Expected an object of type {} at offset {}, got {}",
+                r#type,
+                offset,
+                header[0]
+            );
+        }
+
+        if !(OBJECT_HEADER_SIZE..=MAX_OBJECT_SIZE).contains(&size) {
+            bail!("Invalid object size {} at offset {}", size, offset);
+        }
+
+        self.read_at(offset, size)
+    }
+
+    fn read_at(&mut self, offset: u64, len: u64) -> eyre::Result<Vec<u8>> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.size => {}
+            _ => bail!("Object at offset {} is out of the file bounds", offset),
+        }
+
+        let mut buffer = vec![0u8; len as usize];
+
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buffer)?;
+
+        Ok(buffer)
+    }
+}
+
+fn parse_entry_header(entry: &[u8]) -> EntryHeader {
+    let mut boot_id = [0u8; 16];
+
+    boot_id.copy_from_slice(&entry[40..56]);
+
+    EntryHeader {
+        seqnum: le_u64(entry, 16),
+        realtime: le_u64(entry, 24),
+        monotonic: le_u64(entry, 32),
+        boot_id,
+        xor_hash: le_u64(entry, 56),
+    }
+}
+
+/// LZ4 payloads are prefixed with their uncompressed size.
+fn decompress_lz4(payload: &[u8]) -> eyre::Result<Option<Vec<u8>>> {
+    if payload.len() < 8 {
+        bail!("Truncated LZ4 payload");
+    }
+
+    let size = le_u64(payload, 0);
+
+    if size > MAX_DATA_SIZE {
+        return Ok(None);
+    }
+
+    lz4_flex::block::decompress(&payload[8..], size as usize)
+        .map(Some)
+        .map_err(|e| eyre!("Invalid LZ4 payload: {}", e))
+}
+
+fn decompress_zstd(payload: &[u8]) -> eyre::Result<Option<Vec<u8>>> {
+    let mut output = Vec::new();
+
+    ruzstd::StreamingDecoder::new(payload)
+        .map_err(|e| eyre!("Invalid zstd payload: {:?}", e))?
+        .take(MAX_DATA_SIZE + 1)
+        .read_to_end(&mut output)
+        .wrap_err("Invalid zstd payload")?;
+
+    if output.len() as u64 > MAX_DATA_SIZE {
+        return Ok(None);
+    }
+
+    Ok(Some(output))
+}
+
+fn decompress_xz(mut payload: &[u8]) -> eyre::Result<Option<Vec<u8>>> {
+    // lzma-rs only hands a block over once it's fully decompressed, so what the LZMA2 chunks
+    // announce is checked first.
+    if xz_uncompressed_size(payload).is_some_and(|size| size > MAX_DATA_SIZE) {
+        return Ok(None);
+    }
+
+    let mut output = LimitedWriter::new(MAX_DATA_SIZE as usize);
+
+    match lzma_rs::xz_decompress(&mut payload, &mut output) {
+        Ok(()) => Ok(Some(output.buffer)),
+        Err(_) if output.exceeded => Ok(None),
+        Err(e) => bail!("Invalid XZ payload: {:?}", e),
+    }
+}
+
+/// Sums the sizes the LZMA2 chunks of every block announce. `None` when the payload doesn't
+/// look like a single XZ stream, in which case decompression tells what's wrong with it.
+fn xz_uncompressed_size(payload: &[u8]) -> Option<u64> {
+    let be_u16 = |at: usize| -> Option<u64> {
+        Some(u16::from_be_bytes([*payload.get(at)?, *payload.get(at + 1)?]) as u64)
+    };
+
+    let check_size = match payload.get(7)? & 0x0f {
+        0 => 0,
+        1..=3 => 4,
+        4..=6 => 8,
+        7..=9 => 16,
+        10..=12 => 32,
+        _ => 64,
+    };
+
+    let mut position = 12;
+    let mut size = 0u64;
+
+    loop {
+        let block_start = position;
+        let header_size = *payload.get(position)? as usize;
+
+        // A null byte instead of a block header starts the index.
+        if header_size == 0 {
+            return Some(size);
+        }
+
+        position += (header_size + 1) * 4;
+
+        loop {
+            let status = *payload.get(position)?;
+
+            match status {
+                0 => {
+                    position += 1;
+                    break;
+                }
+
+                // Uncompressed chunks.
+                1 | 2 => {
+                    let unpacked = be_u16(position + 1)? + 1;
+
+                    size = size.saturating_add(unpacked);
+                    position += 3 + unpacked as usize;
+                }
+
+                0x80..=0xff => {
+                    let unpacked = ((status as u64 & 0x1f) << 16 | be_u16(position + 1)?) + 1;
+                    let packed = be_u16(position + 3)? as usize + 1;
+                    // Chunks resetting the properties carry them in an extra byte.
+                    let properties = usize::from(status >= 0xc0);
+
+                    size = size.saturating_add(unpacked);
+                    position += 5 + properties + packed;
+                }
+
+                _ => return None,
+            }
+        }
+
+        // Blocks are padded to a multiple of four bytes, then followed by their check.
+        position = block_start + (position - block_start).div_ceil(4) * 4 + check_size;
+    }
+}
+
+/// Fails writes that would take the output past its limit.
+struct LimitedWriter {
+    buffer: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl LimitedWriter {
+    fn new(limit: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            limit,
+            exceeded: false,
+        }
+    }
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() + buf.len() > self.limit {
+            self.exceeded = true;
+
+            return Err(io::Error::other("Decompressed data exceeds the size limit"));
+        }
+
+        self.buffer.extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0u8; 4];
+
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+fn le_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value = [0u8; 8];
+
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// See `testdata/generate.py` for how those files are written.
+    fn testdata(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/sources/journal/testdata")
+            .join(name)
+    }
+
+    fn field<'a>(entry: &'a Entry, name: &str) -> Vec<&'a [u8]> {
+        entry
+            .fields
+            .iter()
+            .filter(|(field, _)| field == name)
+            .map(|(_, value)| value.as_slice())
+            .collect()
+    }
+
+    #[test]
+    fn reads_headers() {
+        let cases = [
+            ("regular.journal", "0000000000000000000000000000000a", 5, 8),
+            ("compact.journal", "0000000000000000000000000000000b", 3, 5),
+        ];
+
+        for (name, seqnum_id, tail_seqnum, tail_seconds) in cases {
+            let file = JournalFile::open(testdata(name).as_path()).unwrap();
+
+            assert_eq!(format_id(&file.seqnum_id()), seqnum_id, "{}", name);
+            assert_eq!(file.tail_entry_seqnum(), tail_seqnum, "{}", name);
+            assert_eq!(
+                file.tail_entry_realtime(),
+                1_700_000_000_000_000 + tail_seconds * 1_000_000,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn reads_regular_entries() {
+        let mut file = JournalFile::open(testdata("regular.journal").as_path()).unwrap();
+        let entries = file.read_entries(|_| true).unwrap();
+        let messages = entries
+            .iter()
+            .map(|entry| field(entry, "MESSAGE"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                vec![b"started".as_slice()],
+                // Compressed with XZ, LZ4 and zstd.
+                vec![[b'x'; 600].as_slice()],
+                vec![[b'y'; 600].as_slice()],
+                vec![[b'z'; 600].as_slice()],
+                vec![b"stopped".as_slice()],
+            ]
+        );
+
+        let headers = entries
+            .iter()
+            .map(|entry| (entry.header.seqnum, entry.header.monotonic))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            headers,
+            vec![
+                (1, 5_000_000),
+                (2, 5_000_001),
+                (3, 5_000_002),
+                (4, 5_000_003),
+                (5, 5_000_004)
+            ]
+        );
+
+        assert_eq!(
+            format_id(&entries[0].header.boot_id),
+            "8c0a44ba742347dfb188c7adb2e072df"
+        );
+
+        assert_eq!(entries[0].header.xor_hash, 0x79c79a93137a6417);
+        assert_eq!(field(&entries[0], "_SYSTEMD_UNIT"), vec![b"nginx.service"]);
+        assert_eq!(field(&entries[1], "BINARY"), vec![[0u8, 255].as_slice()]);
+        assert_eq!(field(&entries[1], "TAG"), vec![b"a", b"b"]);
+
+        // Decompresses to more than we accept.
+        assert!(field(&entries[3], "HUGE").is_empty());
+        assert_eq!(field(&entries[3], "PRIORITY"), vec![b"4"]);
+    }
+
+    #[test]
+    fn reads_compact_entries() {
+        let mut file = JournalFile::open(testdata("compact.journal").as_path()).unwrap();
+        let entries = file.read_entries(|_| true).unwrap();
+        let messages = entries
+            .iter()
+            .map(|entry| field(entry, "MESSAGE"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                // Compressed with zstd, XZ and LZ4.
+                vec![[b'a'; 600].as_slice()],
+                vec![[b'b'; 600].as_slice()],
+                vec![[b'c'; 600].as_slice()],
+            ]
+        );
+
+        assert!(field(&entries[1], "HUGE").is_empty());
+        assert_eq!(field(&entries[1], "PRIORITY"), vec![b"5"]);
+    }
+
+    #[test]
+    fn only_reads_new_entries() {
+        // The first entry array holds four entries, the second one the last entry.
+        let cases = [
+            (0, vec![1, 2, 3, 4, 5]),
+            (2, vec![3, 4, 5]),
+            (4, vec![5]),
+            (5, vec![]),
+        ];
+
+        for (after, expected) in cases {
+            let mut file = JournalFile::open(testdata("regular.journal").as_path()).unwrap();
+            let entries = file.read_entries(|header| header.seqnum > after).unwrap();
+            let seqnums = entries
+                .iter()
+                .map(|entry| entry.header.seqnum)
+                .collect::<Vec<_>>();
+
+            assert_eq!(seqnums, expected, "after {}", after);
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        match JournalFile::open(testdata("generate.py").as_path()) {
+            Ok(_) => panic!("generate.py was opened"),
+            Err(e) => assert_eq!(format!("{}", e), "Not a journal file"),
+        }
+    }
+
+    #[test]
+    fn parses_ids() {
+        let id = parse_id("8c0a44ba742347dfb188c7adb2e072df").unwrap();
+
+        assert_eq!(format_id(&id), "8c0a44ba742347dfb188c7adb2e072df");
+        assert!(parse_id("8c0a44ba742347dfb188c7adb2e072d").is_none());
+        assert!(parse_id("8c0a44ba742347dfb188c7adb2e072dg").is_none());
+    }
+
+    #[test]
+    fn announces_xz_sizes() {
+        // lzma-rs only writes uncompressed chunks.
+        let mut compressed = Vec::new();
+
+        lzma_rs::xz_compress(&mut b"hello".as_slice(), &mut compressed).unwrap();
+
+        assert_eq!(xz_uncompressed_size(compressed.as_slice()), Some(5));
+        assert_eq!(
+            decompress_xz(compressed.as_slice()).unwrap(),
+            Some(b"hello".to_vec())
+        );
+
+        // The oversized field of the fourth entry, written by liblzma.
+        let mut file = JournalFile::open(testdata("regular.journal").as_path()).unwrap();
+        let array = file
+            .read_object(file.entry_array_offset, OBJECT_ENTRY_ARRAY)
+            .unwrap();
+
+        let entry_offset = file.item_offsets(&array[ENTRY_ARRAY_HEADER_SIZE as usize..])[3];
+        let entry = file.read_object(entry_offset, OBJECT_ENTRY).unwrap();
+        let sizes = entry[ENTRY_HEADER_SIZE as usize..]
+            .chunks_exact(16)
+            .map(|item| file.read_object(le_u64(item, 0), OBJECT_DATA).unwrap())
+            .filter(|object| object[1] & OBJECT_COMPRESSED_XZ != 0)
+            .map(|object| xz_uncompressed_size(&object[64..]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(sizes, vec![Some(5 + 17 * 1_024 * 1_024)]);
+    }
+
+    #[test]
+    fn caps_decompressed_sizes() {
+        let mut oversized = (MAX_DATA_SIZE + 1).to_le_bytes().to_vec();
+
+        oversized.extend_from_slice(&[0u8; 8]);
+
+        assert!(decompress_lz4(oversized.as_slice()).unwrap().is_none());
+        assert!(decompress_lz4(&[0u8; 4]).is_err());
+        assert!(decompress_xz(b"not xz").is_err());
+        assert!(decompress_zstd(b"not zstd").is_err());
+
+        let mut writer = LimitedWriter::new(4);
+
+        writer.write_all(b"abc").unwrap();
+        assert!(writer.write_all(b"de").is_err());
+        assert!(writer.exceeded);
+        assert_eq!(writer.buffer, b"abc");
+    }
+}
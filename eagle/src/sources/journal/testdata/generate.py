@@ -0,0 +1,520 @@
+#!/usr/bin/env python3
+"""Writes the journal files the journal source tests read.
+
+journald only compresses with the algorithm it was built with and never writes fields past its
+own limits, so the files are written by hand, following https://systemd.io/JOURNAL_FILE_FORMAT/.
+They pass `journalctl --verify --file <file>`. Requires the `zstd` command.
+
+    python3 generate.py
+"""
+
+import lzma
+import os
+import struct
+import subprocess
+
+HEADER_SIZE = 264
+SIGNATURE = b"LPKSHHRH"
+
+INCOMPATIBLE_COMPRESSED_XZ = 1 << 0
+INCOMPATIBLE_COMPRESSED_LZ4 = 1 << 1
+INCOMPATIBLE_KEYED_HASH = 1 << 2
+INCOMPATIBLE_COMPRESSED_ZSTD = 1 << 3
+INCOMPATIBLE_COMPACT = 1 << 4
+
+OBJECT_DATA = 1
+OBJECT_FIELD = 2
+OBJECT_ENTRY = 3
+OBJECT_DATA_HASH_TABLE = 4
+OBJECT_FIELD_HASH_TABLE = 5
+OBJECT_ENTRY_ARRAY = 6
+
+COMPRESSED_XZ = 1 << 0
+COMPRESSED_LZ4 = 1 << 1
+COMPRESSED_ZSTD = 1 << 2
+
+DATA_HASH_TABLE_SIZE = 37
+FIELD_HASH_TABLE_SIZE = 11
+
+# Past what the reader accepts once decompressed.
+OVERSIZED = 17 * 1024 * 1024
+
+BOOT_ID = bytes.fromhex("8c0a44ba742347dfb188c7adb2e072df")
+MACHINE_ID = bytes.fromhex("3d1219c7c4c5404aaa1f6d2a48adfda4")
+
+# First realtime timestamp, 2023-11-14T22:13:20Z.
+REALTIME = 1_700_000_000_000_000
+MONOTONIC = 5_000_000
+
+MASK32 = 0xFFFFFFFF
+MASK64 = 0xFFFFFFFFFFFFFFFF
+
+
+def align(offset):
+    return (offset + 7) & ~7
+
+
+def rot32(x, k):
+    return ((x << k) | (x >> (32 - k))) & MASK32
+
+
+def jenkins_hash64(data):
+    """lookup3's hashlittle2, as systemd's jenkins_hash64."""
+    a = b = c = (0xDEADBEEF + len(data)) & MASK32
+    words = struct.unpack_from("<%dI" % (len(data) // 4), data)
+    start = 0
+
+    while len(data) - start > 12:
+        index = start // 4
+        a = (a + words[index]) & MASK32
+        b = (b + words[index + 1]) & MASK32
+        c = (c + words[index + 2]) & MASK32
+
+        a = (a - c) & MASK32; a ^= rot32(c, 4); c = (c + b) & MASK32
+        b = (b - a) & MASK32; b ^= rot32(a, 6); a = (a + c) & MASK32
+        c = (c - b) & MASK32; c ^= rot32(b, 8); b = (b + a) & MASK32
+        a = (a - c) & MASK32; a ^= rot32(c, 16); c = (c + b) & MASK32
+        b = (b - a) & MASK32; b ^= rot32(a, 19); a = (a + c) & MASK32
+        c = (c - b) & MASK32; c ^= rot32(b, 4); b = (b + a) & MASK32
+
+        start += 12
+
+    rest = data[start:]
+
+    if not rest:
+        return (c << 32) | b
+
+    def word(chunk):
+        return int.from_bytes(chunk.ljust(4, b"\0"), "little")
+
+    a = (a + word(rest[0:4])) & MASK32
+    b = (b + word(rest[4:8])) & MASK32
+    c = (c + word(rest[8:12])) & MASK32
+
+    c ^= b; c = (c - rot32(b, 14)) & MASK32
+    a ^= c; a = (a - rot32(c, 11)) & MASK32
+    b ^= a; b = (b - rot32(a, 25)) & MASK32
+    c ^= b; c = (c - rot32(b, 16)) & MASK32
+    a ^= c; a = (a - rot32(c, 4)) & MASK32
+    b ^= a; b = (b - rot32(a, 14)) & MASK32
+    c ^= b; c = (c - rot32(b, 24)) & MASK32
+
+    return (c << 32) | b
+
+
+def rot64(x, k):
+    return ((x << k) | (x >> (64 - k))) & MASK64
+
+
+def siphash24(key, data):
+    k0, k1 = struct.unpack("<QQ", key)
+    v0 = k0 ^ 0x736F6D6570736575
+    v1 = k1 ^ 0x646F72616E646F6D
+    v2 = k0 ^ 0x6C7967656E657261
+    v3 = k1 ^ 0x7465646279746573
+
+    def rounds(n):
+        nonlocal v0, v1, v2, v3
+
+        for _ in range(n):
+            v0 = (v0 + v1) & MASK64; v1 = rot64(v1, 13); v1 ^= v0; v0 = rot64(v0, 32)
+            v2 = (v2 + v3) & MASK64; v3 = rot64(v3, 16); v3 ^= v2
+            v0 = (v0 + v3) & MASK64; v3 = rot64(v3, 21); v3 ^= v0
+            v2 = (v2 + v1) & MASK64; v1 = rot64(v1, 17); v1 ^= v2; v2 = rot64(v2, 32)
+
+    tail = len(data) % 8
+    blocks = data[: len(data) - tail]
+    last = data[len(data) - tail :].ljust(7, b"\0") + bytes([len(data) & 0xFF])
+
+    for (m,) in struct.iter_unpack("<Q", blocks + last):
+        v3 ^= m
+        rounds(2)
+        v0 ^= m
+
+    v2 ^= 0xFF
+    rounds(4)
+
+    return v0 ^ v1 ^ v2 ^ v3
+
+
+def lz4_block(data):
+    """An LZ4 block of the literals before the trailing run of a byte, that run as a match, and
+    the five literals every block ends with."""
+    run = len(data) - len(data.rstrip(data[-1:]))
+    literals = data[: len(data) - run + 1]
+    match = run - 1 - 5
+
+    assert match >= 4
+
+    def length(value):
+        out = b""
+
+        while value >= 255:
+            out += b"\xff"
+            value -= 255
+
+        return out + bytes([value])
+
+    token = (min(len(literals), 15) << 4) | min(match - 4, 15)
+    block = bytes([token])
+
+    if len(literals) >= 15:
+        block += length(len(literals) - 15)
+
+    block += literals + struct.pack("<H", 1)
+
+    if match - 4 >= 15:
+        block += length(match - 4 - 15)
+
+    return block + bytes([0x50]) + data[-5:]
+
+
+def compress(method, payload):
+    if method == COMPRESSED_XZ:
+        return lzma.compress(payload, format=lzma.FORMAT_XZ, check=lzma.CHECK_NONE)
+
+    if method == COMPRESSED_LZ4:
+        return struct.pack("<Q", len(payload)) + lz4_block(payload)
+
+    if method == COMPRESSED_ZSTD:
+        return subprocess.run(
+            # journald requires the uncompressed size in the frame header.
+            ["zstd", "-q", "-c", "--no-check", "--stream-size=%d" % len(payload)],
+            input=payload,
+            capture_output=True,
+            check=True,
+        ).stdout
+
+    return payload
+
+
+class Writer:
+    def __init__(self, file_id, seqnum_id, compact, keyed):
+        self.file_id = file_id
+        self.seqnum_id = seqnum_id
+        self.compact = compact
+        self.keyed = keyed
+        self.objects = []
+        self.offset = HEADER_SIZE
+        self.data = {}
+        self.fields = {}
+        self.entries = []
+        self.data_table = self.add(OBJECT_DATA_HASH_TABLE, 16 * DATA_HASH_TABLE_SIZE)
+        self.field_table = self.add(OBJECT_FIELD_HASH_TABLE, 16 * FIELD_HASH_TABLE_SIZE)
+        self.data_buckets = [[] for _ in range(DATA_HASH_TABLE_SIZE)]
+        self.field_buckets = [[] for _ in range(FIELD_HASH_TABLE_SIZE)]
+
+    def hash(self, payload):
+        if self.keyed:
+            return siphash24(self.file_id, payload)
+
+        return jenkins_hash64(payload)
+
+    def add(self, type, body_size, **fields):
+        obj = dict(type=type, offset=self.offset, size=16 + body_size, flags=0, **fields)
+
+        self.objects.append(obj)
+        self.offset = align(self.offset + obj["size"])
+
+        return obj
+
+    def field(self, name):
+        if name not in self.fields:
+            obj = self.add(OBJECT_FIELD, 24 + len(name), name=name, hash=self.hash(name), data=[])
+
+            self.fields[name] = obj
+            self.field_buckets[obj["hash"] % FIELD_HASH_TABLE_SIZE].append(obj)
+
+        return self.fields[name]
+
+    def data_object(self, payload, method):
+        if payload not in self.data:
+            field = self.field(payload.split(b"=", 1)[0])
+            stored = compress(method, payload)
+            header = 72 if self.compact else 64
+            obj = self.add(
+                OBJECT_DATA,
+                header - 16 + len(stored),
+                payload=payload,
+                stored=stored,
+                hash=self.hash(payload),
+                entries=[],
+            )
+
+            obj["flags"] = method
+            self.data[payload] = obj
+            field["data"].append(obj)
+            self.data_buckets[obj["hash"] % DATA_HASH_TABLE_SIZE].append(obj)
+
+        return self.data[payload]
+
+    def entry(self, seqnum, realtime, monotonic, fields):
+        items = [self.data_object(payload, method) for payload, method in fields]
+        items = sorted({obj["offset"]: obj for obj in items}.values(), key=lambda o: o["offset"])
+        item_size = 4 if self.compact else 16
+        xor_hash = 0
+
+        for obj in items:
+            xor_hash ^= jenkins_hash64(obj["payload"])
+
+        entry = self.add(
+            OBJECT_ENTRY,
+            48 + item_size * len(items),
+            seqnum=seqnum,
+            realtime=realtime,
+            monotonic=monotonic,
+            xor_hash=xor_hash,
+            items=items,
+        )
+
+        for obj in items:
+            obj["entries"].append(entry)
+
+        self.entries.append(entry)
+
+    def entry_arrays(self, entries, first_capacity):
+        """Chains arrays of growing capacities like journald does, returns them."""
+        arrays = []
+        capacity = first_capacity
+        item_size = 4 if self.compact else 8
+
+        while entries:
+            chunk, entries = entries[:capacity], entries[capacity:]
+            arrays.append(self.add(OBJECT_ENTRY_ARRAY, 8 + item_size * capacity, items=chunk))
+            capacity *= 2
+
+        return arrays
+
+    def finish(self, path):
+        self.global_arrays = self.entry_arrays(self.entries, 4)
+
+        for obj in list(self.data.values()):
+            obj["arrays"] = self.entry_arrays(obj["entries"][1:], 4)
+
+        out = bytearray(self.offset)
+
+        for arrays in [self.global_arrays] + [obj["arrays"] for obj in self.data.values()]:
+            for current, following in zip(arrays, arrays[1:] + [None]):
+                current["next"] = following["offset"] if following else 0
+
+        for obj in self.objects:
+            self.serialize(out, obj)
+
+        self.serialize_header(out)
+
+        with open(path, "wb") as file:
+            file.write(out)
+
+    def serialize(self, out, obj):
+        offset = obj["offset"]
+        struct.pack_into("<BB6xQ", out, offset, obj["type"], obj["flags"], obj["size"])
+        body = offset + 16
+
+        if obj["type"] == OBJECT_DATA:
+            entries = obj["entries"]
+            arrays = obj["arrays"]
+            same_field = self.fields[obj["payload"].split(b"=", 1)[0]]["data"]
+            index = same_field.index(obj)
+            next_field = same_field[index + 1]["offset"] if index + 1 < len(same_field) else 0
+            bucket = self.data_buckets[obj["hash"] % DATA_HASH_TABLE_SIZE]
+            index = bucket.index(obj)
+            next_hash = bucket[index + 1]["offset"] if index + 1 < len(bucket) else 0
+
+            struct.pack_into(
+                "<QQQQQQ",
+                out,
+                body,
+                obj["hash"],
+                next_hash,
+                next_field,
+                entries[0]["offset"] if entries else 0,
+                arrays[0]["offset"] if arrays else 0,
+                len(entries),
+            )
+
+            if self.compact:
+                tail = arrays[-1] if arrays else None
+                struct.pack_into(
+                    "<II",
+                    out,
+                    offset + 64,
+                    tail["offset"] if tail else 0,
+                    len(tail["items"]) if tail else 0,
+                )
+
+            start = offset + (72 if self.compact else 64)
+            out[start : start + len(obj["stored"])] = obj["stored"]
+
+        elif obj["type"] == OBJECT_FIELD:
+            bucket = self.field_buckets[obj["hash"] % FIELD_HASH_TABLE_SIZE]
+            index = bucket.index(obj)
+            next_hash = bucket[index + 1]["offset"] if index + 1 < len(bucket) else 0
+            head_data = obj["data"][0]["offset"] if obj["data"] else 0
+
+            struct.pack_into("<QQQ", out, body, obj["hash"], next_hash, head_data)
+            out[offset + 40 : offset + 40 + len(obj["name"])] = obj["name"]
+
+        elif obj["type"] == OBJECT_ENTRY:
+            struct.pack_into(
+                "<QQQ16sQ",
+                out,
+                body,
+                obj["seqnum"],
+                obj["realtime"],
+                obj["monotonic"],
+                BOOT_ID,
+                obj["xor_hash"],
+            )
+
+            for index, item in enumerate(obj["items"]):
+                if self.compact:
+                    struct.pack_into("<I", out, offset + 64 + 4 * index, item["offset"])
+                else:
+                    struct.pack_into(
+                        "<QQ", out, offset + 64 + 16 * index, item["offset"], item["hash"]
+                    )
+
+        elif obj["type"] in (OBJECT_DATA_HASH_TABLE, OBJECT_FIELD_HASH_TABLE):
+            buckets = (
+                self.data_buckets if obj["type"] == OBJECT_DATA_HASH_TABLE else self.field_buckets
+            )
+
+            for index, bucket in enumerate(buckets):
+                if bucket:
+                    struct.pack_into(
+                        "<QQ", out, body + 16 * index, bucket[0]["offset"], bucket[-1]["offset"]
+                    )
+
+        elif obj["type"] == OBJECT_ENTRY_ARRAY:
+            struct.pack_into("<Q", out, body, obj["next"])
+
+            for index, entry in enumerate(obj["items"]):
+                if self.compact:
+                    struct.pack_into("<I", out, offset + 24 + 4 * index, entry["offset"])
+                else:
+                    struct.pack_into("<Q", out, offset + 24 + 8 * index, entry["offset"])
+
+    def serialize_header(self, out):
+        flags = INCOMPATIBLE_COMPRESSED_XZ | INCOMPATIBLE_COMPRESSED_LZ4
+        flags |= INCOMPATIBLE_COMPRESSED_ZSTD
+
+        if self.keyed:
+            flags |= INCOMPATIBLE_KEYED_HASH
+
+        if self.compact:
+            flags |= INCOMPATIBLE_COMPACT
+
+        first, last = self.entries[0], self.entries[-1]
+        tail_array = self.global_arrays[-1]
+        counts = {}
+
+        for obj in self.objects:
+            counts[obj["type"]] = counts.get(obj["type"], 0) + 1
+
+        def depth(buckets):
+            return max(len(bucket) for bucket in buckets) - 1 if any(buckets) else 0
+
+        out[0:8] = SIGNATURE
+        struct.pack_into(
+            "<II8x16s16s16s16s",
+            out,
+            8,
+            0,
+            flags,
+            self.file_id,
+            MACHINE_ID,
+            BOOT_ID,
+            self.seqnum_id,
+        )
+
+        struct.pack_into(
+            "<" + "Q" * 21 + "II",
+            out,
+            88,
+            HEADER_SIZE,
+            len(out) - HEADER_SIZE,
+            self.data_table["offset"] + 16,
+            16 * DATA_HASH_TABLE_SIZE,
+            self.field_table["offset"] + 16,
+            16 * FIELD_HASH_TABLE_SIZE,
+            self.objects[-1]["offset"],
+            len(self.objects),
+            len(self.entries),
+            last["seqnum"],
+            first["seqnum"],
+            self.global_arrays[0]["offset"],
+            first["realtime"],
+            last["realtime"],
+            last["monotonic"],
+            counts.get(OBJECT_DATA, 0),
+            counts.get(OBJECT_FIELD, 0),
+            0,
+            counts.get(OBJECT_ENTRY_ARRAY, 0),
+            depth(self.data_buckets),
+            depth(self.field_buckets),
+            tail_array["offset"] if self.compact else 0,
+            len(tail_array["items"]) if self.compact else 0,
+        )
+
+
+def common(unit, priority, message, method=0):
+    return [
+        (b"_HOSTNAME=fixture", 0),
+        (b"_SYSTEMD_UNIT=" + unit, 0),
+        (b"PRIORITY=" + priority, 0),
+        (b"MESSAGE=" + message, method),
+    ]
+
+
+def regular(path):
+    """Entries at even seconds, in the regular format with Jenkins hashes."""
+    writer = Writer(
+        bytes.fromhex("00000000000000000000000000000001"),
+        bytes.fromhex("0000000000000000000000000000000a"),
+        compact=False,
+        keyed=False,
+    )
+
+    entries = [
+        common(b"nginx.service", b"6", b"started"),
+        common(b"nginx.service", b"3", b"x" * 600, COMPRESSED_XZ)
+        + [(b"BINARY=\x00\xff", 0), (b"TAG=a", 0), (b"TAG=b", 0)],
+        common(b"sshd.service", b"6", b"y" * 600, COMPRESSED_LZ4),
+        common(b"sshd.service", b"4", b"z" * 600, COMPRESSED_ZSTD)
+        + [(b"HUGE=" + b"\0" * OVERSIZED, COMPRESSED_XZ)],
+        common(b"nginx.service", b"6", b"stopped"),
+    ]
+
+    for index, fields in enumerate(entries):
+        writer.entry(index + 1, REALTIME + 2_000_000 * index, MONOTONIC + index, fields)
+
+    writer.finish(path)
+
+
+def compact(path):
+    """Entries at odd seconds, in the compact format with keyed hashes."""
+    writer = Writer(
+        bytes.fromhex("00000000000000000000000000000002"),
+        bytes.fromhex("0000000000000000000000000000000b"),
+        compact=True,
+        keyed=True,
+    )
+
+    entries = [
+        common(b"cron.service", b"6", b"a" * 600, COMPRESSED_ZSTD),
+        common(b"cron.service", b"5", b"b" * 600, COMPRESSED_XZ)
+        + [(b"HUGE=" + b"\0" * OVERSIZED, COMPRESSED_ZSTD)],
+        common(b"cron.service", b"6", b"c" * 600, COMPRESSED_LZ4),
+    ]
+
+    for index, fields in enumerate(entries):
+        writer.entry(index + 1, REALTIME + 1_000_000 + 2_000_000 * index, MONOTONIC + index, fields)
+
+    writer.finish(path)
+
+
+if __name__ == "__main__":
+    directory = os.path.dirname(os.path.abspath(__file__))
+
+    regular(os.path.join(directory, "regular.journal"))
+    compact(os.path.join(directory, "compact.journal"))
@@ -0,0 +1,230 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{TimeZone, Utc};
+use eagle_core::{EagleClient, Source};
+use eyre::{bail, eyre, WrapErr};
+use rdkafka::{
+    config::ClientConfig,
+    consumer::{Consumer, StreamConsumer},
+    message::{BorrowedMessage, Message},
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::formats::kafka::{self, Encoding};
+
+/// What the consumed records hold.
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Payload {
+    #[default]
+    Logs,
+    Metrics,
+}
+
+/// Where a consumer group without committed offsets starts reading a partition.
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffsetReset {
+    Earliest,
+    #[default]
+    Latest,
+}
+
+/// Consumes records from Kafka topics as a member of a consumer group, turning them into logs
+/// or metrics, see `formats::kafka::Encoding`.
+///
+/// The offset of a record is committed once the record is handed to the engine, so records are
+/// consumed at least once: after a restart, the group resumes from its committed offsets.
+///
+/// Logs have the `topic`, `partition`, `offset` and `key` metadata fields, along with the
+/// `timestamp` of the record unless the log already had one. Records that can't be decoded are
+/// skipped.
+pub struct KafkaConsumer {
+    brokers: String,
+    group_id: String,
+    topics: Vec<String>,
+    payload: Payload,
+    encoding: Encoding,
+    category: String,
+    offset_reset: OffsetReset,
+    options: BTreeMap<String, String>,
+}
+
+impl KafkaConsumer {
+    /// `brokers` is a comma separated list of `host:port` addresses.
+    pub fn new(brokers: impl AsRef<str>, group_id: impl AsRef<str>, topics: Vec<String>) -> Self {
+        Self {
+            brokers: brokers.as_ref().to_string(),
+            group_id: group_id.as_ref().to_string(),
+            topics,
+            payload: Payload::default(),
+            encoding: Encoding::default(),
+            category: "kafka".to_string(),
+            offset_reset: OffsetReset::default(),
+            options: BTreeMap::new(),
+        }
+    }
+
+    pub fn payload(self, payload: Payload) -> Self {
+        Self { payload, ..self }
+    }
+
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Category of JSON metrics that don't have one. Defaults to `kafka`.
+    pub fn category(self, category: impl AsRef<str>) -> Self {
+        Self {
+            category: category.as_ref().to_string(),
+            ..self
+        }
+    }
+
+    pub fn offset_reset(self, offset_reset: OffsetReset) -> Self {
+        Self {
+            offset_reset,
+            ..self
+        }
+    }
+
+    /// Extra librdkafka consumer settings, like `security.protocol` or `sasl.mechanisms`. They
+    /// take precedence over the ones the source sets.
+    pub fn options(self, options: BTreeMap<String, String>) -> Self {
+        Self { options, ..self }
+    }
+
+    fn consumer(&self) -> eyre::Result<StreamConsumer> {
+        let offset_reset = match self.offset_reset {
+            OffsetReset::Earliest => "earliest",
+            OffsetReset::Latest => "latest",
+        };
+
+        let mut config = ClientConfig::new();
+
+        // Offsets are committed in the background, but only those stored after a record has
+        // been handled.
+        config
+            .set("bootstrap.servers", self.brokers.as_str())
+            .set("group.id", self.group_id.as_str())
+            .set("enable.auto.commit", "true")
+            .set("enable.auto.offset.store", "false")
+            .set("auto.offset.reset", offset_reset);
+
+        for (key, value) in self.options.iter() {
+            config.set(key.as_str(), value.as_str());
+        }
+
+        config
+            .create()
+            .map_err(|e| eyre!("Error when creating the Kafka consumer: {}", e))
+    }
+
+    async fn handle(
+        &self,
+        client: &EagleClient,
+        message: &BorrowedMessage<'_>,
+    ) -> eyre::Result<()> {
+        let payload = message.payload().unwrap_or_default();
+
+        match self.payload {
+            Payload::Metrics => {
+                match kafka::decode_metrics(self.encoding, self.category.as_str(), payload) {
+                    Ok(metrics) => client.send_metrics(metrics).await?,
+                    Err(e) => skip(client, message, e),
+                }
+            }
+
+            Payload::Logs => match kafka::decode_log(self.encoding, payload) {
+                Ok((log, metadata)) => {
+                    let metadata = record_metadata(message, metadata);
+
+                    client.send_log_with_metadata(log, metadata).await?;
+                }
+                Err(e) => skip(client, message, e),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+fn skip(client: &EagleClient, message: &BorrowedMessage<'_>, error: eyre::Report) {
+    tracing::warn!(
+        target = client.origin().instance_id(),
+        "Skipping record {} of {}/{}: {:?}",
+        message.offset(),
+        message.topic(),
+        message.partition(),
+        error
+    );
+}
+
+fn record_metadata(message: &BorrowedMessage<'_>, metadata: Value) -> Value {
+    let mut fields = match metadata {
+        Value::Object(fields) => fields,
+        _ => Map::new(),
+    };
+
+    let key = message
+        .key()
+        .map(|key| Value::String(String::from_utf8_lossy(key).into_owned()))
+        .unwrap_or_default();
+
+    fields.insert("topic".to_string(), message.topic().into());
+    fields.insert("partition".to_string(), message.partition().into());
+    fields.insert("offset".to_string(), message.offset().into());
+    fields.insert("key".to_string(), key);
+
+    let timestamp = message
+        .timestamp()
+        .to_millis()
+        .and_then(|millis| Utc.timestamp_millis_opt(millis).single());
+
+    if let Some(timestamp) = timestamp {
+        fields
+            .entry("timestamp")
+            .or_insert_with(|| Value::String(timestamp.to_rfc3339()));
+    }
+
+    Value::Object(fields)
+}
+
+#[async_trait::async_trait]
+impl Source for KafkaConsumer {
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        if self.topics.is_empty() {
+            bail!("No topic to consume");
+        }
+
+        let consumer = self.consumer()?;
+        let topics = self.topics.iter().map(String::as_str).collect::<Vec<_>>();
+
+        consumer
+            .subscribe(&topics)
+            .wrap_err_with(|| format!("Error when subscribing to {:?}", self.topics))?;
+
+        loop {
+            let message = match consumer.recv().await {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::error!(
+                        target = client.origin().instance_id(),
+                        "Error when consuming from Kafka: {}",
+                        e
+                    );
+
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            self.handle(&client, &message).await?;
+
+            consumer
+                .store_offset_from_message(&message)
+                .wrap_err("Error when storing a Kafka offset")?;
+        }
+    }
+}
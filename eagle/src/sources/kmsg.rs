@@ -0,0 +1,589 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use eagle_core::{EagleClient, Metric, MetricBuilder, Source};
+use eyre::{eyre, WrapErr};
+use regex::Regex;
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::sources::syslog::{FACILITIES, SEVERITIES};
+
+pub const DEFAULT_KMSG_PATH: &str = "/dev/kmsg";
+
+/// A read must fit a whole record or it fails, records being at most that large.
+const RECORD_MAX_SIZE: usize = 8_192;
+
+#[derive(Serialize)]
+struct Metadata {
+    facility: &'static str,
+    severity: &'static str,
+    sequence: u64,
+    /// Microseconds since boot.
+    monotonic_usec: u64,
+    timestamp: String,
+    /// Key/value pairs some drivers attach to their messages, like `SUBSYSTEM` or `DEVICE`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    fields: BTreeMap<String, String>,
+}
+
+struct Record {
+    metadata: Metadata,
+    message: String,
+    timestamp: DateTime<Utc>,
+}
+
+/// Kernel events we count on top of forwarding their messages.
+struct Patterns {
+    oom_kill: Regex,
+    block_error: Regex,
+    buffer_error: Regex,
+    filesystem_error: Regex,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        Self {
+            oom_kill: Regex::new(
+                r"^(Memory cgroup out of memory|Out of memory): Killed process \d+ \(([^)]*)\)",
+            )
+            .unwrap(),
+            block_error: Regex::new(
+                r"(I/O|critical medium|critical target|critical nexus|critical space allocation|recoverable transport|timeout) error, dev ([^,\s]+)",
+            )
+            .unwrap(),
+            buffer_error: Regex::new(r"^Buffer I/O error on dev(?:ice)? ([^,\s]+)").unwrap(),
+            filesystem_error: Regex::new(
+                r"^(?:(?:EXT[234]-fs|BTRFS) error \(device ([^)]+)\)|XFS \(([^)]+)\): .*[Ee]rror)",
+            )
+            .unwrap(),
+        }
+    }
+}
+
+/// Reads kernel messages from `/dev/kmsg` and sends them as logs. OOM kills and disk errors
+/// are also counted, as `kernel_oom_kills_total` and `kernel_disk_errors_total`.
+pub struct Kmsg {
+    path: PathBuf,
+    read_existing: bool,
+    patterns: Patterns,
+    counters: HashMap<(&'static str, BTreeMap<String, String>), f64>,
+}
+
+impl Default for Kmsg {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_KMSG_PATH),
+            read_existing: false,
+            patterns: Patterns::new(),
+            counters: HashMap::new(),
+        }
+    }
+}
+
+impl Kmsg {
+    pub fn path(self, path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            ..self
+        }
+    }
+
+    /// Also reads the messages already in the ring buffer when the source starts, instead of
+    /// only the new ones. They are read again on every restart.
+    pub fn read_existing(self, read_existing: bool) -> Self {
+        Self {
+            read_existing,
+            ..self
+        }
+    }
+
+    fn increment(
+        &mut self,
+        name: &'static str,
+        tags: BTreeMap<String, String>,
+        timestamp: DateTime<Utc>,
+    ) -> Metric {
+        let value = self.counters.entry((name, tags.clone())).or_default();
+
+        *value += 1f64;
+
+        MetricBuilder::counter("kernel", name, *value)
+            .tags(tags)
+            .timestamp(timestamp)
+            .build()
+    }
+
+    fn event_metrics(&mut self, record: &Record) -> Vec<Metric> {
+        let message = record.message.as_str();
+        let timestamp = record.timestamp;
+        let mut metrics = Vec::new();
+
+        if let Some(captures) = self.patterns.oom_kill.captures(message) {
+            let constraint = if captures[1].starts_with("Memory cgroup") {
+                "cgroup"
+            } else {
+                "global"
+            };
+
+            let tags = BTreeMap::from([
+                ("constraint".to_string(), constraint.to_string()),
+                ("process".to_string(), captures[2].to_string()),
+            ]);
+
+            metrics.push(self.increment("kernel_oom_kills_total", tags, timestamp));
+        }
+
+        let disk_error = if let Some(captures) = self.patterns.block_error.captures(message) {
+            let kind = match &captures[1] {
+                "I/O" => "io",
+                "critical medium" => "medium",
+                "timeout" => "timeout",
+                _ => "transport",
+            };
+
+            Some((captures[2].to_string(), kind))
+        } else if let Some(captures) = self.patterns.buffer_error.captures(message) {
+            Some((captures[1].to_string(), "buffer_io"))
+        } else {
+            self.patterns
+                .filesystem_error
+                .captures(message)
+                .and_then(|captures| captures.get(1).or_else(|| captures.get(2)))
+                .map(|device| (device.as_str().to_string(), "filesystem"))
+        };
+
+        if let Some((device, kind)) = disk_error {
+            let tags = BTreeMap::from([
+                ("device".to_string(), device),
+                ("kind".to_string(), kind.to_string()),
+            ]);
+
+            metrics.push(self.increment("kernel_disk_errors_total", tags, timestamp));
+        }
+
+        metrics
+    }
+}
+
+/// When the system booted according to the realtime clock. Recomputed for every record since
+/// the realtime clock can be adjusted while we run.
+fn boot_time() -> eyre::Result<DateTime<Utc>> {
+    let mut monotonic = std::mem::MaybeUninit::<libc::timespec>::uninit();
+
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, monotonic.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error()).wrap_err("Error when reading monotonic clock");
+    }
+
+    let monotonic = unsafe { monotonic.assume_init() };
+    let uptime = Duration::seconds(monotonic.tv_sec) + Duration::nanoseconds(monotonic.tv_nsec);
+
+    Ok(Utc::now() - uptime)
+}
+
+/// Parses `priority,sequence,timestamp,flags[,...];message` followed by optional ` KEY=value`
+/// lines.
+fn parse_record(input: &str, boot_time: DateTime<Utc>) -> eyre::Result<Record> {
+    let (header, rest) = input
+        .split_once(';')
+        .ok_or_else(|| eyre!("Missing record header"))?;
+
+    let mut header = header.split(',');
+    let mut next_number = |name: &str| {
+        header
+            .next()
+            .and_then(|value| value.parse::<u64>().ok())
+            .ok_or_else(|| eyre!("Invalid record {}", name))
+    };
+
+    let priority = next_number("priority")? as usize;
+    let sequence = next_number("sequence number")?;
+    let monotonic_usec = next_number("timestamp")?;
+
+    let mut lines = rest.trim_end_matches('\n').split('\n');
+    let message = unescape(lines.next().unwrap_or_default());
+    let mut fields = BTreeMap::new();
+
+    for line in lines {
+        if let Some((key, value)) = line.strip_prefix(' ').and_then(|line| line.split_once('=')) {
+            fields.insert(key.to_string(), unescape(value));
+        }
+    }
+
+    let timestamp = boot_time + Duration::microseconds(monotonic_usec as i64);
+
+    Ok(Record {
+        metadata: Metadata {
+            facility: FACILITIES.get(priority / 8).copied().unwrap_or("unknown"),
+            severity: SEVERITIES[priority % 8],
+            sequence,
+            monotonic_usec,
+            timestamp: timestamp.to_rfc3339(),
+            fields,
+        },
+        message,
+        timestamp,
+    })
+}
+
+/// The kernel escapes non-printable bytes as `\xHH`.
+fn unescape(input: &str) -> String {
+    if !input.contains("\\x") {
+        return input.to_string();
+    }
+
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut rest = input.as_bytes();
+
+    while let Some((byte, remaining)) = rest.split_first() {
+        if *byte == b'\\' && remaining.first() == Some(&b'x') {
+            let escaped = remaining
+                .get(1..3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+            if let Some(escaped) = escaped {
+                bytes.push(escaped);
+                rest = &remaining[3..];
+                continue;
+            }
+        }
+
+        bytes.push(*byte);
+        rest = remaining;
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[async_trait::async_trait]
+impl Source for Kmsg {
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        let mut file = tokio::fs::File::open(self.path.as_path())
+            .await
+            .wrap_err_with(|| format!("Error when opening {:?}", self.path))?;
+
+        if !self.read_existing {
+            file.seek(SeekFrom::End(0))
+                .await
+                .wrap_err_with(|| format!("Error when seeking to the end of {:?}", self.path))?;
+        }
+
+        let mut buffer = vec![0u8; RECORD_MAX_SIZE];
+
+        loop {
+            // Every read returns a single record.
+            let size = match file.read(&mut buffer).await {
+                Ok(0) => return Ok(()),
+                Ok(size) => size,
+                // Records were overwritten before we could read them, reading resumes with the
+                // oldest available one.
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                    tracing::warn!(
+                        target = client.origin().instance_id(),
+                        "Kernel messages were lost"
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e).wrap_err_with(|| format!("Error when reading {:?}", self.path))
+                }
+            };
+
+            let input = String::from_utf8_lossy(&buffer[..size]);
+            let record = match parse_record(input.as_ref(), boot_time()?) {
+                Ok(record) => record,
+                Err(e) => {
+                    tracing::warn!(
+                        target = client.origin().instance_id(),
+                        "Invalid kernel message '{}': {}",
+                        input.trim_end(),
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            let metrics = self.event_metrics(&record);
+
+            client
+                .send_log_with_metadata(record.message, record.metadata)
+                .await?;
+
+            if !metrics.is_empty() {
+                client.send_metrics(metrics).await?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn boot() -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000, 0).unwrap()
+    }
+
+    fn record(message: &str) -> Record {
+        parse_record(format!("6,1,0,-;{}\n", message).as_str(), boot()).unwrap()
+    }
+
+    #[test]
+    fn parses_records() {
+        let cases = vec![
+            (
+                "6,339,5140900,-;NET: Registered protocol family 10\n",
+                ("kern", "info", 339, 5_140_900),
+                "NET: Registered protocol family 10",
+                vec![],
+            ),
+            (
+                "30,1023,12345678,-;systemd[1]: Started Journal Service.\n",
+                ("daemon", "info", 1_023, 12_345_678),
+                "systemd[1]: Started Journal Service.",
+                vec![],
+            ),
+            (
+                "3,1004,8011209,-,caller=T1;usb 1-1: device descriptor read/64, error -71\n \
+                 SUBSYSTEM=usb\n DEVICE=+usb:1-1\n",
+                ("kern", "err", 1_004, 8_011_209),
+                "usb 1-1: device descriptor read/64, error -71",
+                vec![("DEVICE", "+usb:1-1"), ("SUBSYSTEM", "usb")],
+            ),
+            (
+                "12,7,0,c;tty: bell\\x07 and tab\\x09\n NAME=a\\x3db\n",
+                ("user", "warning", 7, 0),
+                "tty: bell\x07 and tab\t",
+                vec![("NAME", "a=b")],
+            ),
+            (
+                "191,8,1,-;local7 debug\n",
+                ("local7", "debug", 8, 1),
+                "local7 debug",
+                vec![],
+            ),
+        ];
+
+        for (input, (facility, severity, sequence, monotonic_usec), message, fields) in cases {
+            let record = parse_record(input, boot()).unwrap();
+            let fields = fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<BTreeMap<_, _>>();
+
+            assert_eq!(record.metadata.facility, facility, "parsing {}", input);
+            assert_eq!(record.metadata.severity, severity, "parsing {}", input);
+            assert_eq!(record.metadata.sequence, sequence, "parsing {}", input);
+            assert_eq!(
+                record.metadata.monotonic_usec, monotonic_usec,
+                "parsing {}",
+                input
+            );
+            assert_eq!(record.metadata.fields, fields, "parsing {}", input);
+            assert_eq!(record.message, message, "parsing {}", input);
+            assert_eq!(
+                record.timestamp,
+                boot() + Duration::microseconds(monotonic_usec as i64),
+                "parsing {}",
+                input
+            );
+            assert_eq!(record.metadata.timestamp, record.timestamp.to_rfc3339());
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_records() {
+        let cases = vec![
+            "NET: Registered protocol family 10\n",
+            "6,339;message\n",
+            "6,x,5140900,-;message\n",
+            "-1,339,5140900,-;message\n",
+            ";message\n",
+        ];
+
+        for input in cases {
+            assert!(parse_record(input, boot()).is_err(), "rejecting {}", input);
+        }
+    }
+
+    #[test]
+    fn unescapes_bytes() {
+        let cases = vec![
+            ("plain", "plain"),
+            ("a\\x20b", "a b"),
+            ("\\x1b[31mred\\x1b[0m", "\x1b[31mred\x1b[0m"),
+            ("\\xc3\\xa9t\\xc3\\xa9", "été"),
+            ("back\\slash", "back\\slash"),
+            ("truncated \\x4", "truncated \\x4"),
+            ("truncated \\x", "truncated \\x"),
+            ("invalid \\xzz", "invalid \\xzz"),
+            ("\\x4é", "\\x4é"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(unescape(input), expected, "unescaping {}", input);
+        }
+    }
+
+    /// Name, tags and value.
+    type Expected<'a> = Vec<(&'a str, Vec<(&'a str, &'a str)>, f64)>;
+
+    #[test]
+    fn counts_events() {
+        let cases: Vec<(&str, Expected)> = vec![
+            (
+                "Out of memory: Killed process 1234 (java) total-vm:8069092kB, anon-rss:3735856kB, file-rss:0kB, shmem-rss:0kB, UID:1000 pgtables:7580kB oom_score_adj:0",
+                vec![(
+                    "kernel_oom_kills_total",
+                    vec![("constraint", "global"), ("process", "java")],
+                    1f64,
+                )],
+            ),
+            (
+                "Memory cgroup out of memory: Killed process 4321 (nginx) total-vm:123456kB, anon-rss:65432kB, file-rss:0kB, shmem-rss:0kB",
+                vec![(
+                    "kernel_oom_kills_total",
+                    vec![("constraint", "cgroup"), ("process", "nginx")],
+                    1f64,
+                )],
+            ),
+            (
+                "blk_update_request: I/O error, dev sda, sector 123456 op 0x0:(READ) flags 0x0 phys_seg 1 prio class 0",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "sda"), ("kind", "io")],
+                    1f64,
+                )],
+            ),
+            (
+                "I/O error, dev nvme0n1, sector 2048 op 0x1:(WRITE) flags 0x800 phys_seg 1 prio class 2",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "nvme0n1"), ("kind", "io")],
+                    1f64,
+                )],
+            ),
+            (
+                "blk_update_request: critical medium error, dev sdb, sector 99 op 0x0:(READ) flags 0x0 phys_seg 1 prio class 0",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "sdb"), ("kind", "medium")],
+                    1f64,
+                )],
+            ),
+            (
+                "print_req_error: timeout error, dev sdc, sector 1",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "sdc"), ("kind", "timeout")],
+                    1f64,
+                )],
+            ),
+            (
+                "blk_update_request: recoverable transport error, dev sdd, sector 8",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "sdd"), ("kind", "transport")],
+                    1f64,
+                )],
+            ),
+            (
+                "Buffer I/O error on dev sda1, logical block 0, async page read",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "sda1"), ("kind", "buffer_io")],
+                    1f64,
+                )],
+            ),
+            (
+                "Buffer I/O error on device dm-0, logical block 1",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "dm-0"), ("kind", "buffer_io")],
+                    1f64,
+                )],
+            ),
+            (
+                "EXT4-fs error (device sda1): ext4_find_entry:1455: inode #2: comm ls: reading directory lblock 0",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "sda1"), ("kind", "filesystem")],
+                    1f64,
+                )],
+            ),
+            (
+                "XFS (dm-2): metadata I/O error in \"xfs_da_read_buf+0xd9/0x130 [xfs]\" at daddr 0x78 len 8 error 5",
+                vec![(
+                    "kernel_disk_errors_total",
+                    vec![("device", "dm-2"), ("kind", "filesystem")],
+                    1f64,
+                )],
+            ),
+            ("XFS (sdb1): Mounting V5 Filesystem", vec![]),
+            ("NET: Registered protocol family 10", vec![]),
+            ("oom_reaper: reaped process 1234 (java), now anon-rss:0kB", vec![]),
+        ];
+
+        for (message, expected) in cases {
+            let mut kmsg = Kmsg::default();
+            let metrics = kmsg.event_metrics(&record(message));
+            let metrics = metrics
+                .iter()
+                .map(|metric| {
+                    assert_eq!(metric.category, "kernel");
+                    assert_eq!(metric.r#type, eagle_core::MetricType::Counter);
+                    assert_eq!(metric.timestamp, boot());
+
+                    let tags = metric
+                        .tags
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.as_str()))
+                        .collect::<Vec<_>>();
+
+                    (metric.name.as_str(), tags, metric.value)
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(metrics, expected, "counting {}", message);
+        }
+    }
+
+    #[test]
+    fn increments_counters_per_tag_set() {
+        let mut kmsg = Kmsg::default();
+        let messages = [
+            "blk_update_request: I/O error, dev sda, sector 1",
+            "blk_update_request: I/O error, dev sdb, sector 1",
+            "blk_update_request: I/O error, dev sda, sector 2",
+            "Buffer I/O error on dev sda, logical block 0",
+            "blk_update_request: I/O error, dev sda, sector 3",
+        ];
+
+        let values = messages
+            .iter()
+            .map(|message| {
+                let metrics = kmsg.event_metrics(&record(message));
+
+                (metrics[0].tags["device"].clone(), metrics[0].value)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            values,
+            vec![
+                ("sda".to_string(), 1f64),
+                ("sdb".to_string(), 1f64),
+                ("sda".to_string(), 2f64),
+                ("sda".to_string(), 1f64),
+                ("sda".to_string(), 3f64),
+            ]
+        );
+    }
+}
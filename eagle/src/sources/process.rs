@@ -0,0 +1,441 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use eagle_core::{poll::PollingSource, Metric, MetricBuilder};
+use eyre::{bail, WrapErr};
+use regex::Regex;
+
+/// Selects processes. Every criterion set must match.
+pub struct ProcessMatcher {
+    group: String,
+    name: Option<Regex>,
+    cmdline: Option<Regex>,
+    user: Option<String>,
+    pid_file: Option<PathBuf>,
+    rollup: bool,
+}
+
+impl ProcessMatcher {
+    /// `group` is used as the `process` tag value of every matched process.
+    pub fn new(group: impl AsRef<str>) -> Self {
+        Self {
+            group: group.as_ref().to_string(),
+            name: None,
+            cmdline: None,
+            user: None,
+            pid_file: None,
+            rollup: false,
+        }
+    }
+
+    pub fn name(self, name: impl AsRef<str>) -> eyre::Result<Self> {
+        let name = Regex::new(name.as_ref())
+            .wrap_err_with(|| format!("Invalid process name regex '{}'", name.as_ref()))?;
+
+        Ok(Self {
+            name: Some(name),
+            ..self
+        })
+    }
+
+    pub fn cmdline(self, cmdline: impl AsRef<str>) -> eyre::Result<Self> {
+        let cmdline = Regex::new(cmdline.as_ref())
+            .wrap_err_with(|| format!("Invalid process cmdline regex '{}'", cmdline.as_ref()))?;
+
+        Ok(Self {
+            cmdline: Some(cmdline),
+            ..self
+        })
+    }
+
+    pub fn user(self, user: impl AsRef<str>) -> Self {
+        Self {
+            user: Some(user.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    pub fn pid_file(self, pid_file: impl AsRef<Path>) -> Self {
+        Self {
+            pid_file: Some(pid_file.as_ref().to_path_buf()),
+            ..self
+        }
+    }
+
+    /// Reports a single series for the whole group instead of one per pid.
+    pub fn rollup(self, rollup: bool) -> Self {
+        Self { rollup, ..self }
+    }
+
+    fn check(&self) -> eyre::Result<()> {
+        if self.name.is_none()
+            && self.cmdline.is_none()
+            && self.user.is_none()
+            && self.pid_file.is_none()
+        {
+            bail!(
+                "Process group '{}' needs at least one of name, cmdline, user or pid_file",
+                self.group
+            );
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, process: &ProcessInfo, pid_file: Option<i32>) -> bool {
+        if let Some(name) = self.name.as_ref() {
+            if !name.is_match(process.name.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(cmdline) = self.cmdline.as_ref() {
+            if !cmdline.is_match(process.cmdline.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(user) = self.user.as_ref() {
+            if process.user.as_ref() != Some(user) {
+                return false;
+            }
+        }
+
+        if self.pid_file.is_some() && pid_file != Some(process.pid) {
+            return false;
+        }
+
+        true
+    }
+}
+
+#[derive(Clone)]
+struct ProcessInfo {
+    pid: i32,
+    name: String,
+    cmdline: String,
+    user: Option<String>,
+    cpu_ticks: u64,
+    threads: u64,
+    start_ticks: u64,
+    virtual_bytes: u64,
+    resident_bytes: u64,
+    open_fds: Option<u64>,
+    read_bytes: Option<u64>,
+    written_bytes: Option<u64>,
+}
+
+struct System {
+    clock_ticks: f64,
+    page_size: u64,
+    boot_time: f64,
+    users: HashMap<u32, String>,
+}
+
+impl System {
+    fn load() -> eyre::Result<Self> {
+        let clock_ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        let stat =
+            std::fs::read_to_string("/proc/stat").wrap_err("Error when reading /proc/stat")?;
+
+        let boot_time = stat
+            .lines()
+            .find_map(|line| line.strip_prefix("btime "))
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .unwrap_or_default();
+
+        let users = std::fs::read_to_string("/etc/passwd")
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let uid = fields.nth(1)?.parse::<u32>().ok()?;
+
+                Some((uid, name.to_string()))
+            })
+            .collect();
+
+        Ok(Self {
+            clock_ticks,
+            page_size,
+            boot_time,
+            users,
+        })
+    }
+}
+
+fn read_process(system: &System, pid: i32) -> Option<ProcessInfo> {
+    let root = PathBuf::from(format!("/proc/{}", pid));
+    let stat = std::fs::read_to_string(root.join("stat")).ok()?;
+
+    // The process name is between parentheses and can contain spaces or parentheses itself.
+    let name_start = stat.find('(')?;
+    let name_end = stat.rfind(')')?;
+    let name = stat[name_start + 1..name_end].to_string();
+    let fields = stat[name_end + 1..].split_whitespace().collect::<Vec<_>>();
+    let field = |index: usize| fields.get(index).and_then(|v| v.parse::<u64>().ok());
+
+    let cmdline = std::fs::read(root.join("cmdline"))
+        .map(|bytes| {
+            String::from_utf8_lossy(&bytes)
+                .split('\0')
+                .filter(|arg| !arg.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default();
+
+    let user = std::fs::read_to_string(root.join("status"))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Uid:"))
+                .and_then(|uids| uids.split_whitespace().next())
+                .and_then(|uid| uid.parse::<u32>().ok())
+        })
+        .and_then(|uid| system.users.get(&uid).cloned());
+
+    Some(ProcessInfo {
+        pid,
+        name,
+        cmdline,
+        user,
+        cpu_ticks: field(11)? + field(12)?,
+        threads: field(17)?,
+        start_ticks: field(19)?,
+        virtual_bytes: field(20)?,
+        resident_bytes: field(21)? * system.page_size,
+        open_fds: None,
+        read_bytes: None,
+        written_bytes: None,
+    })
+}
+
+/// Those are only loaded for matched processes as they are more expensive to collect.
+fn read_resources(process: &mut ProcessInfo) {
+    let root = PathBuf::from(format!("/proc/{}", process.pid));
+
+    process.open_fds = std::fs::read_dir(root.join("fd"))
+        .ok()
+        .map(|entries| entries.count() as u64);
+
+    if let Ok(io) = std::fs::read_to_string(root.join("io")) {
+        let io_field = |name: &str| {
+            io.lines()
+                .find_map(|line| line.strip_prefix(name))
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+
+        process.read_bytes = io_field("read_bytes:");
+        process.written_bytes = io_field("write_bytes:");
+    }
+}
+
+fn read_pid_file(path: &Path) -> Option<i32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Returns, for each matcher, the processes it selected.
+fn scan(matchers: &[ProcessMatcher]) -> eyre::Result<(System, Vec<Vec<ProcessInfo>>)> {
+    let system = System::load()?;
+    let pid_files = matchers
+        .iter()
+        .map(|m| m.pid_file.as_deref().and_then(read_pid_file))
+        .collect::<Vec<_>>();
+
+    let mut groups = matchers.iter().map(|_| Vec::new()).collect::<Vec<_>>();
+
+    for entry in std::fs::read_dir("/proc").wrap_err("Error when listing /proc")? {
+        let pid = match entry
+            .ok()
+            .and_then(|e| e.file_name().to_str()?.parse::<i32>().ok())
+        {
+            Some(pid) => pid,
+            None => continue,
+        };
+
+        // Processes can exit while we are reading them.
+        let mut process = match read_process(&system, pid) {
+            Some(process) => process,
+            None => continue,
+        };
+
+        let matched = matchers
+            .iter()
+            .zip(pid_files.iter())
+            .map(|(matcher, pid_file)| matcher.matches(&process, *pid_file))
+            .collect::<Vec<_>>();
+
+        if !matched.contains(&true) {
+            continue;
+        }
+
+        read_resources(&mut process);
+
+        for (group, matched) in groups.iter_mut().zip(matched) {
+            if matched {
+                group.push(process.clone());
+            }
+        }
+    }
+
+    Ok((system, groups))
+}
+
+#[derive(Default)]
+struct Sample {
+    cpu_percent: Option<f64>,
+    virtual_bytes: f64,
+    resident_bytes: f64,
+    open_fds: f64,
+    threads: f64,
+    read_bytes: f64,
+    written_bytes: f64,
+    uptime: f64,
+}
+
+impl Sample {
+    fn add(&mut self, system: &System, process: &ProcessInfo, cpu_percent: Option<f64>) {
+        let started = system.boot_time + process.start_ticks as f64 / system.clock_ticks;
+        let now = chrono::Utc::now().timestamp_millis() as f64 / 1_000f64;
+
+        self.virtual_bytes += process.virtual_bytes as f64;
+        self.resident_bytes += process.resident_bytes as f64;
+        self.open_fds += process.open_fds.unwrap_or_default() as f64;
+        self.threads += process.threads as f64;
+        self.read_bytes += process.read_bytes.unwrap_or_default() as f64;
+        self.written_bytes += process.written_bytes.unwrap_or_default() as f64;
+        self.uptime = self.uptime.max(now - started);
+
+        if let Some(percent) = cpu_percent {
+            self.cpu_percent = Some(self.cpu_percent.unwrap_or_default() + percent);
+        }
+    }
+
+    fn into_metrics(self, tags: &[(&str, String)]) -> Vec<Metric> {
+        let mut metrics = vec![
+            MetricBuilder::gauge(
+                "process",
+                "process_virtual_memory_bytes",
+                self.virtual_bytes,
+            ),
+            MetricBuilder::gauge(
+                "process",
+                "process_resident_memory_bytes",
+                self.resident_bytes,
+            ),
+            MetricBuilder::gauge("process", "process_open_fds", self.open_fds),
+            MetricBuilder::gauge("process", "process_threads", self.threads),
+            MetricBuilder::counter("process", "process_read_bytes_total", self.read_bytes),
+            MetricBuilder::counter("process", "process_written_bytes_total", self.written_bytes),
+            MetricBuilder::gauge("process", "process_uptime_seconds", self.uptime),
+        ];
+
+        if let Some(percent) = self.cpu_percent {
+            metrics.push(MetricBuilder::gauge(
+                "process",
+                "process_cpu_percent",
+                percent,
+            ));
+        }
+
+        metrics
+            .into_iter()
+            .map(|metric| {
+                tags.iter()
+                    .fold(metric, |metric, (name, value)| {
+                        metric.add_tag(name, value.as_str())
+                    })
+                    .build()
+            })
+            .collect()
+    }
+}
+
+pub struct Processes {
+    matchers: Arc<Vec<ProcessMatcher>>,
+    cpu_ticks: HashMap<i32, u64>,
+    last_scan: Option<Instant>,
+}
+
+impl Processes {
+    pub fn new(matchers: Vec<ProcessMatcher>) -> eyre::Result<Self> {
+        for matcher in matchers.iter() {
+            matcher.check()?;
+        }
+
+        Ok(Self {
+            matchers: Arc::new(matchers),
+            cpu_ticks: HashMap::new(),
+            last_scan: None,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl PollingSource for Processes {
+    async fn poll(&mut self) -> eyre::Result<Vec<Metric>> {
+        let matchers = self.matchers.clone();
+        let (system, groups) = tokio::task::spawn_blocking(move || scan(&matchers))
+            .await
+            .wrap_err("Process scanning task panicked")??;
+
+        let now = Instant::now();
+        let elapsed = self.last_scan.map(|last| (now - last).as_secs_f64());
+        let mut cpu_ticks = HashMap::new();
+        let mut metrics = Vec::new();
+
+        for (matcher, processes) in self.matchers.iter().zip(groups.iter()) {
+            let mut rollup = Sample::default();
+
+            // Reported even when nothing matched, so a dead daemon can be noticed.
+            metrics.push(
+                MetricBuilder::gauge("process", "process_count", processes.len() as f64)
+                    .add_tag("process", matcher.group.as_str())
+                    .build(),
+            );
+
+            for process in processes.iter() {
+                let cpu_percent = match (self.cpu_ticks.get(&process.pid), elapsed) {
+                    (Some(previous), Some(elapsed)) if elapsed > 0f64 => {
+                        let used =
+                            process.cpu_ticks.saturating_sub(*previous) as f64 / system.clock_ticks;
+
+                        Some(used / elapsed * 100f64)
+                    }
+                    _ => None,
+                };
+
+                cpu_ticks.insert(process.pid, process.cpu_ticks);
+
+                if matcher.rollup {
+                    rollup.add(&system, process, cpu_percent);
+                    continue;
+                }
+
+                let mut sample = Sample::default();
+                sample.add(&system, process, cpu_percent);
+                metrics.extend(sample.into_metrics(&[
+                    ("process", matcher.group.clone()),
+                    ("pid", process.pid.to_string()),
+                ]));
+            }
+
+            if matcher.rollup && !processes.is_empty() {
+                metrics.extend(rollup.into_metrics(&[("process", matcher.group.clone())]));
+            }
+        }
+
+        self.cpu_ticks = cpu_ticks;
+        self.last_scan = Some(now);
+
+        Ok(metrics)
+    }
+}
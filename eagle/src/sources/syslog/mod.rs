@@ -0,0 +1,374 @@
+mod parser;
+
+use std::{net::SocketAddr, path::Path};
+
+use eagle_core::{EagleClient, Source};
+use eyre::{bail, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
+    net::{TcpListener, UdpSocket},
+};
+use tokio_rustls::TlsAcceptor;
+
+pub(crate) use self::parser::{FACILITIES, SEVERITIES};
+
+use self::parser::Metadata;
+
+/// Largest octet count prefix we accept, as in `65536 <34>1 ...`.
+const MAX_FRAME_LENGTH_DIGITS: u64 = 10;
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    #[serde(flatten)]
+    metadata: Metadata,
+    peer: &'a str,
+}
+
+/// Receives syslog messages and sends them as logs. The message is the log value while the
+/// header fields end up in the log metadata.
+pub struct Syslog {
+    address: String,
+    transport: Transport,
+    tls: Option<TlsAcceptor>,
+    max_message_size: usize,
+}
+
+impl Syslog {
+    pub fn new(address: impl AsRef<str>, transport: Transport) -> Self {
+        Self {
+            address: address.as_ref().to_string(),
+            transport,
+            tls: None,
+            max_message_size: 64 * 1_024,
+        }
+    }
+
+    /// Only applies to the TCP transport.
+    pub fn tls(
+        self,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> eyre::Result<Self> {
+        Ok(Self {
+            tls: Some(crate::tls::acceptor(cert_path, key_path)?),
+            ..self
+        })
+    }
+
+    /// Longer messages are dropped, along with the connection they came from when using TCP.
+    pub fn max_message_size(self, max_message_size: usize) -> Self {
+        Self {
+            max_message_size,
+            ..self
+        }
+    }
+
+    async fn listen_udp(&self, client: EagleClient) -> eyre::Result<()> {
+        let socket = UdpSocket::bind(self.address.as_str())
+            .await
+            .wrap_err_with(|| format!("Error when binding UDP socket on {}", self.address))?;
+
+        receive_datagrams(&socket, &client, self.max_message_size).await
+    }
+
+    async fn listen_tcp(&self, client: EagleClient) -> eyre::Result<()> {
+        let listener = TcpListener::bind(self.address.as_str())
+            .await
+            .wrap_err_with(|| format!("Error when binding TCP listener on {}", self.address))?;
+
+        loop {
+            let (stream, peer) = listener
+                .accept()
+                .await
+                .wrap_err("Error when accepting TCP connection")?;
+
+            let client = client.clone();
+            let tls = self.tls.clone();
+            let max_message_size = self.max_message_size;
+
+            tokio::spawn(async move {
+                let result = match tls {
+                    None => handle_connection(stream, peer, &client, max_message_size).await,
+                    Some(acceptor) => match acceptor.accept(stream).await {
+                        Ok(stream) => {
+                            handle_connection(stream, peer, &client, max_message_size).await
+                        }
+                        Err(e) => Err(e).wrap_err("TLS handshake failed"),
+                    },
+                };
+
+                if let Err(e) = result {
+                    tracing::warn!(
+                        target = client.origin().instance_id(),
+                        "Syslog connection from {} closed: {:?}",
+                        peer,
+                        e
+                    );
+                }
+            });
+        }
+    }
+}
+
+async fn receive_datagrams(
+    socket: &UdpSocket,
+    client: &EagleClient,
+    max_message_size: usize,
+) -> eyre::Result<()> {
+    // One extra byte tells datagrams that were truncated to fit the buffer.
+    let mut buffer = vec![0u8; max_message_size + 1];
+
+    loop {
+        let (size, peer) = socket
+            .recv_from(&mut buffer)
+            .await
+            .wrap_err("Error when receiving UDP datagram")?;
+
+        if size > max_message_size {
+            tracing::warn!(
+                target = client.origin().instance_id(),
+                "Message from {} dropped, it exceeds the size limit of {} bytes",
+                peer,
+                max_message_size
+            );
+
+            continue;
+        }
+
+        let message = String::from_utf8_lossy(&buffer[..size]);
+
+        send_message(client, message.as_ref(), peer).await?;
+    }
+}
+
+async fn send_message(client: &EagleClient, raw: &str, peer: SocketAddr) -> eyre::Result<()> {
+    let peer = peer.to_string();
+
+    match parser::parse(raw) {
+        Ok(message) => {
+            client
+                .send_log_with_metadata(
+                    message.message,
+                    Envelope {
+                        metadata: message.metadata,
+                        peer: peer.as_str(),
+                    },
+                )
+                .await
+        }
+
+        // We'd rather forward a message we don't understand than lose it.
+        Err(e) => {
+            client
+                .send_log_with_metadata(
+                    raw.trim_end(),
+                    json!({ "peer": peer, "parse_error": format!("{}", e) }),
+                )
+                .await
+        }
+    }
+}
+
+async fn handle_connection<S>(
+    stream: S,
+    peer: SocketAddr,
+    client: &EagleClient,
+    max_message_size: usize,
+) -> eyre::Result<()>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut reader = BufReader::new(stream);
+
+    while let Some(frame) = read_frame(&mut reader, max_message_size).await? {
+        if frame.trim().is_empty() {
+            continue;
+        }
+
+        send_message(client, frame.as_str(), peer).await?;
+    }
+
+    Ok(())
+}
+
+/// Reads a single message using either octet counting or newline framing (RFC6587). Octet
+/// counted frames start with a digit while messages start with `<`, so both can be told apart.
+async fn read_frame<R>(reader: &mut R, max_message_size: usize) -> eyre::Result<Option<String>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let first = match reader.fill_buf().await?.first() {
+        Some(first) => *first,
+        None => return Ok(None),
+    };
+
+    if first.is_ascii_digit() {
+        let mut length = Vec::new();
+
+        (&mut *reader)
+            .take(MAX_FRAME_LENGTH_DIGITS + 1)
+            .read_until(b' ', &mut length)
+            .await?;
+
+        // Without the trailing space, the length has too many digits or the stream ended.
+        let length = std::str::from_utf8(&length)
+            .ok()
+            .and_then(|length| length.strip_suffix(' '))
+            .and_then(|length| length.parse::<usize>().ok());
+
+        let length = match length {
+            Some(length) => length,
+            None => bail!("Invalid octet counting frame length"),
+        };
+
+        if length > max_message_size {
+            bail!("Message of {} bytes exceeds the size limit", length);
+        }
+
+        let mut frame = vec![0u8; length];
+
+        reader.read_exact(&mut frame).await?;
+
+        return Ok(Some(String::from_utf8_lossy(&frame).into_owned()));
+    }
+
+    let mut line = Vec::new();
+
+    (&mut *reader)
+        .take(max_message_size as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+
+    if line.len() > max_message_size {
+        bail!(
+            "Message exceeds the size limit of {} bytes",
+            max_message_size
+        );
+    }
+
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+#[async_trait::async_trait]
+impl Source for Syslog {
+    async fn produce(&mut self, client: EagleClient) -> eyre::Result<()> {
+        match self.transport {
+            Transport::Udp => self.listen_udp(client).await,
+            Transport::Tcp => self.listen_tcp(client).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use eagle_core::{poll::Schedule, EagleEndpoint, Event, Origin};
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    async fn frames(input: &[u8], max_message_size: usize) -> eyre::Result<Vec<String>> {
+        let mut reader = input;
+        let mut frames = Vec::new();
+
+        while let Some(frame) = read_frame(&mut reader, max_message_size).await? {
+            frames.push(frame);
+        }
+
+        Ok(frames)
+    }
+
+    #[tokio::test]
+    async fn reads_newline_frames() {
+        let frames = frames(b"<13>first\n<13>second\r\n<13>last", 64)
+            .await
+            .unwrap();
+
+        assert_eq!(frames, vec!["<13>first\n", "<13>second\r\n", "<13>last"]);
+    }
+
+    #[tokio::test]
+    async fn reads_octet_counted_frames() {
+        let input = b"11 <13>a\nb c d13 <13>1 - - - -<13>newline\n";
+        let frames = frames(input, 64).await.unwrap();
+
+        assert_eq!(
+            frames,
+            vec!["<13>a\nb c d", "<13>1 - - - -", "<13>newline\n"]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_frames() {
+        let cases: [(&[u8], &str); 5] = [
+            (b"12x <13>message", "Invalid octet counting frame length"),
+            (b"12", "Invalid octet counting frame length"),
+            (b"12345678901 <13>", "Invalid octet counting frame length"),
+            (b"65 <13>message", "exceeds the size limit"),
+            (
+                b"<13>a message longer than the limit\n",
+                "exceeds the size limit",
+            ),
+        ];
+
+        for (input, error) in cases {
+            let actual = format!("{}", frames(input, 16).await.unwrap_err());
+
+            assert!(
+                actual.contains(error),
+                "{}: {}",
+                String::from_utf8_lossy(input),
+                actual
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_truncated_octet_counted_frames() {
+        assert!(frames(b"10 <13>abc", 64).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn drops_oversized_datagrams() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let client = EagleClient {
+            origin: Arc::new(Origin::new("syslog")),
+            endpoint: EagleEndpoint::new(sender),
+            schedule: Schedule::default(),
+        };
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        tokio::spawn(async move { receive_datagrams(&socket, &client, 32).await });
+
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let messages = [
+            "<13>Oct 11 22:14:15 host app: this message is too long",
+            "<13>Oct 11 22:14:15 host app: ok",
+        ];
+
+        for message in messages {
+            peer.send_to(message.as_bytes(), address).await.unwrap();
+        }
+
+        let event = receiver.recv().await.unwrap();
+
+        match event.event {
+            Event::Log(log) => assert_eq!(log.inner.as_ref(), &json!("ok")),
+            other => panic!("Unexpected event {:?}", other),
+        }
+
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone};
+use eyre::{bail, eyre, WrapErr};
+use serde::Serialize;
+
+pub(crate) const FACILITIES: &[&str] = &[
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+pub(crate) const SEVERITIES: &[&str] = &[
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+const NIL: &str = "-";
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rfc {
+    Rfc3164,
+    Rfc5424,
+}
+
+/// Everything but the message itself, which is sent as the log value.
+#[derive(Serialize)]
+pub struct Metadata {
+    pub rfc: Rfc,
+    pub facility: &'static str,
+    pub severity: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proc_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub msg_id: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub structured_data: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+pub struct Message {
+    pub metadata: Metadata,
+    pub message: String,
+}
+
+/// Parses either a RFC5424 or a RFC3164 message, the former being recognized by its version
+/// number right after the priority.
+pub fn parse(input: &str) -> eyre::Result<Message> {
+    let input = input.trim_end_matches(['\r', '\n', '\0']);
+    let rest = input
+        .strip_prefix('<')
+        .ok_or_else(|| eyre!("Missing priority"))?;
+
+    let (priority, rest) = rest
+        .split_once('>')
+        .ok_or_else(|| eyre!("Unterminated priority"))?;
+
+    let priority = priority
+        .parse::<usize>()
+        .wrap_err_with(|| format!("Invalid priority '{}'", priority))?;
+
+    let facility = FACILITIES
+        .get(priority / 8)
+        .ok_or_else(|| eyre!("Invalid facility in priority {}", priority))?;
+
+    let severity = SEVERITIES[priority % 8];
+
+    match rest.strip_prefix("1 ") {
+        Some(rest) => parse_rfc5424(facility, severity, rest),
+        None => Ok(parse_rfc3164(facility, severity, rest)),
+    }
+}
+
+fn nil_or(value: &str) -> Option<String> {
+    if value == NIL {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn next_field<'a>(rest: &mut &'a str) -> eyre::Result<&'a str> {
+    let (field, remaining) = rest.split_once(' ').unwrap_or((rest, ""));
+
+    if field.is_empty() {
+        bail!("Missing header field");
+    }
+
+    *rest = remaining;
+    Ok(field)
+}
+
+fn parse_rfc5424(
+    facility: &'static str,
+    severity: &'static str,
+    mut rest: &str,
+) -> eyre::Result<Message> {
+    let timestamp = next_field(&mut rest)?;
+    let timestamp = match timestamp {
+        NIL => None,
+        timestamp => Some(
+            DateTime::parse_from_rfc3339(timestamp)
+                .wrap_err_with(|| format!("Invalid timestamp '{}'", timestamp))?
+                .to_rfc3339(),
+        ),
+    };
+
+    let hostname = nil_or(next_field(&mut rest)?);
+    let app_name = nil_or(next_field(&mut rest)?);
+    let proc_id = nil_or(next_field(&mut rest)?);
+    let msg_id = nil_or(next_field(&mut rest)?);
+
+    let (structured_data, rest) = if let Some(remaining) = rest.strip_prefix(NIL) {
+        (BTreeMap::new(), remaining)
+    } else {
+        parse_structured_data(rest)?
+    };
+
+    let message = rest.strip_prefix(' ').unwrap_or(rest);
+    // Messages can be prefixed by a UTF-8 BOM.
+    let message = message.strip_prefix('\u{feff}').unwrap_or(message);
+
+    Ok(Message {
+        metadata: Metadata {
+            rfc: Rfc::Rfc5424,
+            facility,
+            severity,
+            timestamp,
+            hostname,
+            app_name,
+            proc_id,
+            msg_id,
+            structured_data,
+        },
+        message: message.to_string(),
+    })
+}
+
+type StructuredData = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Parses `[id param="value" ...][id2 ...]` and returns what remains after it.
+fn parse_structured_data(mut rest: &str) -> eyre::Result<(StructuredData, &str)> {
+    let mut data = BTreeMap::new();
+
+    while let Some(remaining) = rest.strip_prefix('[') {
+        let id_end = remaining
+            .find([' ', ']'])
+            .ok_or_else(|| eyre!("Unterminated structured data element"))?;
+
+        let id = &remaining[..id_end];
+        let mut params = BTreeMap::new();
+
+        rest = &remaining[id_end..];
+
+        loop {
+            rest = rest.trim_start_matches(' ');
+
+            if let Some(remaining) = rest.strip_prefix(']') {
+                rest = remaining;
+                break;
+            }
+
+            let (name, remaining) = rest
+                .split_once("=\"")
+                .ok_or_else(|| eyre!("Invalid structured data parameter in '{}'", id))?;
+
+            let mut value = String::new();
+            let mut chars = remaining.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c @ ('"' | '\\' | ']'))) => value.push(c),
+                        Some((_, c)) => {
+                            value.push('\\');
+                            value.push(c);
+                        }
+                        None => bail!("Unterminated structured data parameter"),
+                    },
+                    Some((index, '"')) => break index,
+                    Some((_, c)) => value.push(c),
+                    None => bail!("Unterminated structured data parameter"),
+                }
+            };
+
+            params.insert(name.to_string(), value);
+            rest = &remaining[end + 1..];
+        }
+
+        data.insert(id.to_string(), params);
+    }
+
+    Ok((data, rest))
+}
+
+/// RFC3164 is more of a description of existing practices than a standard, so this is lenient:
+/// the timestamp and hostname are optional, and the tag is only extracted when it looks like one.
+fn parse_rfc3164(facility: &'static str, severity: &'static str, rest: &str) -> Message {
+    let (timestamp, mut rest) = match parse_bsd_timestamp(rest) {
+        Some((timestamp, remaining)) => (Some(timestamp), remaining.trim_start()),
+        None => (None, rest),
+    };
+
+    let mut hostname = None;
+
+    // When the first word is followed by another word and isn't a tag, it's the hostname.
+    if timestamp.is_some() {
+        if let Some((word, remaining)) = rest.split_once(' ') {
+            if !looks_like_tag(word) {
+                hostname = Some(word.to_string());
+                rest = remaining;
+            }
+        }
+    }
+
+    let (app_name, proc_id, message) = match split_tag(rest) {
+        Some((app_name, proc_id, message)) => (Some(app_name), proc_id, message),
+        None => (None, None, rest),
+    };
+
+    Message {
+        metadata: Metadata {
+            rfc: Rfc::Rfc3164,
+            facility,
+            severity,
+            timestamp,
+            hostname,
+            app_name,
+            proc_id,
+            msg_id: None,
+            structured_data: BTreeMap::new(),
+        },
+        message: message.to_string(),
+    }
+}
+
+fn looks_like_tag(word: &str) -> bool {
+    word.ends_with(':') || word.contains('[')
+}
+
+/// Splits `app[pid]: message` or `app: message`.
+fn split_tag(input: &str) -> Option<(String, Option<String>, &str)> {
+    let (tag, message) = input.split_once(':')?;
+
+    if tag.is_empty() || tag.contains(' ') {
+        return None;
+    }
+
+    let message = message.strip_prefix(' ').unwrap_or(message);
+
+    match tag.split_once('[') {
+        Some((app_name, proc_id)) => Some((
+            app_name.to_string(),
+            Some(proc_id.trim_end_matches(']').to_string()),
+            message,
+        )),
+        None => Some((tag.to_string(), None, message)),
+    }
+}
+
+/// `Mmm dd hh:mm:ss`, without a year nor timezone. We assume the local timezone and the current
+/// year, unless that would put the message in the future, e.g. around new year.
+fn parse_bsd_timestamp(input: &str) -> Option<(String, &str)> {
+    let raw = input.get(..15)?;
+    let now = Local::now();
+    let with_year = |year: i32| {
+        NaiveDateTime::parse_from_str(
+            format!("{} {}", year, raw.replace("  ", " ")).as_str(),
+            "%Y %b %d %H:%M:%S",
+        )
+        .ok()
+        .and_then(|naive| Local.from_local_datetime(&naive).single())
+    };
+
+    let mut timestamp = with_year(now.year())?;
+
+    if timestamp > now + chrono::Duration::days(1) {
+        timestamp = with_year(now.year() - 1)?;
+    }
+
+    Some((timestamp.to_rfc3339(), &input[15..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Timelike;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn metadata(message: &Message) -> Value {
+        serde_json::to_value(&message.metadata).unwrap()
+    }
+
+    fn local_time(timestamp: &Value) -> (u32, u32, u32, u32, u32) {
+        let timestamp = DateTime::parse_from_rfc3339(timestamp.as_str().unwrap()).unwrap();
+
+        (
+            timestamp.month(),
+            timestamp.day(),
+            timestamp.hour(),
+            timestamp.minute(),
+            timestamp.second(),
+        )
+    }
+
+    #[test]
+    fn parses_rfc3164() {
+        let cases = [
+            (
+                "<34>Oct 11 22:14:15 mymachine su: 'su root' failed for lonvick on /dev/pts/8",
+                (10, 11, 22, 14, 15),
+                json!({
+                    "rfc": "rfc3164",
+                    "facility": "auth",
+                    "severity": "crit",
+                    "hostname": "mymachine",
+                    "app_name": "su",
+                }),
+                "'su root' failed for lonvick on /dev/pts/8",
+            ),
+            (
+                "<189>Mar  1 18:48:50 router1 %SYS-5-CONFIG_I: Configured from console by vty2 (10.34.195.36)\r\n",
+                (3, 1, 18, 48, 50),
+                json!({
+                    "rfc": "rfc3164",
+                    "facility": "local7",
+                    "severity": "notice",
+                    "hostname": "router1",
+                    "app_name": "%SYS-5-CONFIG_I",
+                }),
+                "Configured from console by vty2 (10.34.195.36)",
+            ),
+            (
+                "<38>Feb  5 07:01:02 sshd[1234]: Accepted publickey for root",
+                (2, 5, 7, 1, 2),
+                json!({
+                    "rfc": "rfc3164",
+                    "facility": "auth",
+                    "severity": "info",
+                    "app_name": "sshd",
+                    "proc_id": "1234",
+                }),
+                "Accepted publickey for root",
+            ),
+        ];
+
+        for (input, time, expected, text) in cases {
+            let message = parse(input).unwrap();
+            let mut actual = metadata(&message);
+            let timestamp = actual.as_object_mut().unwrap().remove("timestamp").unwrap();
+
+            assert_eq!(local_time(&timestamp), time, "{}", input);
+            assert_eq!(actual, expected, "{}", input);
+            assert_eq!(message.message, text, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_rfc3164_without_header() {
+        let cases = [
+            ("<13>just a message", json!({}), "just a message"),
+            (
+                "<13>cron: job started",
+                json!({ "app_name": "cron" }),
+                "job started",
+            ),
+            (
+                "<13>a note: with a colon",
+                json!({}),
+                "a note: with a colon",
+            ),
+        ];
+
+        for (input, fields, text) in cases {
+            let message = parse(input).unwrap();
+            let mut expected = json!({
+                "rfc": "rfc3164",
+                "facility": "user",
+                "severity": "notice",
+            });
+
+            expected
+                .as_object_mut()
+                .unwrap()
+                .extend(fields.as_object().unwrap().clone());
+
+            assert_eq!(metadata(&message), expected, "{}", input);
+            assert_eq!(message.message, text, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_rfc5424() {
+        let input = concat!(
+            "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog 8710 ID47 ",
+            r#"[exampleSDID@32473 iut="3" eventSource="Application" eventID="1011"]"#,
+            r#"[examplePriority@32473 class="high" escaped="a\"b\\c\]d\x"]"#,
+            " \u{feff}An application event log entry\n",
+        );
+
+        let message = parse(input).unwrap();
+
+        assert_eq!(
+            metadata(&message),
+            json!({
+                "rfc": "rfc5424",
+                "facility": "local4",
+                "severity": "notice",
+                "timestamp": "2003-10-11T22:14:15.003+00:00",
+                "hostname": "mymachine.example.com",
+                "app_name": "evntslog",
+                "proc_id": "8710",
+                "msg_id": "ID47",
+                "structured_data": {
+                    "exampleSDID@32473": {
+                        "iut": "3",
+                        "eventSource": "Application",
+                        "eventID": "1011",
+                    },
+                    "examplePriority@32473": {
+                        "class": "high",
+                        "escaped": "a\"b\\c]d\\x",
+                    },
+                },
+            })
+        );
+
+        assert_eq!(message.message, "An application event log entry");
+    }
+
+    #[test]
+    fn parses_rfc5424_nil_values() {
+        let cases = [
+            ("<14>1 - - - - - -", ""),
+            ("<14>1 - - - - - - message", "message"),
+            ("<14>1 - - - - - [id] message", "message"),
+        ];
+
+        for (input, text) in cases {
+            let message = parse(input).unwrap();
+            let mut expected = json!({
+                "rfc": "rfc5424",
+                "facility": "user",
+                "severity": "info",
+            });
+
+            if input.contains("[id]") {
+                expected["structured_data"] = json!({ "id": {} });
+            }
+
+            assert_eq!(metadata(&message), expected, "{}", input);
+            assert_eq!(message.message, text, "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let cases = [
+            ("no priority", "Missing priority"),
+            ("<34 unterminated", "Unterminated priority"),
+            ("<>1 - - - - - -", "Invalid priority"),
+            ("<abc>1 - - - - - -", "Invalid priority"),
+            ("<-1>1 - - - - - -", "Invalid priority"),
+            ("<192>1 - - - - - -", "Invalid facility"),
+            ("<34>1 yesterday - - - - -", "Invalid timestamp"),
+            ("<34>1 - host", "Missing header field"),
+            (
+                "<34>1 - - - - - [id",
+                "Unterminated structured data element",
+            ),
+            (
+                "<34>1 - - - - - [id a]",
+                "Invalid structured data parameter",
+            ),
+            (
+                r#"<34>1 - - - - - [id a="b"#,
+                "Unterminated structured data parameter",
+            ),
+        ];
+
+        for (input, error) in cases {
+            let actual = match parse(input) {
+                Ok(_) => panic!("{} was parsed", input),
+                Err(e) => format!("{}", e),
+            };
+
+            assert!(actual.contains(error), "{}: {}", input, actual);
+        }
+    }
+}
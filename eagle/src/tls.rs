@@ -0,0 +1,52 @@
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+
+use eyre::{bail, WrapErr};
+use tokio_rustls::{
+    rustls::{Certificate, PrivateKey, ServerConfig},
+    TlsAcceptor,
+};
+
+/// Builds a TLS acceptor out of PEM encoded certificate chain and private key files.
+pub fn acceptor(
+    cert_path: impl AsRef<Path>,
+    key_path: impl AsRef<Path>,
+) -> eyre::Result<TlsAcceptor> {
+    let cert_path = cert_path.as_ref();
+    let key_path = key_path.as_ref();
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(cert_path).wrap_err_with(|| format!("Error when opening {:?}", cert_path))?,
+    ))
+    .wrap_err_with(|| format!("Invalid certificate file {:?}", cert_path))?
+    .into_iter()
+    .map(Certificate)
+    .collect::<Vec<_>>();
+
+    if certs.is_empty() {
+        bail!("No certificate found in {:?}", cert_path);
+    }
+
+    let mut reader = BufReader::new(
+        File::open(key_path).wrap_err_with(|| format!("Error when opening {:?}", key_path))?,
+    );
+
+    let key = loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .wrap_err_with(|| format!("Invalid private key file {:?}", key_path))?
+        {
+            Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => break PrivateKey(key),
+            Some(_) => continue,
+            None => bail!("No private key found in {:?}", key_path),
+        }
+    };
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .wrap_err("Invalid TLS certificate or private key")?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
@@ -1 +1,5 @@
+pub mod cardinality;
+pub mod rate;
+pub mod relabel;
+pub mod script;
 pub mod tags;
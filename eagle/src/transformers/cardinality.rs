@@ -0,0 +1,387 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eagle_core::{Metric, MetricBuilder, Origin, Transformer};
+use serde::{Deserialize, Serialize};
+
+#[derive(Eq, PartialEq, Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitAction {
+    /// Drops any series we haven't seen before once the limit is reached.
+    Drop,
+    /// Removes the tags with the most distinct values until the series is a
+    /// known one. Series reduced that way get their own budget of `limit`
+    /// series and are dropped past it.
+    StripTag,
+}
+
+#[derive(Default)]
+struct MetricSeries {
+    series: HashSet<u64>,
+    stripped: HashSet<u64>,
+    tag_values: HashMap<String, HashSet<u64>>,
+    /// How many series were dropped and how many times each tag was
+    /// stripped since the transformer started.
+    dropped: u64,
+    stripped_tags: BTreeMap<String, u64>,
+}
+
+impl MetricSeries {
+    fn track(&mut self, series: u64, tags: &BTreeMap<String, String>, limit: usize) {
+        self.series.insert(series);
+
+        for (name, value) in tags.iter() {
+            let values = self.tag_values.entry(name.clone()).or_default();
+
+            // Past the limit, we already know the tag is a problem.
+            if values.len() <= limit {
+                values.insert(hash(value));
+            }
+        }
+    }
+
+    fn worst_tag(&self, tags: &BTreeMap<String, String>) -> Option<String> {
+        tags.keys()
+            .max_by_key(|name| self.tag_values.get(*name).map_or(0, |v| v.len()))
+            .cloned()
+    }
+}
+
+/// Limits how many distinct tag sets a metric name can have.
+///
+/// The transformer reports what it limited through the pipeline, as counters
+/// in the `cardinality` category emitted along with the metrics going through
+/// it, at most once per report interval: `series_dropped` tagged with the
+/// `metric` name, and `tags_stripped` tagged with the `metric` name and the
+/// stripped `tag`. Metric names that were never limited aren't reported.
+pub struct Cardinality {
+    limit: usize,
+    action: LimitAction,
+    metrics: HashMap<String, MetricSeries>,
+    report_interval: Duration,
+    last_report: Instant,
+}
+
+impl Cardinality {
+    pub fn new(limit: usize, action: LimitAction) -> Self {
+        Self {
+            limit,
+            action,
+            metrics: HashMap::new(),
+            report_interval: Duration::from_secs(60),
+            last_report: Instant::now(),
+        }
+    }
+
+    /// Defaults to a minute.
+    pub fn report_interval(self, report_interval: Duration) -> Self {
+        Self {
+            report_interval,
+            ..self
+        }
+    }
+
+    fn report(&self) -> Vec<Metric> {
+        let mut report = Vec::new();
+
+        for (name, state) in self.metrics.iter() {
+            if state.dropped > 0 {
+                report.push(
+                    MetricBuilder::counter("cardinality", "series_dropped", state.dropped as f64)
+                        .add_tag("metric", name)
+                        .build(),
+                );
+            }
+
+            for (tag, count) in state.stripped_tags.iter() {
+                report.push(
+                    MetricBuilder::counter("cardinality", "tags_stripped", *count as f64)
+                        .add_tag("metric", name)
+                        .add_tag("tag", tag)
+                        .build(),
+                );
+            }
+        }
+
+        report
+    }
+}
+
+fn hash<H: Hash>(value: &H) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Transformer for Cardinality {
+    fn transform(&mut self, _origin: Arc<Origin>, mut metric: Metric) -> Option<Metric> {
+        let state = self.metrics.entry(metric.name.clone()).or_default();
+        let series = hash(&metric.tags);
+
+        if state.series.contains(&series) {
+            return Some(metric);
+        }
+
+        if state.series.len() < self.limit {
+            state.track(series, &metric.tags, self.limit);
+            return Some(metric);
+        }
+
+        if self.action == LimitAction::StripTag {
+            let mut stripped = Vec::new();
+
+            while let Some(tag) = state.worst_tag(&metric.tags) {
+                metric.tags.remove(tag.as_str());
+                stripped.push(tag);
+
+                let reduced = hash(&metric.tags);
+                let admitted = state.series.contains(&reduced)
+                    || state.stripped.contains(&reduced)
+                    || (state.stripped.len() < self.limit && state.stripped.insert(reduced));
+
+                if !admitted {
+                    continue;
+                }
+
+                for tag in stripped {
+                    *state.stripped_tags.entry(tag).or_default() += 1;
+                }
+
+                return Some(metric);
+            }
+        }
+
+        tracing::debug!(
+            "Series of metric {}:{} dropped, cardinality limit of {} reached",
+            metric.category,
+            metric.name,
+            self.limit
+        );
+
+        state.dropped += 1;
+
+        None
+    }
+
+    fn transform_many(&mut self, origin: Arc<Origin>, metric: Metric) -> Vec<Metric> {
+        let mut metrics = self
+            .transform(origin, metric)
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        if self.last_report.elapsed() >= self.report_interval {
+            self.last_report = Instant::now();
+            metrics.extend(self.report());
+        }
+
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eagle_core::MetricType;
+
+    use super::*;
+
+    fn metric(tags: &[(&str, &str)]) -> Metric {
+        let mut builder = MetricBuilder::counter("http", "requests", 1f64);
+
+        for (name, value) in tags {
+            builder = builder.add_tag(name, value);
+        }
+
+        builder.build()
+    }
+
+    /// Tags of the metrics let through, `None` for dropped ones.
+    fn transform(
+        cardinality: &mut Cardinality,
+        series: &[&[(&str, &str)]],
+    ) -> Vec<Option<Vec<(String, String)>>> {
+        series
+            .iter()
+            .map(|tags| {
+                cardinality
+                    .transform(Arc::new(Origin::new("test")), metric(tags))
+                    .map(|metric| metric.tags.into_iter().collect())
+            })
+            .collect()
+    }
+
+    fn tags(tags: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(
+            tags.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn drops_new_series_past_the_limit() {
+        let mut cardinality = Cardinality::new(2, LimitAction::Drop);
+        let series: &[&[(&str, &str)]] = &[
+            &[("path", "/a")],
+            &[("path", "/b")],
+            &[("path", "/c")],
+            &[("path", "/a")],
+            &[("path", "/b")],
+            &[],
+        ];
+
+        assert_eq!(
+            transform(&mut cardinality, series),
+            vec![
+                tags(&[("path", "/a")]),
+                tags(&[("path", "/b")]),
+                None,
+                tags(&[("path", "/a")]),
+                tags(&[("path", "/b")]),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn limits_each_metric_name() {
+        let mut cardinality = Cardinality::new(1, LimitAction::Drop);
+        let origin = Arc::new(Origin::new("test"));
+
+        assert!(cardinality
+            .transform(origin.clone(), metric(&[("path", "/a")]))
+            .is_some());
+
+        let other = MetricBuilder::counter("http", "errors", 1f64)
+            .add_tag("path", "/b")
+            .build();
+
+        assert!(cardinality.transform(origin.clone(), other).is_some());
+        assert!(cardinality
+            .transform(origin, metric(&[("path", "/b")]))
+            .is_none());
+    }
+
+    #[test]
+    fn strips_the_tag_with_the_most_values() {
+        let mut cardinality = Cardinality::new(2, LimitAction::StripTag);
+        let series: &[&[(&str, &str)]] = &[
+            &[("host", "web-1"), ("request", "1")],
+            &[("host", "web-1"), ("request", "2")],
+            &[("host", "web-1"), ("request", "3")],
+            &[("host", "web-1"), ("request", "2")],
+        ];
+
+        assert_eq!(
+            transform(&mut cardinality, series),
+            vec![
+                tags(&[("host", "web-1"), ("request", "1")]),
+                tags(&[("host", "web-1"), ("request", "2")]),
+                tags(&[("host", "web-1")]),
+                tags(&[("host", "web-1"), ("request", "2")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn caps_stripped_series() {
+        let mut cardinality = Cardinality::new(2, LimitAction::StripTag);
+        let series: &[&[(&str, &str)]] = &[
+            &[("host", "web-1"), ("request", "1")],
+            &[("host", "web-1"), ("request", "2")],
+            &[("host", "web-2"), ("request", "3")],
+            &[("host", "web-3"), ("request", "4")],
+            // Both stripped series are taken, and so is the one without tags.
+            &[("host", "web-4"), ("request", "5")],
+            &[("host", "web-2"), ("request", "6")],
+        ];
+
+        assert_eq!(
+            transform(&mut cardinality, series),
+            vec![
+                tags(&[("host", "web-1"), ("request", "1")]),
+                tags(&[("host", "web-1"), ("request", "2")]),
+                tags(&[("host", "web-2")]),
+                tags(&[("host", "web-3")]),
+                None,
+                tags(&[("host", "web-2")]),
+            ]
+        );
+
+        assert_eq!(cardinality.metrics["requests"].stripped.len(), 2);
+    }
+
+    #[test]
+    fn reports_limited_series() {
+        let mut cardinality =
+            Cardinality::new(1, LimitAction::StripTag).report_interval(Duration::ZERO);
+        let origin = Arc::new(Origin::new("test"));
+        let mut report = Vec::new();
+
+        for tags in [
+            &[("host", "web-1"), ("request", "1")][..],
+            &[("host", "web-1"), ("request", "2")],
+            &[("host", "web-2"), ("request", "3")],
+        ] {
+            report = cardinality.transform_many(origin.clone(), metric(tags));
+        }
+
+        let report = report
+            .iter()
+            .map(|metric| {
+                (
+                    metric.category.as_str(),
+                    metric.name.as_str(),
+                    metric.value,
+                    metric.r#type,
+                    metric.tags.clone().into_iter().collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            report,
+            vec![
+                (
+                    "cardinality",
+                    "series_dropped",
+                    1f64,
+                    MetricType::Counter,
+                    tags(&[("metric", "requests")]).unwrap(),
+                ),
+                (
+                    "cardinality",
+                    "tags_stripped",
+                    1f64,
+                    MetricType::Counter,
+                    tags(&[("metric", "requests"), ("tag", "request")]).unwrap(),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_once_per_interval() {
+        let mut cardinality = Cardinality::new(1, LimitAction::Drop);
+        let origin = Arc::new(Origin::new("test"));
+
+        cardinality.transform_many(origin.clone(), metric(&[("path", "/a")]));
+
+        assert!(cardinality
+            .transform_many(origin.clone(), metric(&[("path", "/b")]))
+            .is_empty());
+
+        cardinality.last_report -= Duration::from_secs(60);
+
+        let report = cardinality.transform_many(origin.clone(), metric(&[("path", "/a")]));
+
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[1].name, "series_dropped");
+        assert!(cardinality
+            .transform_many(origin, metric(&[("path", "/c")]))
+            .is_empty());
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use eagle_core::{Metric, MetricType, Origin, Transformer};
+use serde::{Deserialize, Serialize};
+
+#[derive(Eq, PartialEq, Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateMode {
+    /// Emits the difference with the previous value as a delta counter.
+    Delta,
+    /// Emits the per-second rate of change since the previous value as a gauge.
+    Rate,
+}
+
+#[derive(Hash, Eq, PartialEq)]
+struct SeriesKey {
+    category: String,
+    name: String,
+    tags: BTreeMap<String, String>,
+}
+
+struct Sample {
+    value: f64,
+    timestamp: DateTime<Utc>,
+    /// When the transformer got the sample, which can differ from the metric timestamp.
+    seen: DateTime<Utc>,
+    /// Time between the last two samples of the series, if it had more than one.
+    interval: Option<Duration>,
+}
+
+/// How often series that stopped reporting are looked for.
+const SWEEP_PERIOD_IN_SECS: i64 = 30;
+
+/// Interval assumed for series seen only once.
+const DEFAULT_INTERVAL_IN_SECS: i64 = 60;
+
+/// Turns cumulative values into deltas or per-second rates.
+///
+/// The first value of a series is only used as a reference point and is
+/// filtered out. Series not seen for `stale_after` of their intervals are
+/// forgotten, so their next value is a first value again.
+pub struct Rate {
+    mode: RateMode,
+    metrics: Vec<String>,
+    wraparound: Option<f64>,
+    suffix: Option<String>,
+    stale_after: u32,
+    previous: HashMap<SeriesKey, Sample>,
+    last_sweep: Option<DateTime<Utc>>,
+}
+
+impl Rate {
+    pub fn new(mode: RateMode) -> Self {
+        Self {
+            mode,
+            metrics: Vec::new(),
+            wraparound: None,
+            suffix: None,
+            stale_after: 5,
+            previous: HashMap::new(),
+            last_sweep: None,
+        }
+    }
+
+    /// Metric names this transformer applies to, gauges included. When empty,
+    /// only counters are converted.
+    pub fn metrics(self, metrics: Vec<String>) -> Self {
+        Self { metrics, ..self }
+    }
+
+    /// Value at which the source counter wraps around. A decrease is only
+    /// considered a wrap when the resulting delta is below half that value,
+    /// otherwise it's a counter reset, like when not set.
+    pub fn wraparound(self, wraparound: Option<f64>) -> Self {
+        Self { wraparound, ..self }
+    }
+
+    pub fn suffix(self, suffix: Option<String>) -> Self {
+        Self { suffix, ..self }
+    }
+
+    /// Number of intervals after which a series that stopped reporting is
+    /// forgotten. Defaults to 5.
+    pub fn stale_after(self, stale_after: u32) -> Self {
+        Self {
+            stale_after: stale_after.max(1),
+            ..self
+        }
+    }
+
+    fn is_handled(&self, metric: &Metric) -> bool {
+        if self.metrics.is_empty() {
+            return metric.r#type == MetricType::Counter;
+        }
+
+        self.metrics.iter().any(|name| name == &metric.name)
+    }
+
+    fn sweep(&mut self, now: DateTime<Utc>) {
+        let last_sweep = *self.last_sweep.get_or_insert(now);
+
+        if now - last_sweep < Duration::seconds(SWEEP_PERIOD_IN_SECS) {
+            return;
+        }
+
+        let stale_after = self.stale_after as i32;
+
+        self.previous.retain(|_, sample| {
+            let interval = sample
+                .interval
+                .unwrap_or_else(|| Duration::seconds(DEFAULT_INTERVAL_IN_SECS));
+
+            now - sample.seen <= interval * stale_after
+        });
+
+        self.last_sweep = Some(now);
+    }
+
+    fn delta(&self, previous: f64, value: f64) -> f64 {
+        if value >= previous {
+            return value - previous;
+        }
+
+        match self.wraparound {
+            Some(max) if max - previous + value < max / 2f64 => max - previous + value,
+            // Counter reset, everything accumulated since then is new.
+            _ => value,
+        }
+    }
+
+    fn transform_at(&mut self, mut metric: Metric, now: DateTime<Utc>) -> Option<Metric> {
+        self.sweep(now);
+
+        if !self.is_handled(&metric) {
+            return Some(metric);
+        }
+
+        let key = SeriesKey {
+            category: metric.category.clone(),
+            name: metric.name.clone(),
+            tags: metric.tags.clone(),
+        };
+
+        let interval = self.previous.get(&key).map(|previous| now - previous.seen);
+
+        let current = Sample {
+            value: metric.value,
+            timestamp: metric.timestamp,
+            seen: now,
+            interval,
+        };
+
+        let previous = self.previous.insert(key, current)?;
+        let delta = self.delta(previous.value, metric.value);
+
+        match self.mode {
+            RateMode::Delta => {
+                metric.value = delta;
+                metric.r#type = MetricType::Delta;
+            }
+
+            RateMode::Rate => {
+                let elapsed = (metric.timestamp - previous.timestamp).num_milliseconds();
+
+                if elapsed <= 0 {
+                    return None;
+                }
+
+                metric.value = delta * 1_000f64 / elapsed as f64;
+                metric.r#type = MetricType::Gauge;
+            }
+        }
+
+        if let Some(suffix) = self.suffix.as_ref() {
+            metric.name.push_str(suffix);
+        }
+
+        Some(metric)
+    }
+}
+
+impl Transformer for Rate {
+    fn transform(&mut self, _origin: Arc<Origin>, metric: Metric) -> Option<Metric> {
+        self.transform_at(metric, Utc::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use eagle_core::MetricBuilder;
+
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    fn counter(value: f64, seconds: i64) -> Metric {
+        MetricBuilder::counter("net", "bytes", value)
+            .timestamp(at(seconds))
+            .build()
+    }
+
+    fn values(rate: &mut Rate, samples: &[(f64, i64)]) -> Vec<Option<f64>> {
+        samples
+            .iter()
+            .map(|(value, seconds)| {
+                rate.transform_at(counter(*value, *seconds), at(*seconds))
+                    .map(|metric| metric.value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drops_first_sample() {
+        let mut rate = Rate::new(RateMode::Delta);
+
+        assert_eq!(values(&mut rate, &[(10f64, 0)]), vec![None]);
+    }
+
+    #[test]
+    fn emits_delta() {
+        let mut rate = Rate::new(RateMode::Delta);
+        let metric = rate.transform_at(counter(10f64, 0), at(0));
+
+        assert!(metric.is_none());
+
+        let metric = rate.transform_at(counter(25f64, 10), at(10)).unwrap();
+
+        assert_eq!(metric.value, 15f64);
+        assert_eq!(metric.r#type, MetricType::Delta);
+        assert_eq!(metric.name, "bytes");
+    }
+
+    #[test]
+    fn handles_reset() {
+        let mut rate = Rate::new(RateMode::Delta);
+
+        assert_eq!(
+            values(&mut rate, &[(100f64, 0), (7f64, 10)]),
+            vec![None, Some(7f64)]
+        );
+    }
+
+    #[test]
+    fn handles_wraparound() {
+        let mut rate = Rate::new(RateMode::Delta).wraparound(Some(1_000f64));
+
+        assert_eq!(
+            values(&mut rate, &[(990f64, 0), (5f64, 10)]),
+            vec![None, Some(15f64)]
+        );
+    }
+
+    #[test]
+    fn implausible_wraparound_is_a_reset() {
+        let mut rate = Rate::new(RateMode::Delta).wraparound(Some(1_000f64));
+
+        // Wrapping would mean 990 units in one interval, it's rather a restart.
+        assert_eq!(
+            values(&mut rate, &[(20f64, 0), (10f64, 10)]),
+            vec![None, Some(10f64)]
+        );
+    }
+
+    #[test]
+    fn emits_rate() {
+        let mut rate = Rate::new(RateMode::Rate);
+        let metric = rate.transform_at(counter(0f64, 0), at(0));
+
+        assert!(metric.is_none());
+
+        let metric = rate.transform_at(counter(50f64, 10), at(10)).unwrap();
+
+        assert_eq!(metric.value, 5f64);
+        assert_eq!(metric.r#type, MetricType::Gauge);
+    }
+
+    #[test]
+    fn drops_rate_without_elapsed_time() {
+        let mut rate = Rate::new(RateMode::Rate);
+
+        assert_eq!(
+            values(&mut rate, &[(0f64, 10), (50f64, 10)]),
+            vec![None, None]
+        );
+    }
+
+    #[test]
+    fn appends_suffix() {
+        let mut rate = Rate::new(RateMode::Rate).suffix(Some("_per_sec".to_string()));
+        let metric = rate.transform_at(counter(0f64, 0), at(0));
+
+        assert!(metric.is_none());
+
+        let metric = rate.transform_at(counter(10f64, 10), at(10)).unwrap();
+
+        assert_eq!(metric.name, "bytes_per_sec");
+    }
+
+    #[test]
+    fn forgets_stale_series() {
+        let mut rate = Rate::new(RateMode::Delta).stale_after(2);
+
+        assert_eq!(
+            values(&mut rate, &[(0f64, 0), (10f64, 10)]),
+            vec![None, Some(10f64)]
+        );
+
+        // Seen every 10 seconds, so forgotten after 20 seconds of silence.
+        assert_eq!(values(&mut rate, &[(30f64, 60)]), vec![None]);
+    }
+
+    #[test]
+    fn ignores_gauges() {
+        let mut rate = Rate::new(RateMode::Delta);
+        let gauge = MetricBuilder::gauge("host", "load", 1f64).build();
+        let metric = rate.transform_at(gauge, at(0)).unwrap();
+
+        assert_eq!(metric.value, 1f64);
+        assert_eq!(metric.r#type, MetricType::Gauge);
+    }
+}
@@ -0,0 +1,459 @@
+use std::sync::Arc;
+
+use eagle_core::{Metric, Origin, Transformer};
+use eyre::WrapErr;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Label name used to read or write the metric name.
+pub const NAME_LABEL: &str = "__name__";
+
+/// Label name used to read or write the metric category.
+pub const CATEGORY_LABEL: &str = "__category__";
+
+#[derive(Eq, PartialEq, Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelabelAction {
+    Replace,
+    LabelDrop,
+    LabelKeep,
+    Drop,
+    Keep,
+    Lowercase,
+    HashMod,
+}
+
+pub struct RelabelRule {
+    action: RelabelAction,
+    source_labels: Vec<String>,
+    separator: String,
+    regex: Regex,
+    target_label: Option<String>,
+    replacement: String,
+    modulus: u64,
+}
+
+impl RelabelRule {
+    pub fn new(action: RelabelAction) -> Self {
+        Self {
+            action,
+            source_labels: Vec::new(),
+            separator: ";".to_string(),
+            regex: Regex::new("^(?:(.*))$").unwrap(),
+            target_label: None,
+            replacement: "$1".to_string(),
+            modulus: 1,
+        }
+    }
+
+    pub fn source_labels(self, source_labels: Vec<String>) -> Self {
+        Self {
+            source_labels,
+            ..self
+        }
+    }
+
+    pub fn separator(self, separator: impl AsRef<str>) -> Self {
+        Self {
+            separator: separator.as_ref().to_string(),
+            ..self
+        }
+    }
+
+    /// Like Prometheus, the expression is anchored on both ends.
+    pub fn regex(self, regex: impl AsRef<str>) -> eyre::Result<Self> {
+        let regex = Regex::new(format!("^(?:{})$", regex.as_ref()).as_str())
+            .wrap_err_with(|| format!("Invalid relabel regex '{}'", regex.as_ref()))?;
+
+        Ok(Self { regex, ..self })
+    }
+
+    pub fn target_label(self, target_label: impl AsRef<str>) -> Self {
+        Self {
+            target_label: Some(target_label.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    pub fn replacement(self, replacement: impl AsRef<str>) -> Self {
+        Self {
+            replacement: replacement.as_ref().to_string(),
+            ..self
+        }
+    }
+
+    /// Used by `hashmod` rules.
+    ///
+    /// Panics if `modulus` is zero.
+    pub fn modulus(self, modulus: u64) -> Self {
+        assert!(modulus > 0, "A hashmod modulus can't be zero");
+
+        Self { modulus, ..self }
+    }
+
+    fn source_value(&self, metric: &Metric) -> String {
+        self.source_labels
+            .iter()
+            .map(|label| label_value(metric, label).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(self.separator.as_str())
+    }
+
+    /// Returns `false` if the metric must be dropped.
+    fn apply(&self, metric: &mut Metric) -> bool {
+        match self.action {
+            RelabelAction::Replace => {
+                let value = self.source_value(metric);
+
+                if let (Some(captures), Some(target)) = (
+                    self.regex.captures(value.as_str()),
+                    self.target_label.as_ref(),
+                ) {
+                    let mut result = String::new();
+                    captures.expand(self.replacement.as_str(), &mut result);
+                    set_label(metric, target, result);
+                }
+            }
+
+            RelabelAction::LabelDrop => {
+                metric.tags.retain(|name, _| !self.regex.is_match(name));
+            }
+
+            RelabelAction::LabelKeep => {
+                metric.tags.retain(|name, _| self.regex.is_match(name));
+            }
+
+            RelabelAction::Drop => {
+                return !self.regex.is_match(self.source_value(metric).as_str());
+            }
+
+            RelabelAction::Keep => {
+                return self.regex.is_match(self.source_value(metric).as_str());
+            }
+
+            RelabelAction::Lowercase => {
+                if let Some(target) = self.target_label.as_ref() {
+                    let value = self.source_value(metric).to_lowercase();
+                    set_label(metric, target, value);
+                }
+            }
+
+            RelabelAction::HashMod => {
+                if let Some(target) = self.target_label.as_ref() {
+                    let hash = fnv1a(self.source_value(metric).as_bytes());
+                    set_label(metric, target, (hash % self.modulus).to_string());
+                }
+            }
+        }
+
+        true
+    }
+}
+
+fn label_value(metric: &Metric, label: &str) -> Option<String> {
+    match label {
+        NAME_LABEL => Some(metric.name.clone()),
+        CATEGORY_LABEL => Some(metric.category.clone()),
+        tag => metric.tags.get(tag).cloned(),
+    }
+}
+
+fn set_label(metric: &mut Metric, label: &str, value: String) {
+    match label {
+        NAME_LABEL => metric.name = value,
+        CATEGORY_LABEL => metric.category = value,
+        tag => {
+            if value.is_empty() {
+                metric.tags.remove(tag);
+            } else {
+                metric.tags.insert(tag.to_string(), value);
+            }
+        }
+    }
+}
+
+/// We need a hash that stays stable across releases and hosts so sharding
+/// decisions don't move around.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+/// Prometheus-style relabeling. Rules are applied in order and the metric is
+/// dropped as soon as a `drop` or `keep` rule says so.
+pub struct Relabel {
+    rules: Vec<RelabelRule>,
+}
+
+impl Relabel {
+    pub fn new(rules: Vec<RelabelRule>) -> Self {
+        Self { rules }
+    }
+}
+
+impl Transformer for Relabel {
+    fn transform(&mut self, _origin: Arc<Origin>, mut metric: Metric) -> Option<Metric> {
+        for rule in self.rules.iter() {
+            if !rule.apply(&mut metric) {
+                return None;
+            }
+        }
+
+        Some(metric)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use eagle_core::MetricBuilder;
+
+    use super::*;
+
+    /// Name, category and tags of the relabeled metric, if it's kept.
+    type Expected<'a> = Option<(&'a str, &'a str, Vec<(&'a str, &'a str)>)>;
+
+    fn metric() -> Metric {
+        MetricBuilder::gauge("Host", "cpu_usage", 1f64)
+            .add_tag("instance", "web-1:9100")
+            .add_tag("job", "Node")
+            .add_tag("env", "prod")
+            .build()
+    }
+
+    fn labels(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn relabel(rules: Vec<RelabelRule>, metric: Metric) -> Option<Metric> {
+        Relabel::new(rules).transform(Arc::new(Origin::new("test")), metric)
+    }
+
+    fn expected(expected: &Expected<'_>) -> Option<(String, String, BTreeMap<String, String>)> {
+        expected.as_ref().map(|(name, category, tags)| {
+            let tags = tags
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+
+            (name.to_string(), category.to_string(), tags)
+        })
+    }
+
+    #[test]
+    fn applies_rules() {
+        let cases: Vec<(&str, Vec<RelabelRule>, Expected)> = vec![
+            (
+                "replace with capture groups",
+                vec![RelabelRule::new(RelabelAction::Replace)
+                    .source_labels(labels(&["instance"]))
+                    .regex("([^:]+):(\\d+)")
+                    .unwrap()
+                    .target_label("host")
+                    .replacement("$1 on $2")],
+                Some((
+                    "cpu_usage",
+                    "Host",
+                    vec![
+                        ("env", "prod"),
+                        ("host", "web-1 on 9100"),
+                        ("instance", "web-1:9100"),
+                        ("job", "Node"),
+                    ],
+                )),
+            ),
+            (
+                "replace joining labels",
+                vec![RelabelRule::new(RelabelAction::Replace)
+                    .source_labels(labels(&[CATEGORY_LABEL, NAME_LABEL]))
+                    .separator(".")
+                    .target_label(NAME_LABEL)],
+                Some((
+                    "Host.cpu_usage",
+                    "Host",
+                    vec![("env", "prod"), ("instance", "web-1:9100"), ("job", "Node")],
+                )),
+            ),
+            (
+                "replace not matching",
+                vec![RelabelRule::new(RelabelAction::Replace)
+                    .source_labels(labels(&["job"]))
+                    .regex("node")
+                    .unwrap()
+                    .target_label("job")
+                    .replacement("")],
+                Some((
+                    "cpu_usage",
+                    "Host",
+                    vec![("env", "prod"), ("instance", "web-1:9100"), ("job", "Node")],
+                )),
+            ),
+            (
+                "replace with an empty value",
+                vec![RelabelRule::new(RelabelAction::Replace)
+                    .source_labels(labels(&["job"]))
+                    .target_label("env")
+                    .replacement("")],
+                Some((
+                    "cpu_usage",
+                    "Host",
+                    vec![("instance", "web-1:9100"), ("job", "Node")],
+                )),
+            ),
+            (
+                "labeldrop",
+                vec![RelabelRule::new(RelabelAction::LabelDrop)
+                    .regex("env|job")
+                    .unwrap()],
+                Some(("cpu_usage", "Host", vec![("instance", "web-1:9100")])),
+            ),
+            (
+                "labelkeep",
+                vec![RelabelRule::new(RelabelAction::LabelKeep)
+                    .regex("j.*")
+                    .unwrap()],
+                Some(("cpu_usage", "Host", vec![("job", "Node")])),
+            ),
+            (
+                "drop matching",
+                vec![RelabelRule::new(RelabelAction::Drop)
+                    .source_labels(labels(&["env"]))
+                    .regex("prod")
+                    .unwrap()],
+                None,
+            ),
+            (
+                "drop only matching whole values",
+                vec![RelabelRule::new(RelabelAction::Drop)
+                    .source_labels(labels(&["env"]))
+                    .regex("pro")
+                    .unwrap()],
+                Some((
+                    "cpu_usage",
+                    "Host",
+                    vec![("env", "prod"), ("instance", "web-1:9100"), ("job", "Node")],
+                )),
+            ),
+            (
+                "keep matching",
+                vec![RelabelRule::new(RelabelAction::Keep)
+                    .source_labels(labels(&[NAME_LABEL]))
+                    .regex("cpu_.*")
+                    .unwrap()],
+                Some((
+                    "cpu_usage",
+                    "Host",
+                    vec![("env", "prod"), ("instance", "web-1:9100"), ("job", "Node")],
+                )),
+            ),
+            (
+                "keep only matching whole values",
+                vec![RelabelRule::new(RelabelAction::Keep)
+                    .source_labels(labels(&[NAME_LABEL]))
+                    .regex("cpu")
+                    .unwrap()],
+                None,
+            ),
+            (
+                "lowercase",
+                vec![RelabelRule::new(RelabelAction::Lowercase)
+                    .source_labels(labels(&["job"]))
+                    .target_label("job")],
+                Some((
+                    "cpu_usage",
+                    "Host",
+                    vec![("env", "prod"), ("instance", "web-1:9100"), ("job", "node")],
+                )),
+            ),
+            (
+                "rules in order",
+                vec![
+                    RelabelRule::new(RelabelAction::Lowercase)
+                        .source_labels(labels(&[CATEGORY_LABEL]))
+                        .target_label(CATEGORY_LABEL),
+                    RelabelRule::new(RelabelAction::Replace)
+                        .source_labels(labels(&[CATEGORY_LABEL]))
+                        .target_label("category"),
+                    RelabelRule::new(RelabelAction::LabelKeep)
+                        .regex("category")
+                        .unwrap(),
+                    RelabelRule::new(RelabelAction::Keep)
+                        .source_labels(labels(&["category", "env"]))
+                        .regex("host;")
+                        .unwrap(),
+                ],
+                Some(("cpu_usage", "host", vec![("category", "host")])),
+            ),
+            (
+                "rules after a drop",
+                vec![
+                    RelabelRule::new(RelabelAction::Drop)
+                        .source_labels(labels(&["job"]))
+                        .regex("Node")
+                        .unwrap(),
+                    RelabelRule::new(RelabelAction::Keep)
+                        .source_labels(labels(&["job"]))
+                        .regex("Node")
+                        .unwrap(),
+                ],
+                None,
+            ),
+        ];
+
+        for (description, rules, expected_metric) in cases {
+            let relabeled =
+                relabel(rules, metric()).map(|metric| (metric.name, metric.category, metric.tags));
+
+            assert_eq!(relabeled, expected(&expected_metric), "{}", description);
+        }
+    }
+
+    #[test]
+    fn shards_with_hashmod() {
+        let shard = |instance: &str| {
+            let metric = MetricBuilder::gauge("host", "cpu_usage", 1f64)
+                .add_tag("instance", instance)
+                .build();
+
+            let rule = RelabelRule::new(RelabelAction::HashMod)
+                .source_labels(labels(&["instance"]))
+                .target_label("shard")
+                .modulus(4);
+
+            relabel(vec![rule], metric).unwrap().tags["shard"].clone()
+        };
+
+        // FNV-1a must keep giving the same shards, or series would move between them.
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+
+        let cases = vec![("web-1:9100", "1"), ("web-2:9100", "2"), ("db-1:9100", "3")];
+
+        for (instance, expected) in cases {
+            assert_eq!(shard(instance), expected, "sharding {}", instance);
+            assert_eq!(shard(instance), shard(instance));
+        }
+
+        let shards = (0..100)
+            .map(|index| shard(format!("web-{}:9100", index).as_str()))
+            .collect::<std::collections::BTreeSet<_>>();
+
+        assert_eq!(
+            shards.into_iter().collect::<Vec<_>>(),
+            vec!["0", "1", "2", "3"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "A hashmod modulus can't be zero")]
+    fn rejects_zero_modulus() {
+        RelabelRule::new(RelabelAction::HashMod).modulus(0);
+    }
+}
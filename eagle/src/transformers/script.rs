@@ -0,0 +1,532 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use chrono::{TimeZone, Utc};
+use eagle_core::{Log, Metric, MetricType, Origin, Transformer};
+use eyre::bail;
+use mlua::{Function, HookTriggers, Lua, LuaOptions, LuaSerdeExt, RegistryKey, StdLib, Table};
+use serde_json::Value;
+
+/// How often, in Lua VM instructions, the instruction limit is checked.
+const HOOK_PERIOD: u32 = 1_000;
+
+pub struct ScriptLimits {
+    pub max_instructions: u64,
+    pub max_memory: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_instructions: 1_000_000,
+            max_memory: 16 * 1_024 * 1_024,
+        }
+    }
+}
+
+/// Metric fields read back from a Lua table. Missing fields are taken from the
+/// metric the script ran against.
+#[derive(Default)]
+struct MetricFields {
+    name: Option<String>,
+    value: Option<f64>,
+    r#type: Option<String>,
+    category: Option<String>,
+    tags: Option<BTreeMap<String, String>>,
+    timestamp: Option<i64>,
+}
+
+impl MetricFields {
+    fn from_table(table: Table) -> mlua::Result<Self> {
+        let tags = match table.get::<_, Option<Table>>("tags")? {
+            None => None,
+            Some(tags) => Some(
+                tags.pairs::<String, String>()
+                    .collect::<mlua::Result<BTreeMap<_, _>>>()?,
+            ),
+        };
+
+        Ok(Self {
+            name: table.get("name")?,
+            value: table.get("value")?,
+            r#type: table.get("type")?,
+            category: table.get("category")?,
+            tags,
+            timestamp: table.get("timestamp")?,
+        })
+    }
+
+    fn into_metric(self, original: &Metric) -> eyre::Result<Metric> {
+        let r#type = match self.r#type.as_deref() {
+            None => original.r#type,
+            Some("counter") => MetricType::Counter,
+            Some("gauge") => MetricType::Gauge,
+            Some("delta") => MetricType::Delta,
+            Some(unknown) => bail!("Unknown metric type '{}'", unknown),
+        };
+
+        let timestamp = match self.timestamp {
+            None => original.timestamp,
+            Some(millis) => match Utc.timestamp_millis_opt(millis).single() {
+                Some(timestamp) => timestamp,
+                None => bail!("Invalid metric timestamp: {}", millis),
+            },
+        };
+
+        Ok(Metric {
+            name: self.name.unwrap_or_else(|| original.name.clone()),
+            value: self.value.unwrap_or(original.value),
+            r#type,
+            category: self.category.unwrap_or_else(|| original.category.clone()),
+            tags: self.tags.unwrap_or_else(|| original.tags.clone()),
+            timestamp,
+        })
+    }
+}
+
+/// Log fields read back from a Lua table. Missing fields are taken from the log
+/// the script ran against.
+#[derive(Default)]
+struct LogFields {
+    value: Option<Value>,
+    metadata: Option<Value>,
+}
+
+impl LogFields {
+    fn from_table(lua: &Lua, table: Table) -> mlua::Result<Self> {
+        let value = match table.get::<_, mlua::Value>("value")? {
+            mlua::Value::Nil => None,
+            value => Some(lua.from_value(value)?),
+        };
+
+        let metadata = match table.get::<_, mlua::Value>("metadata")? {
+            mlua::Value::Nil => None,
+            metadata => Some(lua.from_value(metadata)?),
+        };
+
+        Ok(Self { value, metadata })
+    }
+
+    fn into_log(self, original: &Log) -> Log {
+        Log {
+            inner: match self.value {
+                Some(value) => Arc::new(value),
+                None => original.inner.clone(),
+            },
+            metadata: self.metadata.unwrap_or_else(|| original.metadata.clone()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct CallState {
+    /// Whether the script runs against a log, which tells what `emit` produces.
+    log: bool,
+    dropped: bool,
+    emitted: Vec<MetricFields>,
+    emitted_logs: Vec<LogFields>,
+}
+
+/// Runs a Lua script against every metric and every log.
+///
+/// The metric is exposed as the `metric` global table with the `name`, `value`,
+/// `type`, `category`, `tags` and `timestamp` (milliseconds since epoch) fields.
+/// Changes made to it are kept once the script completes. The script can also
+/// call `drop()` to filter the metric out and `emit({ ... })` to produce
+/// additional metrics.
+///
+/// Logs are exposed the same way as the `log` global table, with the `value`
+/// and `metadata` fields holding their JSON content, `emit` producing logs then.
+/// `metric` is `nil` when the script runs against a log and `log` is `nil` when
+/// it runs against a metric, so scripts only meant for metrics can start with
+/// `if metric == nil then return end`.
+///
+/// Only the `string`, `table`, `math` and `utf8` standard libraries are available.
+pub struct Script {
+    lua: Lua,
+    chunk: RegistryKey,
+    state: Arc<Mutex<CallState>>,
+    instructions: Arc<AtomicU64>,
+}
+
+impl Script {
+    /// `name` is used when reporting errors, along with the script line number.
+    pub fn new(
+        name: impl AsRef<str>,
+        source: impl AsRef<str>,
+        limits: ScriptLimits,
+    ) -> eyre::Result<Self> {
+        match Self::load(name.as_ref(), source.as_ref(), limits) {
+            Ok(script) => Ok(script),
+            Err(e) => bail!("Error when loading script '{}': {}", name.as_ref(), e),
+        }
+    }
+
+    fn load(name: &str, source: &str, limits: ScriptLimits) -> mlua::Result<Self> {
+        let lua = Lua::new_with(
+            StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8,
+            LuaOptions::default(),
+        )?;
+
+        let state = Arc::new(Mutex::new(CallState::default()));
+        let instructions = Arc::new(AtomicU64::new(0));
+
+        lua.set_memory_limit(limits.max_memory)?;
+
+        let counter = instructions.clone();
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(HOOK_PERIOD),
+            move |_, _| {
+                let count = counter.fetch_add(HOOK_PERIOD as u64, Ordering::Relaxed);
+
+                if count >= limits.max_instructions {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "script exceeded its limit of {} instructions",
+                        limits.max_instructions
+                    )));
+                }
+
+                Ok(())
+            },
+        );
+
+        let drop_state = state.clone();
+        let drop = lua.create_function(move |_, ()| {
+            drop_state.lock().unwrap().dropped = true;
+            Ok(())
+        })?;
+
+        let emit_state = state.clone();
+        let emit = lua.create_function(move |lua, table: Table| {
+            let mut state = emit_state.lock().unwrap();
+
+            if state.log {
+                let fields = LogFields::from_table(lua, table)?;
+                state.emitted_logs.push(fields);
+            } else {
+                let fields = MetricFields::from_table(table)?;
+                state.emitted.push(fields);
+            }
+
+            Ok(())
+        })?;
+
+        lua.globals().set("drop", drop)?;
+        lua.globals().set("emit", emit)?;
+
+        // The '=' prefix makes Lua report errors as 'name:line:' instead of quoting the source.
+        let chunk = lua
+            .load(source)
+            .set_name(format!("={}", name))
+            .into_function()?;
+        let chunk = lua.create_registry_value(chunk)?;
+
+        Ok(Self {
+            lua,
+            chunk,
+            state,
+            instructions,
+        })
+    }
+
+    fn to_table(&self, metric: &Metric) -> mlua::Result<Table<'_>> {
+        let table = self.lua.create_table()?;
+        let tags = self.lua.create_table()?;
+
+        for (key, value) in metric.tags.iter() {
+            tags.set(key.as_str(), value.as_str())?;
+        }
+
+        table.set("name", metric.name.as_str())?;
+        table.set("value", metric.value)?;
+        table.set(
+            "type",
+            match metric.r#type {
+                MetricType::Counter => "counter",
+                MetricType::Gauge => "gauge",
+                MetricType::Delta => "delta",
+            },
+        )?;
+        table.set("category", metric.category.as_str())?;
+        table.set("tags", tags)?;
+        table.set("timestamp", metric.timestamp.timestamp_millis())?;
+
+        Ok(table)
+    }
+
+    fn log_table(&self, log: &Log) -> mlua::Result<Table<'_>> {
+        let table = self.lua.create_table()?;
+
+        table.set("value", self.lua.to_value(log.inner.as_ref())?)?;
+        table.set("metadata", self.lua.to_value(&log.metadata)?)?;
+
+        Ok(table)
+    }
+
+    fn call(&self, metric: &Metric) -> mlua::Result<Option<MetricFields>> {
+        self.instructions.store(0, Ordering::Relaxed);
+        self.lua.globals().set("metric", self.to_table(metric)?)?;
+        self.lua.globals().set("log", mlua::Value::Nil)?;
+        self.lua
+            .registry_value::<Function>(&self.chunk)?
+            .call::<_, ()>(())?;
+
+        match self.lua.globals().get::<_, Option<Table>>("metric")? {
+            Some(table) => Ok(Some(MetricFields::from_table(table)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn run(&mut self, metric: &Metric) -> eyre::Result<Vec<Metric>> {
+        let outcome = self.call(metric);
+        let state = std::mem::take(&mut *self.state.lock().unwrap());
+
+        let fields = match outcome {
+            Ok(fields) => fields,
+            Err(e) => bail!("Error when running script: {}", e),
+        };
+
+        let mut metrics = Vec::with_capacity(state.emitted.len() + 1);
+
+        // Setting 'metric' to nil is another way of dropping it.
+        if let (false, Some(fields)) = (state.dropped, fields) {
+            metrics.push(fields.into_metric(metric)?);
+        }
+
+        for fields in state.emitted {
+            metrics.push(fields.into_metric(metric)?);
+        }
+
+        Ok(metrics)
+    }
+
+    fn call_log(&self, log: &Log) -> mlua::Result<Option<LogFields>> {
+        self.instructions.store(0, Ordering::Relaxed);
+        self.state.lock().unwrap().log = true;
+        self.lua.globals().set("log", self.log_table(log)?)?;
+        self.lua.globals().set("metric", mlua::Value::Nil)?;
+        self.lua
+            .registry_value::<Function>(&self.chunk)?
+            .call::<_, ()>(())?;
+
+        match self.lua.globals().get::<_, Option<Table>>("log")? {
+            Some(table) => Ok(Some(LogFields::from_table(&self.lua, table)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn run_log(&mut self, log: &Log) -> eyre::Result<Vec<Log>> {
+        let outcome = self.call_log(log);
+        let state = std::mem::take(&mut *self.state.lock().unwrap());
+
+        let fields = match outcome {
+            Ok(fields) => fields,
+            Err(e) => bail!("Error when running script: {}", e),
+        };
+
+        let mut logs = Vec::with_capacity(state.emitted_logs.len() + 1);
+
+        // Setting 'log' to nil is another way of dropping it.
+        if let (false, Some(fields)) = (state.dropped, fields) {
+            logs.push(fields.into_log(log));
+        }
+
+        for fields in state.emitted_logs {
+            logs.push(fields.into_log(log));
+        }
+
+        Ok(logs)
+    }
+}
+
+impl Transformer for Script {
+    /// Extra metrics emitted by the script are only available through
+    /// `transform_many`.
+    fn transform(&mut self, origin: Arc<Origin>, metric: Metric) -> Option<Metric> {
+        self.transform_many(origin, metric).into_iter().next()
+    }
+
+    fn transform_many(&mut self, origin: Arc<Origin>, metric: Metric) -> Vec<Metric> {
+        match self.run(&metric) {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                tracing::error!(
+                    target = origin.instance_id(),
+                    "Metric {}:{} left untouched: {}",
+                    metric.category,
+                    metric.name,
+                    e
+                );
+
+                vec![metric]
+            }
+        }
+    }
+
+    fn transform_log(&mut self, origin: Arc<Origin>, log: Log) -> Vec<Log> {
+        match self.run_log(&log) {
+            Ok(logs) => logs,
+            Err(e) => {
+                tracing::error!(target = origin.instance_id(), "Log left untouched: {}", e);
+
+                vec![log]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eagle_core::MetricBuilder;
+    use serde_json::json;
+
+    use super::*;
+
+    fn script(source: &str) -> Script {
+        Script::new("test.lua", source, ScriptLimits::default()).unwrap()
+    }
+
+    fn metric() -> Metric {
+        MetricBuilder::gauge("host", "load", 1f64)
+            .tags(BTreeMap::from([("host".to_string(), "web01".to_string())]))
+            .build()
+    }
+
+    fn log(value: Value) -> Log {
+        Log {
+            inner: Arc::new(value),
+            metadata: json!({ "tag": "app" }),
+        }
+    }
+
+    #[test]
+    fn updates_metric() {
+        let mut script = script(
+            r#"
+            metric.value = metric.value * 2
+            metric.tags.env = "prod"
+            emit({ name = "load_copy" })
+            "#,
+        );
+
+        let metrics = script.run(&metric()).unwrap();
+
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].value, 2f64);
+        assert_eq!(metrics[0].tags.get("env").map(String::as_str), Some("prod"));
+        assert_eq!(metrics[1].name, "load_copy");
+        assert_eq!(metrics[1].value, 1f64);
+    }
+
+    #[test]
+    fn drops_metric() {
+        let mut script = script("drop()");
+
+        assert!(script.run(&metric()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn updates_log() {
+        let mut script = script(
+            r#"
+            if log == nil then return end
+            log.value.level = string.upper(log.value.level)
+            log.metadata.parsed = true
+            emit({ value = "audit: " .. log.value.message })
+            "#,
+        );
+
+        let logs = script
+            .run_log(&log(
+                json!({ "level": "warn", "message": "disk", "extra": null }),
+            ))
+            .unwrap();
+
+        assert_eq!(logs.len(), 2);
+        assert_eq!(
+            logs[0].inner.as_ref(),
+            &json!({ "level": "WARN", "message": "disk", "extra": null })
+        );
+        assert_eq!(logs[0].metadata, json!({ "tag": "app", "parsed": true }));
+        assert_eq!(logs[1].inner.as_ref(), &json!("audit: disk"));
+        assert_eq!(logs[1].metadata, json!({ "tag": "app" }));
+
+        // Metrics go through the same script untouched.
+        assert_eq!(script.run(&metric()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn drops_log() {
+        let mut script = script("if log.metadata.tag == 'app' then log = nil end");
+
+        assert!(script.run_log(&log(json!("line"))).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_errors_with_name_and_line() {
+        let mut script = script("local x = 1\nerror('boom')");
+        let error = script.run(&metric()).unwrap_err().to_string();
+
+        assert!(error.contains("test.lua:2:"), "{}", error);
+        assert!(error.contains("boom"), "{}", error);
+
+        let error = Script::new("broken.lua", "metric.value = (", ScriptLimits::default())
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert!(error.contains("broken.lua:1:"), "{}", error);
+    }
+
+    #[test]
+    fn enforces_instruction_limit() {
+        let limits = ScriptLimits {
+            max_instructions: 10_000,
+            ..ScriptLimits::default()
+        };
+
+        let mut script = Script::new("loop.lua", "while true do end", limits).unwrap();
+        let error = script.run(&metric()).unwrap_err().to_string();
+
+        assert!(error.contains("limit of 10000 instructions"), "{}", error);
+
+        // The count starts over with every call.
+        let mut script = Script::new(
+            "short.lua",
+            "for i = 1, 1000 do end",
+            ScriptLimits {
+                max_instructions: 10_000,
+                ..ScriptLimits::default()
+            },
+        )
+        .unwrap();
+
+        for _ in 0..20 {
+            assert_eq!(script.run(&metric()).unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn enforces_memory_limit() {
+        let limits = ScriptLimits {
+            max_memory: 1_024 * 1_024,
+            ..ScriptLimits::default()
+        };
+
+        let mut script = Script::new(
+            "memory.lua",
+            "local t = {} for i = 1, 1000000 do t[i] = string.rep('x', 64) .. i end",
+            limits,
+        )
+        .unwrap();
+
+        let error = script.run(&metric()).unwrap_err().to_string();
+
+        assert!(error.contains("memory"), "{}", error);
+    }
+}